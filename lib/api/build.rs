@@ -0,0 +1,88 @@
+use std::env;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const LIB_ROOT: &str = "../c-api";
+const ENCLAVE_ROOT: &str = "../../pod-enclave";
+const DEFAULT_SGX_SDK: &str = "/opt/intel/sgxsdk";
+const DEFAULT_SGX_SSL: &str = "/opt/intel/sgxssl";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=SGX_SDK");
+    println!("cargo:rerun-if-env-changed=SGX_SSL");
+    // Without SGX support there's nothing to build or link against.
+    if env::var_os("CARGO_FEATURE_SGX").is_none() {
+        return;
+    }
+    // Get some basic conf vars
+    let sgx_sdk = env::var("SGX_SDK").unwrap_or_else(|_| DEFAULT_SGX_SDK.to_string());
+    let sgx_sdk_path = Path::new(&sgx_sdk);
+    let sgx_ssl = env::var("SGX_SSL").unwrap_or_else(|_| DEFAULT_SGX_SSL.to_string());
+    let simulation = env::var_os("CARGO_FEATURE_SGX_SIM").is_some();
+    // Without the SDK we can still type check the crate (e.g., `cargo check`
+    // on a developer laptop), but we won't be able to link against it.
+    if !sgx_sdk_path.exists() {
+        println!(
+            "cargo:warning=SGX SDK not found in '{}' (set SGX_SDK to override); \
+             skipping build of libpod_sgx, the resulting binaries will fail to link",
+            sgx_sdk
+        );
+        return;
+    }
+    let lib_root = Path::new(LIB_ROOT);
+    let enclave_root = Path::new(ENCLAVE_ROOT);
+    for file in &["pod_log.c", "pod_log.h", "pod_sgx.c", "pod_sgx.h"] {
+        println!("cargo:rerun-if-changed={}", lib_root.join(file).display());
+    }
+    for file in &[
+        "pod_enclave.c",
+        "pod_enclave.h",
+        "pod_enclave.edl",
+        "ed25519ph.c",
+        "ed25519ph.h",
+        "Makefile",
+    ] {
+        println!(
+            "cargo:rerun-if-changed={}",
+            enclave_root.join(file).display()
+        );
+    }
+    // Run Makefile for `pod-enclave` first as we need it to build the
+    // c-api lib.
+    let mut cmd = Command::new("make");
+    cmd.stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .current_dir(enclave_root)
+        .env("SGX_SDK", &sgx_sdk)
+        .env("SGX_SSL", &sgx_ssl)
+        .env("SGX_MODE", if simulation { "SIM" } else { "HW" });
+    let status = cmd.status().expect("make pod-enclave should start");
+    assert!(status.success(), "make pod-enclave should succeed");
+    // Expose the MRSIGNER of the enclave to `SgxBackend::built_mr_signer`.
+    let mr_signer = std::fs::read(enclave_root.join("pod_enclave.mrsigner"))
+        .expect("make pod-enclave should produce pod_enclave.mrsigner");
+    let mr_signer: String = mr_signer.iter().map(|b| format!("{:02x}", b)).collect();
+    println!("cargo:rustc-env=POD_ENCLAVE_MR_SIGNER={}", mr_signer);
+    // Build c-api
+    cc::Build::new()
+        .files(&[
+            lib_root.join("pod_log.c"),
+            lib_root.join("pod_sgx.c"),
+            enclave_root.join("pod_enclave_u.c"),
+        ])
+        .include(sgx_sdk_path.join("include"))
+        .include(enclave_root)
+        .flag_if_supported("-D_GNU_SOURCE")
+        .flag_if_supported("-Wno-attributes")
+        .flag_if_supported("-std=c99")
+        .compile("libpod_sgx.a");
+    // Linker flags; simulation mode uses the `_sim` flavour of the untrusted
+    // runtime libs.
+    let suffix = if simulation { "_sim" } else { "" };
+    println!("cargo:rustc-flags=-l crypto");
+    println!("cargo:rustc-flags=-l sgx_urts{}", suffix);
+    println!("cargo:rustc-flags=-l sgx_uae_service{}", suffix);
+    println!("cargo:rustc-flags=-l sgx_usgxssl");
+    println!("cargo:rustc-flags=-L {}/lib64", sgx_sdk);
+    println!("cargo:rustc-flags=-L {}/lib64", sgx_ssl);
+}
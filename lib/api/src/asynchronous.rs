@@ -0,0 +1,341 @@
+use super::{
+    Ed25519PublicKey, Ed25519Signature, PodEnclave, PodError, QuoteType, RelyingParty, Result,
+    SigningPayload, Spid,
+};
+use futures_channel::oneshot;
+use futures_timer::Delay;
+use rust_sgx_util::Quote;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+type Job = Box<dyn FnOnce(&PodEnclave) + Send>;
+
+/// Async facade of [`PodEnclave`], available with the `async` feature.
+///
+/// The `PodEnclave` is owned by a dedicated worker thread, which runs the
+/// calls one at a time, so that no blocking FFI or AESM call ever runs on the
+/// async executor. The returned futures don't depend on any particular
+/// runtime, and can be awaited from tokio, actix or any other executor.
+///
+/// Dropping a future cancels its call, unless the worker has already started
+/// running it. With [`with_timeout`] set, calls which don't complete in time
+/// fail with [`PodError::Timeout`] and are cancelled likewise. The enclave is
+/// unloaded on the worker thread once the `AsyncPodEnclave` is dropped and the
+/// call in progress, if any, completes.
+///
+/// If a call panics, the worker unloads the enclave and stops: that call, and
+/// every call after it, fails with [`PodError::WorkerStopped`].
+///
+/// [`PodEnclave`]: struct.PodEnclave.html
+/// [`with_timeout`]: #method.with_timeout
+/// [`PodError::Timeout`]: enum.PodError.html#variant.Timeout
+/// [`PodError::WorkerStopped`]: enum.PodError.html#variant.WorkerStopped
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pod_api::{AsyncPodEnclave, PodEnclave, SigningPayload, SigningPurpose, SoftwareBackend};
+/// # use std::time::Duration;
+/// # fn main() -> pod_api::Result<()> {
+/// futures_executor::block_on(async {
+///     let pod_enclave = AsyncPodEnclave::load(|| {
+///         PodEnclave::with_backend(SoftwareBackend::new("secret"), "private_key.sealed")
+///     })
+///     .await?
+///     .with_timeout(Duration::from_secs(10));
+///
+///     let payload = SigningPayload::now(
+///         "https://example.com".parse()?,
+///         SigningPurpose::Authentication,
+///         &b"challenge"[..],
+///     );
+///     let signature = pod_enclave.sign_payload(payload.clone()).await?;
+///     payload.verify(&pod_enclave.public_key().await?, &signature)?;
+///     Ok::<_, pod_api::PodError>(())
+/// })
+/// # }
+/// ```
+pub struct AsyncPodEnclave {
+    jobs: Mutex<mpsc::Sender<Job>>,
+    timeout: Option<Duration>,
+}
+
+impl AsyncPodEnclave {
+    /// Moves the already loaded `pod_enclave` to a new worker thread.
+    pub fn new(pod_enclave: PodEnclave) -> Result<Self> {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        spawn_worker(move || run_jobs(&pod_enclave, receiver))?;
+        Ok(Self::from_sender(jobs))
+    }
+
+    /// Runs `load` on a new worker thread, e.g., [`PodEnclave::with_backend`],
+    /// so that loading the enclave doesn't block the executor either.
+    ///
+    /// [`PodEnclave::with_backend`]: struct.PodEnclave.html#method.with_backend
+    pub async fn load<F>(load: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<PodEnclave> + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let (loaded, reply) = oneshot::channel();
+        spawn_worker(move || {
+            let pod_enclave = match load() {
+                Ok(pod_enclave) => pod_enclave,
+                Err(err) => {
+                    let _ = loaded.send(Err(err));
+                    return;
+                }
+            };
+            let _ = loaded.send(Ok(()));
+            run_jobs(&pod_enclave, receiver);
+        })?;
+        Reply::new(reply, None).await?;
+        Ok(Self::from_sender(jobs))
+    }
+
+    fn from_sender(jobs: mpsc::Sender<Job>) -> Self {
+        Self {
+            jobs: Mutex::new(jobs),
+            timeout: None,
+        }
+    }
+
+    /// Sets how long calls may take, including the time spent waiting for
+    /// the calls submitted before them. By default, there's no timeout.
+    ///
+    /// Note that a call already running in the enclave can't be interrupted:
+    /// the worker finishes it before moving on to the next one.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs `f` with the `PodEnclave` on the worker thread, e.g., to make any
+    /// call which doesn't have an async counterpart here.
+    pub fn call<F, T>(&self, f: F) -> impl Future<Output = Result<T>>
+    where
+        F: FnOnce(&PodEnclave) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let job: Job = Box::new(move |pod_enclave| {
+            // Skip calls nobody waits for anymore.
+            if !sender.is_canceled() {
+                let _ = sender.send(f(pod_enclave));
+            }
+        });
+        // If the worker is gone, `receiver` is canceled along with the job.
+        let _ = self
+            .jobs
+            .lock()
+            .expect("AsyncPodEnclave mutex shouldn't be poisoned")
+            .send(job);
+        Reply::new(receiver, self.timeout)
+    }
+
+    /// See [`PodEnclave::public_key`].
+    ///
+    /// [`PodEnclave::public_key`]: struct.PodEnclave.html#method.public_key
+    pub async fn public_key(&self) -> Result<Ed25519PublicKey> {
+        self.call(|pod_enclave| pod_enclave.public_key()).await
+    }
+
+    /// See [`PodEnclave::public_key_for`].
+    ///
+    /// [`PodEnclave::public_key_for`]: struct.PodEnclave.html#method.public_key_for
+    pub async fn public_key_for(&self, relying_party: RelyingParty) -> Result<Ed25519PublicKey> {
+        self.call(move |pod_enclave| pod_enclave.public_key_for(&relying_party))
+            .await
+    }
+
+    /// See [`PodEnclave::get_quote`].
+    ///
+    /// [`PodEnclave::get_quote`]: struct.PodEnclave.html#method.get_quote
+    pub async fn get_quote(
+        &self,
+        spid: Spid,
+        quote_type: QuoteType,
+        user_data: Vec<u8>,
+    ) -> Result<Quote> {
+        self.call(move |pod_enclave| pod_enclave.get_quote(&spid, quote_type, user_data))
+            .await
+    }
+
+    /// See [`PodEnclave::get_quote_for`].
+    ///
+    /// [`PodEnclave::get_quote_for`]: struct.PodEnclave.html#method.get_quote_for
+    pub async fn get_quote_for(
+        &self,
+        relying_party: RelyingParty,
+        spid: Spid,
+        quote_type: QuoteType,
+        user_data: Vec<u8>,
+    ) -> Result<Quote> {
+        self.call(move |pod_enclave| {
+            pod_enclave.get_quote_for(&relying_party, &spid, quote_type, user_data)
+        })
+        .await
+    }
+
+    /// See [`PodEnclave::sign_payload`].
+    ///
+    /// [`PodEnclave::sign_payload`]: struct.PodEnclave.html#method.sign_payload
+    pub async fn sign_payload(&self, payload: SigningPayload) -> Result<Ed25519Signature> {
+        self.call(move |pod_enclave| pod_enclave.sign_payload(&payload))
+            .await
+    }
+
+    /// See [`PodEnclave::sign_payload_for`].
+    ///
+    /// [`PodEnclave::sign_payload_for`]: struct.PodEnclave.html#method.sign_payload_for
+    pub async fn sign_payload_for(
+        &self,
+        relying_party: RelyingParty,
+        payload: SigningPayload,
+    ) -> Result<Ed25519Signature> {
+        self.call(move |pod_enclave| pod_enclave.sign_payload_for(&relying_party, &payload))
+            .await
+    }
+
+    /// See [`PodEnclave::sign`]. Raw signing has to be enabled on the
+    /// `PodEnclave` before it's passed to [`new`].
+    ///
+    /// [`PodEnclave::sign`]: struct.PodEnclave.html#method.sign
+    /// [`new`]: #method.new
+    pub async fn sign(&self, message: Vec<u8>) -> Result<Ed25519Signature> {
+        self.call(move |pod_enclave| pod_enclave.sign(message))
+            .await
+    }
+}
+
+/// Runs `jobs` until the `AsyncPodEnclave` is dropped, or a job panics. In the
+/// latter case, the `PodEnclave` may be in any state, so the worker stops
+/// rather than go on using it; the pending jobs are dropped, which fails them
+/// with `PodError::WorkerStopped`.
+fn run_jobs(pod_enclave: &PodEnclave, jobs: mpsc::Receiver<Job>) {
+    for job in jobs {
+        if panic::catch_unwind(AssertUnwindSafe(|| job(pod_enclave))).is_err() {
+            log::error!("Enclave call panicked, stopping the worker");
+            return;
+        }
+    }
+}
+
+fn spawn_worker<F: FnOnce() + Send + 'static>(worker: F) -> Result<()> {
+    thread::Builder::new()
+        .name("pod-enclave".to_owned())
+        .spawn(worker)?;
+    Ok(())
+}
+
+/// Result of a call running on the worker thread. Dropping it cancels the
+/// call.
+struct Reply<T> {
+    receiver: oneshot::Receiver<Result<T>>,
+    timeout: Option<(Duration, Delay)>,
+}
+
+impl<T> Reply<T> {
+    fn new(receiver: oneshot::Receiver<Result<T>>, timeout: Option<Duration>) -> Self {
+        Self {
+            receiver,
+            timeout: timeout.map(|timeout| (timeout, Delay::new(timeout))),
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(reply) = Pin::new(&mut self.receiver).poll(cx) {
+            // The worker drops the sender without replying only if it's gone.
+            return Poll::Ready(reply.unwrap_or(Err(PodError::WorkerStopped)));
+        }
+        if let Some((timeout, delay)) = &mut self.timeout {
+            if Pin::new(delay).poll(cx).is_ready() {
+                return Poll::Ready(Err(PodError::Timeout(*timeout)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{payload, software_enclave};
+
+    #[test]
+    fn signs_on_worker() {
+        let (_dir, pod_enclave) = software_enclave();
+        futures_executor::block_on(async {
+            let pod_enclave = AsyncPodEnclave::new(pod_enclave)
+                .unwrap()
+                .with_timeout(Duration::from_secs(10));
+
+            let payload = payload("https://example.com");
+            let signature = pod_enclave.sign_payload(payload.clone()).await.unwrap();
+            payload
+                .verify(&pod_enclave.public_key().await.unwrap(), &signature)
+                .unwrap();
+
+            let report = pod_enclave
+                .call(|pod_enclave| pod_enclave.identity())
+                .await
+                .unwrap();
+            assert!(!report.is_debug());
+        });
+    }
+
+    #[test]
+    fn signs_for_relying_party_on_worker() {
+        let (_dir, pod_enclave) = software_enclave();
+        let origin: RelyingParty = "https://example.com".parse().unwrap();
+        let pub_key = pod_enclave.public_key_for(&origin).unwrap();
+        futures_executor::block_on(async {
+            let pod_enclave = AsyncPodEnclave::new(pod_enclave).unwrap();
+            assert_eq!(
+                pod_enclave.public_key_for(origin.clone()).await.unwrap(),
+                pub_key
+            );
+
+            let payload = payload("https://example.com");
+            let signature = pod_enclave
+                .sign_payload_for(origin.clone(), payload.clone())
+                .await
+                .unwrap();
+            payload.verify(&pub_key, &signature).unwrap();
+
+            let spid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+            let quote = pod_enclave
+                .get_quote_for(origin, spid, QuoteType::Unlinkable, b"nonce".to_vec())
+                .await
+                .unwrap();
+            assert_eq!(
+                Ed25519PublicKey::from_report_data(quote.report_data().unwrap(), b"nonce").unwrap(),
+                pub_key
+            );
+        });
+    }
+
+    #[test]
+    fn panicking_call_stops_worker() {
+        let (_dir, pod_enclave) = software_enclave();
+        futures_executor::block_on(async {
+            let pod_enclave = AsyncPodEnclave::new(pod_enclave).unwrap();
+            let panicked = pod_enclave.call::<_, ()>(|_| panic!("call panicked")).await;
+            assert!(matches!(panicked, Err(PodError::WorkerStopped)));
+            assert!(matches!(
+                pod_enclave.public_key().await,
+                Err(PodError::WorkerStopped)
+            ));
+        });
+    }
+}
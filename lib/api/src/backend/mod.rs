@@ -0,0 +1,125 @@
+#[cfg(feature = "sgx")]
+pub(crate) mod sgx;
+pub(crate) mod software;
+#[cfg(feature = "tpm")]
+pub(crate) mod tpm;
+
+use super::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveReport, QuoteType, RelyingParty, Result,
+    SealAlgorithm, Spid,
+};
+use rust_sgx_util::Quote;
+use sha2::Sha512;
+
+/// Abstraction over the component which generates, seals and uses the
+/// private key on behalf of `PodEnclave`.
+///
+/// The canonical implementation is [`SgxBackend`] which talks to the actual
+/// `pod-enclave` via the `libpod_sgx` C library. [`SoftwareBackend`] keeps
+/// the key in the host's memory instead, and is meant for development and
+/// testing on machines without SGX. [`TpmBackend`] protects the key with a
+/// TPM 2.0 instead, and attests it with TPM2 quotes.
+///
+/// Methods using the key take an optional [`RelyingParty`]: if `None`, the
+/// master key is used, otherwise the key derived from it for that relying
+/// party. Derivation has to be deterministic, so that a relying party sees
+/// the same key every time.
+///
+/// Backends must be `Send` so that `PodEnclave` can be shared across threads;
+/// `PodEnclave` takes care of serializing calls into the backend.
+///
+/// [`RelyingParty`]: struct.RelyingParty.html
+/// [`SgxBackend`]: struct.SgxBackend.html
+/// [`SoftwareBackend`]: struct.SoftwareBackend.html
+/// [`TpmBackend`]: struct.TpmBackend.html
+pub trait EnclaveBackend: Send {
+    /// Loads the backend and generates a fresh key pair. Returns the private
+    /// key in sealed form which can later be fed to [`load`].
+    ///
+    /// [`load`]: #tymethod.load
+    fn init(&mut self) -> Result<Vec<u8>>;
+
+    /// Loads the backend and restores the private key from `sealed_keys`.
+    fn load(&mut self, sealed_keys: &[u8]) -> Result<()>;
+
+    /// Seals the loaded private key again, e.g., after loading keys sealed
+    /// with another algorithm than [`seal_algorithm`].
+    ///
+    /// [`seal_algorithm`]: #tymethod.seal_algorithm
+    fn reseal(&self) -> Result<Vec<u8>>;
+
+    /// Replaces the key pair with a freshly generated one. Returns the new
+    /// private key sealed, and the signature of [`KeyRotation::message`] over
+    /// the old and new keys of `relying_party`, made with the old one. On
+    /// failure, the old key pair stays in place.
+    ///
+    /// [`KeyRotation::message`]: struct.KeyRotation.html#method.message
+    fn rotate_key(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+    ) -> Result<(Vec<u8>, Ed25519Signature)>;
+
+    /// Unloads the backend. Calling this on a backend which is not loaded
+    /// is a no-op.
+    fn unload(&mut self) -> Result<()>;
+
+    /// Generates a quote for remote attestation with the IAS. The quote's
+    /// `report_data` binds the public key to `user_data`, see
+    /// [`Ed25519PublicKey::to_report_data`].
+    ///
+    /// `nonce` is passed on to the Quoting Enclave when generating its report;
+    /// if `None`, a random one is used.
+    ///
+    /// [`Ed25519PublicKey::to_report_data`]: struct.Ed25519PublicKey.html#method.to_report_data
+    fn get_quote(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+    ) -> Result<Quote>;
+
+    /// Seals application `data` together with `aad`, additional data which
+    /// is authenticated but not encrypted. The result must not be loadable
+    /// with [`load`], nor can sealed keys be unsealed with [`unseal_data`].
+    ///
+    /// [`load`]: #tymethod.load
+    /// [`unseal_data`]: #tymethod.unseal_data
+    fn seal_data(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>>;
+
+    /// Unseals data sealed with [`seal_data`], returning the data and its
+    /// additional authenticated data.
+    ///
+    /// [`seal_data`]: #tymethod.seal_data
+    fn unseal_data(&self, sealed_data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)>;
+
+    /// Signs `message` with the private key.
+    fn sign(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        message: &[u8],
+    ) -> Result<Ed25519Signature>;
+
+    /// Signs the message hashed into `prehashed` with Ed25519ph (RFC 8032),
+    /// with empty context. Only the 64-byte digest has to reach the key, so
+    /// the message can be of any size.
+    fn sign_prehashed(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        prehashed: Sha512,
+    ) -> Result<Ed25519Signature>;
+
+    /// Returns the ED25519 public key.
+    fn public_key(&self, relying_party: Option<&RelyingParty>) -> Result<Ed25519PublicKey>;
+
+    /// Returns the report of the loaded enclave, i.e., its identity (MRENCLAVE,
+    /// MRSIGNER), version and attributes.
+    fn report(&self) -> Result<EnclaveReport>;
+
+    /// Returns the algorithm the sealed keys returned by [`init`] are
+    /// protected with.
+    ///
+    /// [`init`]: #tymethod.init
+    fn seal_algorithm(&self) -> SealAlgorithm;
+}
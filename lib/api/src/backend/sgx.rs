@@ -0,0 +1,307 @@
+use super::EnclaveBackend;
+use crate::wrappers::{self, EnclaveHandle};
+use crate::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveIdentity, EnclaveReport, LaunchMode, Measurement,
+    PodError, QuoteType, RelyingParty, Result, SealAlgorithm, SealPolicy, SigStruct, Spid,
+};
+use rust_sgx_util::Quote;
+use sha2::{Digest, Sha512};
+use std::path::{Path, PathBuf};
+
+/// Backend using the actual `pod-enclave` running inside Intel SGX.
+///
+/// Each `SgxBackend` loads its own instance of the enclave, so several of them
+/// (e.g., with different sealed keys) can be used side by side.
+///
+/// Keys are sealed with [`SealPolicy::MrEnclave`] unless configured otherwise
+/// with [`with_seal_policy`]. The enclave is launched in the default
+/// [`LaunchMode`] unless configured otherwise with [`with_launch_mode`].
+///
+/// [`SealPolicy::MrEnclave`]: enum.SealPolicy.html#variant.MrEnclave
+/// [`with_seal_policy`]: #method.with_seal_policy
+///
+/// With [`with_expected_mr_signer`] (or [`with_expected_mr_enclave`]), the
+/// [`SigStruct`] of the enclave binary is checked before it's loaded, and the
+/// identity of the loaded enclave once again afterwards, so that a wrong or
+/// tampered binary is refused with [`PodError::UntrustedEnclave`]. In
+/// [`LaunchMode::Production`], enclaves are always pinned: to
+/// [`built_mr_signer`] if neither is set, and if that's unknown as well,
+/// loading fails with [`PodError::UntrustedEnclave`] rather than load any
+/// enclave binary.
+///
+/// [`LaunchMode`]: enum.LaunchMode.html
+/// [`LaunchMode::Production`]: enum.LaunchMode.html#variant.Production
+/// [`built_mr_signer`]: #method.built_mr_signer
+/// [`with_launch_mode`]: #method.with_launch_mode
+/// [`with_expected_mr_signer`]: #method.with_expected_mr_signer
+/// [`with_expected_mr_enclave`]: #method.with_expected_mr_enclave
+/// [`SigStruct`]: struct.SigStruct.html
+/// [`PodError::UntrustedEnclave`]: enum.PodError.html#variant.UntrustedEnclave
+pub struct SgxBackend {
+    enclave_path: PathBuf,
+    launch_mode: LaunchMode,
+    seal_policy: SealPolicy,
+    expected_mr_signer: Option<Measurement>,
+    expected_mr_enclave: Option<Measurement>,
+    enclave: Option<EnclaveHandle>,
+}
+
+impl SgxBackend {
+    /// Creates new instance which will load the enclave binary found at
+    /// `enclave_path` (e.g., `pod_enclave.signed.so`).
+    pub fn new<P: AsRef<Path>>(enclave_path: P) -> Self {
+        Self {
+            enclave_path: enclave_path.as_ref().to_owned(),
+            launch_mode: LaunchMode::default(),
+            seal_policy: SealPolicy::default(),
+            expected_mr_signer: None,
+            expected_mr_enclave: None,
+            enclave: None,
+        }
+    }
+
+    /// Sets the policy used for sealing the private key. Keys sealed under
+    /// another policy are still loaded, and resealed by `PodEnclave`.
+    pub fn with_seal_policy(mut self, seal_policy: SealPolicy) -> Self {
+        self.seal_policy = seal_policy;
+        self
+    }
+
+    /// Sets the mode the enclave is launched in. Takes effect the next time
+    /// the enclave is loaded.
+    pub fn with_launch_mode(mut self, launch_mode: LaunchMode) -> Self {
+        self.launch_mode = launch_mode;
+        self
+    }
+
+    /// Only loads enclave binaries signed with the key whose hash is
+    /// `mr_signer`, e.g., [`built_mr_signer`].
+    ///
+    /// [`built_mr_signer`]: #method.built_mr_signer
+    pub fn with_expected_mr_signer(mut self, mr_signer: Measurement) -> Self {
+        self.expected_mr_signer = Some(mr_signer);
+        self
+    }
+
+    /// Only loads the exact enclave build measured as `mr_enclave`.
+    pub fn with_expected_mr_enclave(mut self, mr_enclave: Measurement) -> Self {
+        self.expected_mr_enclave = Some(mr_enclave);
+        self
+    }
+
+    /// Returns the MRSIGNER of the enclave built along with this crate, if
+    /// it was built (which requires the SGX SDK).
+    pub fn built_mr_signer() -> Option<Measurement> {
+        option_env!("POD_ENCLAVE_MR_SIGNER").and_then(|mr_signer| mr_signer.parse().ok())
+    }
+
+    fn enclave(&self) -> Result<&EnclaveHandle> {
+        self.enclave.as_ref().ok_or(PodError::EnclaveNotLoaded)
+    }
+
+    /// Returns the expected MRSIGNER, which defaults to [`built_mr_signer`]
+    /// in production mode, unless the MRENCLAVE is pinned instead.
+    ///
+    /// [`built_mr_signer`]: #method.built_mr_signer
+    fn expected_mr_signer(&self) -> Option<Measurement> {
+        match self.launch_mode {
+            LaunchMode::Production if self.expected_mr_enclave.is_none() => {
+                self.expected_mr_signer.or_else(Self::built_mr_signer)
+            }
+            _ => self.expected_mr_signer,
+        }
+    }
+
+    /// Returns whether the enclave identity is to be checked. Production
+    /// enclaves have to be.
+    fn is_pinned(&self) -> Result<bool> {
+        if self.expected_mr_signer().is_some() || self.expected_mr_enclave.is_some() {
+            return Ok(true);
+        }
+        match self.launch_mode {
+            LaunchMode::Production => Err(PodError::UntrustedEnclave(
+                "production enclave requires an expected MRSIGNER or MRENCLAVE".to_owned(),
+            )),
+            LaunchMode::Debug => Ok(false),
+        }
+    }
+
+    fn check_identity(&self, identity: &EnclaveIdentity) -> Result<()> {
+        check_measurement("MRSIGNER", identity.mr_signer(), self.expected_mr_signer())?;
+        check_measurement("MRENCLAVE", identity.mr_enclave(), self.expected_mr_enclave)
+    }
+
+    /// Checks the SIGSTRUCT of the binary before handing it to the SGX SDK.
+    fn check_enclave_file(&self) -> Result<()> {
+        if !self.is_pinned()? {
+            return Ok(());
+        }
+        let sigstruct = SigStruct::from_enclave_file(&self.enclave_path)?;
+        self.check_identity(&sigstruct.identity())
+    }
+
+    /// Checks the identity the CPU measured for the loaded enclave, which
+    /// unlike the SIGSTRUCT can't be faked.
+    fn check_loaded_enclave(&mut self) -> Result<()> {
+        let result = self.is_pinned().and_then(|pinned| {
+            if !pinned {
+                return Ok(());
+            }
+            self.report()
+                .and_then(|report| self.check_identity(report.identity()))
+        });
+        if result.is_err() {
+            self.unload()?;
+        }
+        result
+    }
+}
+
+fn check_measurement(
+    name: &str,
+    actual: &[u8; Measurement::LENGTH],
+    expected: Option<Measurement>,
+) -> Result<()> {
+    match expected {
+        Some(expected) if expected.as_bytes() != actual => {
+            Err(PodError::UntrustedEnclave(format!(
+                "{} {} doesn't match the expected {}",
+                name,
+                Measurement::from(*actual),
+                expected
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl EnclaveBackend for SgxBackend {
+    fn init(&mut self) -> Result<Vec<u8>> {
+        self.unload()?;
+        self.check_enclave_file()?;
+        let (enclave, sealed_keys) =
+            wrappers::init_enclave(&self.enclave_path, self.launch_mode, self.seal_policy)?;
+        self.enclave = Some(enclave);
+        self.check_loaded_enclave()?;
+        Ok(sealed_keys)
+    }
+
+    fn load(&mut self, sealed_keys: &[u8]) -> Result<()> {
+        self.unload()?;
+        self.check_enclave_file()?;
+        self.enclave = Some(wrappers::load_enclave(
+            &self.enclave_path,
+            self.launch_mode,
+            sealed_keys,
+        )?);
+        self.check_loaded_enclave()
+    }
+
+    fn reseal(&self) -> Result<Vec<u8>> {
+        wrappers::reseal_keys(self.enclave()?, self.seal_policy)
+    }
+
+    fn rotate_key(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+    ) -> Result<(Vec<u8>, Ed25519Signature)> {
+        let (sealed_keys, signature) =
+            wrappers::rotate_key(self.enclave()?, self.seal_policy, relying_party)?;
+        Ok((sealed_keys, Ed25519Signature::from_bytes(&signature)?))
+    }
+
+    fn seal_data(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        wrappers::seal_data(self.enclave()?, self.seal_policy, data, aad)
+    }
+
+    fn unseal_data(&self, sealed_data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        wrappers::unseal_data(self.enclave()?, sealed_data)
+    }
+
+    fn unload(&mut self) -> Result<()> {
+        if let Some(enclave) = &self.enclave {
+            wrappers::unload_enclave(enclave)?;
+            self.enclave = None;
+        }
+        Ok(())
+    }
+
+    fn get_quote(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+    ) -> Result<Quote> {
+        wrappers::get_quote(
+            self.enclave()?,
+            relying_party,
+            user_data,
+            spid,
+            quote_type,
+            nonce,
+        )
+    }
+
+    fn sign(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        message: &[u8],
+    ) -> Result<Ed25519Signature> {
+        let signature = wrappers::sign_buffer(self.enclave()?, relying_party, message)?;
+        Ed25519Signature::from_bytes(&signature)
+    }
+
+    fn sign_prehashed(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        prehashed: Sha512,
+    ) -> Result<Ed25519Signature> {
+        let digest = prehashed.finalize();
+        let signature = wrappers::sign_prehashed(self.enclave()?, relying_party, &digest)?;
+        Ed25519Signature::from_bytes(&signature)
+    }
+
+    fn public_key(&self, relying_party: Option<&RelyingParty>) -> Result<Ed25519PublicKey> {
+        let pubkey = wrappers::get_public_key(self.enclave()?, relying_party)?;
+        Ed25519PublicKey::from_bytes(&pubkey)
+    }
+
+    fn report(&self) -> Result<EnclaveReport> {
+        let report_body = wrappers::get_report_body(self.enclave()?)?;
+        EnclaveReport::from_report_body(&report_body)
+    }
+
+    fn seal_algorithm(&self) -> SealAlgorithm {
+        match self.seal_policy {
+            SealPolicy::MrEnclave => SealAlgorithm::SgxMrEnclave,
+            SealPolicy::MrSigner => SealAlgorithm::SgxMrSigner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn production_enclave_has_to_be_pinned() {
+        let backend = SgxBackend::new("pod_enclave.signed.so");
+        let debug = backend.with_launch_mode(LaunchMode::Debug);
+        assert!(!debug.is_pinned().unwrap());
+
+        let production = debug.with_launch_mode(LaunchMode::Production);
+        match SgxBackend::built_mr_signer() {
+            Some(mr_signer) => assert_eq!(production.expected_mr_signer(), Some(mr_signer)),
+            None => assert!(matches!(
+                production.is_pinned(),
+                Err(PodError::UntrustedEnclave(_))
+            )),
+        }
+
+        let pinned = production.with_expected_mr_enclave(Measurement::from([0u8; 32]));
+        assert!(pinned.is_pinned().unwrap());
+        assert_eq!(pinned.expected_mr_signer(), None);
+    }
+}
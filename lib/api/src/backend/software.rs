@@ -0,0 +1,467 @@
+use super::EnclaveBackend;
+use crate::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveIdentity, EnclaveReport, KeyRotation, PodError,
+    QuoteType, RelyingParty, Result, SealAlgorithm, Spid,
+};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use hmac::{Hmac, Mac, NewMac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rust_sgx_util::Quote;
+use sha2::{Digest, Sha256, Sha512};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+const SEALED_MAGIC: &[u8] = b"PODSWKEY";
+const SEALED_DATA_MAGIC: &[u8] = b"PODSWDAT";
+const SEALING_KEY_DOMAIN: &[u8] = b"pod-software-backend-sealing-key";
+// Same as `RELYING_PARTY_KEY_DOMAIN` of `pod-enclave`, NUL included.
+const RELYING_PARTY_KEY_DOMAIN: &[u8] = b"pod-enclave-relying-party-key-v1\0";
+const NONCE_SIZE: usize = 12;
+const SECRET_SIZE: usize = 32;
+const SECRET_FILE_MODE: u32 = 0o600;
+
+// Layout of the synthetic quote mimics `sgx_quote_t` so that
+// `Quote::report_data` works on it as expected.
+const QUOTE_VERSION: u16 = 0xffff; // Never a valid EPID quote version
+const QUOTE_MARKER: &[u8] = b"POD SOFTWARE QUOTE - NOT SGX";
+const QUOTE_BASENAME_OFFSET: usize = 16;
+const QUOTE_REPORT_DATA_OFFSET: usize = 368;
+const QUOTE_BODY_SIZE: usize = 432;
+const QUOTE_SIGNATURE_SIZE: usize = 64;
+
+/// Backend which keeps an ED25519 key in the host's memory, and persists it
+/// encrypted with ChaCha20-Poly1305 instead of SGX-sealed.
+///
+/// This backend offers **no** hardware protection whatsoever and is meant for
+/// development and testing on machines without SGX. The quotes it generates
+/// are self-signed, clearly marked as such (see [`is_software_quote`]), and
+/// will be rejected by the IAS.
+///
+/// [`is_software_quote`]: #method.is_software_quote
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::{EnclaveBackend, QuoteType, SoftwareBackend};
+/// # fn main() -> pod_api::Result<()> {
+/// let mut backend = SoftwareBackend::new("some secret");
+/// let sealed_keys = backend.init()?;
+///
+/// let mut backend = SoftwareBackend::new("some secret");
+/// backend.load(&sealed_keys)?;
+/// let spid = "0123456789abcdef0123456789abcdef".parse()?;
+/// let quote = backend.get_quote(None, &[], &spid, QuoteType::Unlinkable, None)?;
+/// assert!(SoftwareBackend::is_software_quote(&quote));
+/// assert_eq!(SoftwareBackend::verify_quote(&quote)?, backend.public_key(None)?.as_bytes());
+///
+/// let mut backend = SoftwareBackend::new("other secret");
+/// assert!(backend.load(&sealed_keys).is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub struct SoftwareBackend {
+    sealing_key: [u8; 32],
+    keypair: Option<Keypair>,
+}
+
+impl SoftwareBackend {
+    /// Creates new instance which derives the key used for encrypting the
+    /// private key from `secret`.
+    pub fn new<B: AsRef<[u8]>>(secret: B) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(SEALING_KEY_DOMAIN);
+        hasher.update(secret.as_ref());
+        let mut sealing_key = [0u8; 32];
+        sealing_key.copy_from_slice(&hasher.finalize());
+        Self {
+            sealing_key,
+            keypair: None,
+        }
+    }
+
+    /// Creates new instance whose secret is kept in the file at `path`. If
+    /// the file doesn't exist yet, it's created with a random secret and
+    /// made readable by its owner only.
+    ///
+    /// Anyone able to read the file can decrypt the private key, hence files
+    /// readable by the group or others are rejected.
+    pub fn from_secret_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut secret = [0u8; SECRET_SIZE];
+        OsRng.fill_bytes(&mut secret);
+        let created = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(SECRET_FILE_MODE)
+            .open(path);
+        match created {
+            Ok(mut file) => {
+                if let Err(err) = file.write_all(&secret).and_then(|_| file.sync_all()) {
+                    let _ = fs::remove_file(path);
+                    return Err(err.into());
+                }
+                return Ok(Self::new(secret));
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut file = File::open(path)?;
+        if file.metadata()?.permissions().mode() & 0o077 != 0 {
+            return Err(PodError::Io(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is accessible by other users", path.display()),
+            )));
+        }
+        let mut secret = Vec::new();
+        file.read_to_end(&mut secret)?;
+        if secret.len() != SECRET_SIZE {
+            return Err(PodError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} doesn't hold a valid secret", path.display()),
+            )));
+        }
+        Ok(Self::new(secret))
+    }
+
+    /// Checks whether `quote` was generated by a `SoftwareBackend`.
+    pub fn is_software_quote(quote: &Quote) -> bool {
+        let version = match quote.get(..2) {
+            Some(version) => u16::from_le_bytes([version[0], version[1]]),
+            None => return false,
+        };
+        let marker = quote.get(QUOTE_BASENAME_OFFSET..QUOTE_BASENAME_OFFSET + QUOTE_MARKER.len());
+        version == QUOTE_VERSION && marker == Some(QUOTE_MARKER)
+    }
+
+    /// Verifies the self-signature of a quote generated by a `SoftwareBackend`,
+    /// and returns the raw public key embedded within it.
+    ///
+    /// Note that this only proves that the quote was not tampered with, and
+    /// **not** that the key is protected in any way.
+    pub fn verify_quote(quote: &Quote) -> Result<Vec<u8>> {
+        if !Self::is_software_quote(quote) {
+            return Err(PodError::InvalidQuote("not a software quote"));
+        }
+        let body = quote
+            .get(..QUOTE_BODY_SIZE)
+            .ok_or(PodError::InvalidQuote("software quote too short"))?;
+        let signature = quote
+            .get(QUOTE_BODY_SIZE + 4..)
+            .filter(|signature| signature.len() == QUOTE_SIGNATURE_SIZE)
+            .ok_or(PodError::InvalidSignature)?;
+        let signature = Signature::try_from(signature).map_err(|_| PodError::InvalidSignature)?;
+        let pubkey = &quote.report_data()?[..32];
+        PublicKey::from_bytes(pubkey)
+            .map_err(|_| PodError::InvalidPublicKey)?
+            .verify(body, &signature)
+            .map_err(|_| PodError::InvalidSignature)?;
+        Ok(pubkey.to_vec())
+    }
+
+    fn keypair(&self) -> Result<&Keypair> {
+        self.keypair.as_ref().ok_or(PodError::EnclaveNotLoaded)
+    }
+
+    /// Calls `f` with the master key pair, or with the one derived from it
+    /// for `relying_party`.
+    fn with_keypair<T, F>(&self, relying_party: Option<&RelyingParty>, f: F) -> Result<T>
+    where
+        F: FnOnce(&Keypair) -> T,
+    {
+        let keypair = self.keypair()?;
+        Ok(match relying_party {
+            Some(relying_party) => f(&derive_keypair(keypair, relying_party)),
+            None => f(keypair),
+        })
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&Key::from(self.sealing_key))
+    }
+
+    fn seal(&self, secret: &SecretKey) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: secret.as_bytes(),
+                    aad: SEALED_MAGIC,
+                },
+            )
+            .map_err(|_| PodError::SealingFailed)?;
+
+        let mut sealed_keys =
+            Vec::with_capacity(SEALED_MAGIC.len() + NONCE_SIZE + ciphertext.len());
+        sealed_keys.extend_from_slice(SEALED_MAGIC);
+        sealed_keys.extend_from_slice(&nonce);
+        sealed_keys.extend_from_slice(&ciphertext);
+        Ok(sealed_keys)
+    }
+}
+
+/// Derives the key pair of `relying_party` the same way `pod-enclave` does,
+/// i.e., seeded with `HMAC-SHA256(master secret, domain || relying party)`.
+fn derive_keypair(master: &Keypair, relying_party: &RelyingParty) -> Keypair {
+    let mut mac = Hmac::<Sha256>::new_from_slice(master.secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(RELYING_PARTY_KEY_DOMAIN);
+    mac.update(relying_party.as_bytes());
+    let secret = SecretKey::from_bytes(&mac.finalize().into_bytes())
+        .expect("HMAC-SHA256 output is a valid secret key");
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+impl EnclaveBackend for SoftwareBackend {
+    fn init(&mut self) -> Result<Vec<u8>> {
+        let keypair = Keypair::generate(&mut OsRng);
+        let sealed_keys = self.seal(&keypair.secret)?;
+        self.keypair = Some(keypair);
+        Ok(sealed_keys)
+    }
+
+    fn load(&mut self, sealed_keys: &[u8]) -> Result<()> {
+        if !sealed_keys.starts_with(SEALED_MAGIC)
+            || sealed_keys.len() < SEALED_MAGIC.len() + NONCE_SIZE
+        {
+            return Err(PodError::SealedStateInvalid);
+        }
+        let (nonce, ciphertext) = sealed_keys[SEALED_MAGIC.len()..].split_at(NONCE_SIZE);
+        let nonce = <[u8; NONCE_SIZE]>::try_from(nonce).expect("nonce is NONCE_SIZE bytes long");
+        let plaintext = self
+            .cipher()
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: SEALED_MAGIC,
+                },
+            )
+            .map_err(|_| PodError::SealedStateInvalid)?;
+        let secret = SecretKey::from_bytes(&plaintext).map_err(|_| PodError::SealedStateInvalid)?;
+        let public = PublicKey::from(&secret);
+        self.keypair = Some(Keypair { secret, public });
+        Ok(())
+    }
+
+    fn reseal(&self) -> Result<Vec<u8>> {
+        self.seal(&self.keypair()?.secret)
+    }
+
+    fn rotate_key(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+    ) -> Result<(Vec<u8>, Ed25519Signature)> {
+        let new_keypair = Keypair::generate(&mut OsRng);
+        let new_public = match relying_party {
+            Some(relying_party) => derive_keypair(&new_keypair, relying_party).public,
+            None => new_keypair.public,
+        };
+        let signature = self.with_keypair(relying_party, |old_keypair| {
+            let message = KeyRotation::message(
+                &Ed25519PublicKey::from_bytes(old_keypair.public.as_bytes())?,
+                &Ed25519PublicKey::from_bytes(new_public.as_bytes())?,
+            );
+            Ed25519Signature::from_bytes(&old_keypair.sign(&message).to_bytes())
+        })??;
+        let sealed_keys = self.seal(&new_keypair.secret)?;
+        self.keypair = Some(new_keypair);
+        Ok((sealed_keys, signature))
+    }
+
+    // Sealed data is `magic || aad length (u32 LE) || aad || nonce ||
+    // ciphertext`, with everything before the nonce authenticated.
+    fn seal_data(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let aad_length = u32::try_from(aad.len()).map_err(|_| PodError::SealingFailed)?;
+        let mut sealed_data = Vec::with_capacity(
+            SEALED_DATA_MAGIC.len() + 4 + aad.len() + NONCE_SIZE + data.len() + 16,
+        );
+        sealed_data.extend_from_slice(SEALED_DATA_MAGIC);
+        sealed_data.extend_from_slice(&aad_length.to_le_bytes());
+        sealed_data.extend_from_slice(aad);
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: data,
+                    aad: &sealed_data,
+                },
+            )
+            .map_err(|_| PodError::SealingFailed)?;
+        sealed_data.extend_from_slice(&nonce);
+        sealed_data.extend_from_slice(&ciphertext);
+        Ok(sealed_data)
+    }
+
+    fn unseal_data(&self, sealed_data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let header_size = SEALED_DATA_MAGIC.len() + 4;
+        if !sealed_data.starts_with(SEALED_DATA_MAGIC) || sealed_data.len() < header_size {
+            return Err(PodError::SealedDataInvalid);
+        }
+        let aad_length = u32::from_le_bytes(
+            <[u8; 4]>::try_from(&sealed_data[SEALED_DATA_MAGIC.len()..header_size])
+                .expect("aad length is 4 bytes long"),
+        ) as usize;
+        if sealed_data.len() - header_size < aad_length + NONCE_SIZE {
+            return Err(PodError::SealedDataInvalid);
+        }
+        let (authenticated, rest) = sealed_data.split_at(header_size + aad_length);
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+        let nonce = <[u8; NONCE_SIZE]>::try_from(nonce).expect("nonce is NONCE_SIZE bytes long");
+        let data = self
+            .cipher()
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: authenticated,
+                },
+            )
+            .map_err(|_| PodError::SealedDataInvalid)?;
+        Ok((data, authenticated[header_size..].to_vec()))
+    }
+
+    fn unload(&mut self) -> Result<()> {
+        self.keypair = None;
+        Ok(())
+    }
+
+    fn get_quote(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        _spid: &Spid,
+        quote_type: QuoteType,
+        _nonce: Option<&[u8; 16]>,
+    ) -> Result<Quote> {
+        // There's no Quoting Enclave here, so neither the SPID nor the nonce
+        // have anything to go into.
+        let sign_type: u16 = match quote_type {
+            QuoteType::Unlinkable => 0,
+            QuoteType::Linkable => 1,
+        };
+
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+        quote[..2].copy_from_slice(&QUOTE_VERSION.to_le_bytes());
+        quote[2..4].copy_from_slice(&sign_type.to_le_bytes());
+        quote[QUOTE_BASENAME_OFFSET..QUOTE_BASENAME_OFFSET + QUOTE_MARKER.len()]
+            .copy_from_slice(QUOTE_MARKER);
+        self.with_keypair(relying_party, |keypair| {
+            let public_key = Ed25519PublicKey::from_bytes(keypair.public.as_bytes())?;
+            quote[QUOTE_REPORT_DATA_OFFSET..QUOTE_REPORT_DATA_OFFSET + 64]
+                .copy_from_slice(&public_key.to_report_data(user_data));
+            let signature = keypair.sign(&quote);
+            quote.extend_from_slice(&(QUOTE_SIGNATURE_SIZE as u32).to_le_bytes());
+            quote.extend_from_slice(&signature.to_bytes());
+            Ok(Quote::from(quote))
+        })?
+    }
+
+    fn sign(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        message: &[u8],
+    ) -> Result<Ed25519Signature> {
+        let signature = self.with_keypair(relying_party, |keypair| keypair.sign(message))?;
+        Ed25519Signature::from_bytes(&signature.to_bytes())
+    }
+
+    fn sign_prehashed(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        prehashed: Sha512,
+    ) -> Result<Ed25519Signature> {
+        let signature = self
+            .with_keypair(relying_party, |keypair| {
+                keypair.sign_prehashed(prehashed, None)
+            })?
+            .map_err(|_| PodError::InvalidSignature)?;
+        Ed25519Signature::from_bytes(&signature.to_bytes())
+    }
+
+    fn public_key(&self, relying_party: Option<&RelyingParty>) -> Result<Ed25519PublicKey> {
+        let public = self.with_keypair(relying_party, |keypair| keypair.public)?;
+        Ed25519PublicKey::from_bytes(public.as_bytes())
+    }
+
+    fn report(&self) -> Result<EnclaveReport> {
+        // There's no enclave to measure; use a fixed MRENCLAVE so that
+        // software sealed keys are easy to tell apart, and no signer.
+        self.keypair()?;
+        let mut mr_enclave = [0u8; 32];
+        mr_enclave.copy_from_slice(&Sha256::digest(QUOTE_MARKER));
+        let identity = EnclaveIdentity::new(mr_enclave, [0u8; 32]);
+        Ok(EnclaveReport::new(identity, 0, 0, 0, 0))
+    }
+
+    fn seal_algorithm(&self) -> SealAlgorithm {
+        SealAlgorithm::SoftwareChaCha20Poly1305
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{payload, software_enclave};
+
+    #[test]
+    fn relying_parties_get_their_own_keys() {
+        let (_dir, pod_enclave) = software_enclave();
+        let alice = "https://alice.example".parse().unwrap();
+        let bob = "https://bob.example".parse().unwrap();
+
+        let alice_key = pod_enclave.public_key_for(&alice).unwrap();
+        assert_ne!(alice_key, pod_enclave.public_key_for(&bob).unwrap());
+        assert_ne!(alice_key, pod_enclave.public_key().unwrap());
+        assert_eq!(alice_key, pod_enclave.public_key_for(&alice).unwrap());
+
+        let payload = payload("https://alice.example");
+        let signature = pod_enclave.sign_payload_for(&alice, &payload).unwrap();
+        payload.verify(&alice_key, &signature).unwrap();
+    }
+
+    #[test]
+    fn sign_reader_is_ed25519ph() {
+        let (_dir, pod_enclave) = software_enclave();
+        let document = vec![0x42; 1 << 20];
+        let signature = pod_enclave.sign_reader(&document[..]).unwrap();
+
+        let pub_key = pod_enclave.public_key().unwrap();
+        pub_key.verify_reader(&document[..], &signature).unwrap();
+        assert!(pub_key
+            .verify_reader(&b"other document"[..], &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn sealed_data_only_unseals_with_same_key() {
+        let (dir, pod_enclave) = software_enclave();
+        let sealed = pod_enclave.seal(b"recovery code", b"example.com").unwrap();
+
+        let (data, aad) = pod_enclave.unseal(&sealed).unwrap();
+        assert_eq!(data, b"recovery code");
+        assert_eq!(aad, b"example.com");
+
+        let other = dir.load("other_sealed_keys", "other secret").unwrap();
+        assert!(other.unseal(&sealed).is_err());
+    }
+
+    #[test]
+    fn software_identity_is_not_debug() {
+        let (_dir, pod_enclave) = software_enclave();
+        assert!(!pod_enclave.identity().unwrap().is_debug());
+    }
+}
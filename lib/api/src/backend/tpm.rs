@@ -0,0 +1,808 @@
+use super::software::SoftwareBackend;
+use super::EnclaveBackend;
+use crate::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveIdentity, EnclaveReport, Measurement, PodError,
+    QuoteType, RelyingParty, Result, SealAlgorithm, Spid,
+};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::{EncodedPoint, FieldBytes};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rust_sgx_util::Quote;
+use sha2::{Digest, Sha256, Sha512};
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::fs::{self, DirBuilder};
+use std::io::{self, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SEALED_MAGIC: &[u8] = b"PODTPMKY";
+const ROOT_SECRET_SIZE: usize = 32;
+const TCTI_ENV: &str = "TPM2TOOLS_TCTI";
+
+// The storage primary key is derived from the owner hierarchy's seed with a
+// fixed template, so the TPM recreates the same one every time; the sealed
+// object is only loadable under it, i.e., on the same TPM.
+const STORAGE_KEY_ALGORITHM: &str = "ecc";
+// Likewise, the attestation key (AK) is an endorsement hierarchy primary key.
+// Being restricted, it only signs structures generated by the TPM itself.
+const AK_ALGORITHM: &str = "ecc256:ecdsa-sha256:null";
+const AK_ATTRIBUTES: &str = "fixedtpm|fixedparent|sensitivedataorigin|userwithauth|restricted|sign";
+const PCR_SELECTION: &str = "sha256:0,1,2,3,4,5,6,7";
+
+// Layout of the quote body mimics `sgx_quote_t` so that `Quote::report_data`
+// works on it as expected; the TPM evidence follows it.
+const QUOTE_VERSION: u16 = 0xfffe; // Never a valid EPID quote version
+const QUOTE_MARKER: &[u8] = b"POD TPM2 QUOTE - NOT SGX";
+const QUOTE_BASENAME_OFFSET: usize = 16;
+const QUOTE_REPORT_DATA_OFFSET: usize = 368;
+const QUOTE_REPORT_DATA_SIZE: usize = 64;
+const QUOTE_BODY_SIZE: usize = 432;
+
+// TPM 2.0 constants, see "TPM 2.0 Part 2: Structures".
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+const TPM_ALG_ECC: u16 = 0x0023;
+const TPM_ALG_ECDSA: u16 = 0x0018;
+const TPM_ALG_SHA256: u16 = 0x000b;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ECC_NIST_P256: u16 = 0x0003;
+const TPMA_OBJECT_FIXEDTPM: u32 = 1 << 1;
+const TPMA_OBJECT_RESTRICTED: u32 = 1 << 16;
+const TPMA_OBJECT_SIGN: u32 = 1 << 18;
+
+/// Backend which protects the ED25519 key with a TPM 2.0, driven with the
+/// `tpm2-tools` command line tools, and attests it with TPM2 quotes.
+///
+/// A random root secret is sealed into a data object created under a primary
+/// key of the TPM's storage hierarchy, so that only the same TPM can unseal
+/// it; the private key is encrypted with a key derived from that secret the
+/// way [`SoftwareBackend`] does.
+///
+/// [`get_quote`] returns a quote whose report data binds the public key to the
+/// user data like SGX quotes do, followed by a TPM2 quote over it and PCRs
+/// 0-7, signed by an attestation key (AK) created under the endorsement
+/// hierarchy. See [`verify_quote`] for what a verifier can and can't rely on.
+///
+/// This offers much weaker guarantees than SGX:
+///
+/// * The ED25519 signing key is **not** TPM-resident. It's a software key
+///   which is unsealed into the host's memory while the backend is loaded,
+///   and signs there, so the TPM only protects it at rest and ties it to the
+///   device. Anything able to read the process' memory can copy it.
+/// * A new AK is created for every quote. Being a primary key created from a
+///   fixed template, it's the same key each time, but nothing certifies it:
+///   the endorsement key (EK) certificate chain isn't checked.
+/// * The quoted PCRs aren't checked against any policy either, neither here
+///   nor by [`verify_quote`].
+///
+/// The TPM is chosen with a TCTI string, e.g., `device:/dev/tpmrm0` or
+/// `swtpm:port=2321` for the `swtpm` simulator; by default, `tpm2-tools` use
+/// the one in the `TPM2TOOLS_TCTI` env var, or else the TPM device.
+///
+/// [`SoftwareBackend`]: struct.SoftwareBackend.html
+/// [`get_quote`]: trait.EnclaveBackend.html#tymethod.get_quote
+/// [`verify_quote`]: #method.verify_quote
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pod_api::{EnclaveBackend, QuoteType, TpmBackend};
+/// # fn main() -> pod_api::Result<()> {
+/// let mut backend = TpmBackend::new().with_tcti("swtpm:port=2321");
+/// let sealed_keys = backend.init()?;
+///
+/// let mut backend = TpmBackend::new().with_tcti("swtpm:port=2321");
+/// backend.load(&sealed_keys)?;
+/// let spid = "0123456789abcdef0123456789abcdef".parse()?;
+/// let quote = backend.get_quote(None, b"nonce", &spid, QuoteType::Unlinkable, None)?;
+/// assert!(TpmBackend::is_tpm_quote(&quote));
+///
+/// let attestation = TpmBackend::verify_quote(&quote)?;
+/// assert_eq!(attestation.attestation_key(), &backend.attestation_key()?);
+/// let public_key = pod_api::Ed25519PublicKey::from_report_data(quote.report_data()?, b"nonce")?;
+/// assert_eq!(public_key, backend.public_key(None)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct TpmBackend {
+    tcti: Option<String>,
+    loaded: Option<Loaded>,
+}
+
+struct Loaded {
+    sealed_object: SealedObject,
+    keys: SoftwareBackend,
+    /// `TPM2B_PUBLIC` of the attestation key.
+    attestation_key: Vec<u8>,
+}
+
+/// Public and private parts of the sealed data object, as returned by
+/// `tpm2_create`.
+struct SealedObject {
+    public: Vec<u8>,
+    private: Vec<u8>,
+}
+
+impl TpmBackend {
+    /// Creates new instance using the default TPM of `tpm2-tools`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the TCTI used to reach the TPM, e.g., `swtpm:port=2321`.
+    pub fn with_tcti<S: Into<String>>(mut self, tcti: S) -> Self {
+        self.tcti = Some(tcti.into());
+        self
+    }
+
+    /// Returns the SHA-256 of the attestation key's public area, which
+    /// identifies the TPM in the quotes it signs. It's also the MRSIGNER of
+    /// this backend's [`report`].
+    ///
+    /// [`report`]: trait.EnclaveBackend.html#tymethod.report
+    pub fn attestation_key(&self) -> Result<Measurement> {
+        let public_area = public_area(&self.loaded()?.attestation_key)?;
+        Ok(Measurement::from(<[u8; 32]>::from(Sha256::digest(
+            public_area,
+        ))))
+    }
+
+    /// Checks whether `quote` was generated by a `TpmBackend`.
+    pub fn is_tpm_quote(quote: &Quote) -> bool {
+        let version = match quote.get(..2) {
+            Some(version) => u16::from_le_bytes([version[0], version[1]]),
+            None => return false,
+        };
+        let marker = quote.get(QUOTE_BASENAME_OFFSET..QUOTE_BASENAME_OFFSET + QUOTE_MARKER.len());
+        version == QUOTE_VERSION && marker == Some(QUOTE_MARKER)
+    }
+
+    /// Verifies the TPM2 quote embedded in a quote generated by a
+    /// `TpmBackend`, and that it covers the quote's report data, which can
+    /// then be read with `Quote::report_data` as usual.
+    ///
+    /// This only proves that the report data was signed by the attestation
+    /// key embedded in the quote. It does **not** prove that the key resides
+    /// in a genuine TPM, since its endorsement key certificate chain isn't
+    /// checked: verifiers have to know which attestation keys to trust (see
+    /// [`TpmAttestation::attestation_key`]), e.g., enrolled out of band.
+    ///
+    /// [`TpmAttestation::attestation_key`]: struct.TpmAttestation.html#method.attestation_key
+    pub fn verify_quote(quote: &Quote) -> Result<TpmAttestation> {
+        if !Self::is_tpm_quote(quote) {
+            return Err(PodError::InvalidQuote("not a TPM quote"));
+        }
+        let body = quote
+            .get(..QUOTE_BODY_SIZE)
+            .ok_or(PodError::InvalidQuote("TPM quote too short"))?;
+        let mut evidence = LeReader(&quote[QUOTE_BODY_SIZE..]);
+        let attest = evidence.sized()?;
+        let signature = evidence.sized()?;
+        let attestation_key = evidence.sized()?;
+        if !evidence.0.is_empty() {
+            return Err(PodError::InvalidQuote("trailing bytes after TPM evidence"));
+        }
+
+        let public_area = public_area(attestation_key)?;
+        let verifying_key = parse_attestation_key(public_area)?;
+        let mut name = TPM_ALG_SHA256.to_be_bytes().to_vec();
+        name.extend_from_slice(&Sha256::digest(public_area));
+        verifying_key
+            .verify(attest, &parse_signature(signature)?)
+            .map_err(|_| PodError::InvalidSignature)?;
+
+        // Only now that the TPM is known to have generated `attest`, check
+        // what it says.
+        let mut reader = BeReader(attest);
+        if reader.u32()? != TPM_GENERATED_VALUE || reader.u16()? != TPM_ST_ATTEST_QUOTE {
+            return Err(PodError::InvalidQuote("TPM attestation is not a quote"));
+        }
+        if reader.sized()? != &name[..] {
+            return Err(PodError::InvalidQuote("TPM quote signed by another key"));
+        }
+        let qualifying_data = reader.sized()?;
+        reader.skip(17 + 8)?; // clockInfo, firmwareVersion
+        let pcr_selection = reader.pcr_selection()?;
+        let pcr_digest = reader.sized()?.to_vec();
+        if !reader.0.is_empty() {
+            return Err(PodError::InvalidQuote("trailing bytes after TPM quote"));
+        }
+
+        let report_data = &body[QUOTE_REPORT_DATA_OFFSET..][..QUOTE_REPORT_DATA_SIZE];
+        if qualifying_data != &Sha256::digest(report_data)[..]
+            || body != &quote_body(report_data)[..]
+        {
+            return Err(PodError::InvalidQuote(
+                "TPM quote doesn't cover the report data",
+            ));
+        }
+        Ok(TpmAttestation {
+            attestation_key: Measurement::from(<[u8; 32]>::from(Sha256::digest(public_area))),
+            pcr_selection: pcr_selection.to_vec(),
+            pcr_digest,
+        })
+    }
+
+    fn loaded(&self) -> Result<&Loaded> {
+        self.loaded.as_ref().ok_or(PodError::EnclaveNotLoaded)
+    }
+
+    fn loaded_mut(&mut self) -> Result<&mut Loaded> {
+        self.loaded.as_mut().ok_or(PodError::EnclaveNotLoaded)
+    }
+
+    /// Runs `tool` of `tpm2-tools`, feeding it `input` on stdin, and returns
+    /// its stdout.
+    fn run<I, S>(&self, tool: &str, args: I, input: Option<&[u8]>) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(tool);
+        command
+            .args(args)
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(tcti) = &self.tcti {
+            command.env(TCTI_ENV, tcti);
+        }
+        let mut child = command.spawn().map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => {
+                PodError::Tpm(format!("{} not found, is tpm2-tools installed?", tool))
+            }
+            _ => PodError::Io(err),
+        })?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(PodError::Tpm(format!("{} failed: {}", tool, stderr.trim())));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Creates the storage primary key, saving its context to `context`.
+    fn create_storage_key(&self, context: &OsStr) -> Result<()> {
+        self.run(
+            "tpm2_createprimary",
+            [
+                OsStr::new("-C"),
+                OsStr::new("o"),
+                OsStr::new("-g"),
+                OsStr::new("sha256"),
+                OsStr::new("-G"),
+                OsStr::new(STORAGE_KEY_ALGORITHM),
+                OsStr::new("-c"),
+                context,
+            ]
+            .iter(),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Creates the attestation key, saving its context to `context`, and
+    /// returns its `TPM2B_PUBLIC`.
+    fn create_attestation_key(&self, work_dir: &WorkDir, context: &OsStr) -> Result<Vec<u8>> {
+        self.run(
+            "tpm2_createprimary",
+            [
+                OsStr::new("-C"),
+                OsStr::new("e"),
+                OsStr::new("-g"),
+                OsStr::new("sha256"),
+                OsStr::new("-G"),
+                OsStr::new(AK_ALGORITHM),
+                OsStr::new("-a"),
+                OsStr::new(AK_ATTRIBUTES),
+                OsStr::new("-c"),
+                context,
+            ]
+            .iter(),
+            None,
+        )?;
+        let public = work_dir.path("ak.pub");
+        self.run(
+            "tpm2_readpublic",
+            [
+                OsStr::new("-c"),
+                context,
+                OsStr::new("-o"),
+                public.as_os_str(),
+            ]
+            .iter(),
+            None,
+        )?;
+        Ok(fs::read(public)?)
+    }
+
+    /// Seals `root_secret` into a new data object under the storage primary
+    /// key. The secret is passed on stdin, so it never touches the disk.
+    fn seal_root_secret(&self, root_secret: &[u8]) -> Result<SealedObject> {
+        let work_dir = WorkDir::new()?;
+        let primary = work_dir.path("primary.ctx");
+        let public = work_dir.path("sealed.pub");
+        let private = work_dir.path("sealed.priv");
+        self.create_storage_key(primary.as_os_str())?;
+        self.run(
+            "tpm2_create",
+            [
+                OsStr::new("-C"),
+                primary.as_os_str(),
+                OsStr::new("-g"),
+                OsStr::new("sha256"),
+                OsStr::new("-i"),
+                OsStr::new("-"),
+                OsStr::new("-u"),
+                public.as_os_str(),
+                OsStr::new("-r"),
+                private.as_os_str(),
+            ]
+            .iter(),
+            Some(root_secret),
+        )?;
+        Ok(SealedObject {
+            public: fs::read(public)?,
+            private: fs::read(private)?,
+        })
+    }
+
+    fn unseal_root_secret(&self, sealed_object: &SealedObject) -> Result<Vec<u8>> {
+        let work_dir = WorkDir::new()?;
+        let primary = work_dir.path("primary.ctx");
+        let public = work_dir.path("sealed.pub");
+        let private = work_dir.path("sealed.priv");
+        let sealed = work_dir.path("sealed.ctx");
+        fs::write(&public, &sealed_object.public)?;
+        fs::write(&private, &sealed_object.private)?;
+        self.create_storage_key(primary.as_os_str())?;
+        // The storage key could be created, so the TPM is there; failing to
+        // load the object means it was sealed by another TPM (or tampered with).
+        let unsealed = self
+            .run(
+                "tpm2_load",
+                [
+                    OsStr::new("-C"),
+                    primary.as_os_str(),
+                    OsStr::new("-u"),
+                    public.as_os_str(),
+                    OsStr::new("-r"),
+                    private.as_os_str(),
+                    OsStr::new("-c"),
+                    sealed.as_os_str(),
+                ]
+                .iter(),
+                None,
+            )
+            .and_then(|_| {
+                self.run(
+                    "tpm2_unseal",
+                    [OsStr::new("-c"), sealed.as_os_str()].iter(),
+                    None,
+                )
+            });
+        match unsealed {
+            Ok(root_secret) if root_secret.len() == ROOT_SECRET_SIZE => Ok(root_secret),
+            Ok(_) => Err(PodError::SealedStateInvalid),
+            Err(err) => {
+                log::debug!("Unsealing root secret failed: {}", err);
+                Err(PodError::SealedStateInvalid)
+            }
+        }
+    }
+
+    fn read_attestation_key(&self) -> Result<Vec<u8>> {
+        let work_dir = WorkDir::new()?;
+        let context = work_dir.path("ak.ctx");
+        self.create_attestation_key(&work_dir, context.as_os_str())
+    }
+}
+
+/// Result of verifying a quote generated by a `TpmBackend`, see
+/// [`TpmBackend::verify_quote`].
+///
+/// [`TpmBackend::verify_quote`]: struct.TpmBackend.html#method.verify_quote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmAttestation {
+    attestation_key: Measurement,
+    pcr_selection: Vec<u8>,
+    pcr_digest: Vec<u8>,
+}
+
+impl TpmAttestation {
+    /// Returns the SHA-256 of the public area of the attestation key which
+    /// signed the quote, see [`TpmBackend::attestation_key`].
+    ///
+    /// [`TpmBackend::attestation_key`]: struct.TpmBackend.html#method.attestation_key
+    pub fn attestation_key(&self) -> &Measurement {
+        &self.attestation_key
+    }
+
+    /// Returns the quoted PCR selection, as a raw `TPML_PCR_SELECTION`.
+    pub fn pcr_selection(&self) -> &[u8] {
+        &self.pcr_selection
+    }
+
+    /// Returns the digest of the quoted PCRs' values, which reflects the boot
+    /// chain of the machine.
+    pub fn pcr_digest(&self) -> &[u8] {
+        &self.pcr_digest
+    }
+}
+
+/// Temporary directory, readable by the owner only, for the files exchanged
+/// with `tpm2-tools`. None of them hold secrets in the clear.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new() -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pod-tpm-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        DirBuilder::new().mode(0o700).create(&path)?;
+        Ok(Self(path))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Sealed keys are `magic || public length (u32 LE) || public || private
+/// length (u32 LE) || private || keys sealed with the root secret`.
+fn encode_sealed_keys(sealed_object: &SealedObject, keys: Vec<u8>) -> Result<Vec<u8>> {
+    let mut sealed_keys = SEALED_MAGIC.to_vec();
+    for part in &[&sealed_object.public, &sealed_object.private] {
+        let length = u32::try_from(part.len()).map_err(|_| PodError::SealingFailed)?;
+        sealed_keys.extend_from_slice(&length.to_le_bytes());
+        sealed_keys.extend_from_slice(part);
+    }
+    sealed_keys.extend_from_slice(&keys);
+    Ok(sealed_keys)
+}
+
+fn decode_sealed_keys(sealed_keys: &[u8]) -> Result<(SealedObject, &[u8])> {
+    if !sealed_keys.starts_with(SEALED_MAGIC) {
+        return Err(PodError::SealedStateInvalid);
+    }
+    let mut reader = LeReader(&sealed_keys[SEALED_MAGIC.len()..]);
+    let mut part = || {
+        reader
+            .sized()
+            .map(<[u8]>::to_vec)
+            .map_err(|_| PodError::SealedStateInvalid)
+    };
+    let sealed_object = SealedObject {
+        public: part()?,
+        private: part()?,
+    };
+    Ok((sealed_object, reader.0))
+}
+
+fn quote_body(report_data: &[u8]) -> Vec<u8> {
+    let mut body = vec![0u8; QUOTE_BODY_SIZE];
+    body[..2].copy_from_slice(&QUOTE_VERSION.to_le_bytes());
+    body[QUOTE_BASENAME_OFFSET..QUOTE_BASENAME_OFFSET + QUOTE_MARKER.len()]
+        .copy_from_slice(QUOTE_MARKER);
+    body[QUOTE_REPORT_DATA_OFFSET..][..QUOTE_REPORT_DATA_SIZE].copy_from_slice(report_data);
+    body
+}
+
+/// Returns the `TPMT_PUBLIC` within a `TPM2B_PUBLIC`.
+fn public_area(public: &[u8]) -> Result<&[u8]> {
+    let mut reader = BeReader(public);
+    let public_area = reader.sized()?;
+    if !reader.0.is_empty() {
+        return Err(PodError::InvalidQuote(
+            "trailing bytes after TPM public key",
+        ));
+    }
+    Ok(public_area)
+}
+
+/// Parses the `TPMT_PUBLIC` of the attestation key, which has to be a
+/// restricted ECDSA P-256 signing key with SHA-256.
+fn parse_attestation_key(public_area: &[u8]) -> Result<VerifyingKey> {
+    let mut reader = BeReader(public_area);
+    let key_type = reader.u16()?;
+    let name_alg = reader.u16()?;
+    let attributes = reader.u32()?;
+    let required = TPMA_OBJECT_FIXEDTPM | TPMA_OBJECT_RESTRICTED | TPMA_OBJECT_SIGN;
+    if key_type != TPM_ALG_ECC || name_alg != TPM_ALG_SHA256 || attributes & required != required {
+        return Err(PodError::InvalidQuote(
+            "TPM attestation key is not a restricted ECC signing key",
+        ));
+    }
+    reader.sized()?; // authPolicy
+    let symmetric = reader.u16()?;
+    let scheme = reader.u16()?;
+    let scheme_hash = if scheme == TPM_ALG_NULL {
+        TPM_ALG_NULL
+    } else {
+        reader.u16()?
+    };
+    let curve = reader.u16()?;
+    let kdf = reader.u16()?;
+    if symmetric != TPM_ALG_NULL
+        || scheme != TPM_ALG_ECDSA
+        || scheme_hash != TPM_ALG_SHA256
+        || curve != TPM_ECC_NIST_P256
+        || kdf != TPM_ALG_NULL
+    {
+        return Err(PodError::InvalidQuote(
+            "TPM attestation key is not an ECDSA P-256 key with SHA-256",
+        ));
+    }
+    let x = field_bytes(reader.sized()?)?;
+    let y = field_bytes(reader.sized()?)?;
+    if !reader.0.is_empty() {
+        return Err(PodError::InvalidQuote(
+            "trailing bytes after TPM public key",
+        ));
+    }
+    VerifyingKey::from_encoded_point(&EncodedPoint::from_affine_coordinates(&x, &y, false))
+        .map_err(|_| PodError::InvalidQuote("invalid TPM attestation key"))
+}
+
+/// Parses a `TPMT_SIGNATURE`, which has to be ECDSA with SHA-256.
+fn parse_signature(signature: &[u8]) -> Result<Signature> {
+    let mut reader = BeReader(signature);
+    if reader.u16()? != TPM_ALG_ECDSA || reader.u16()? != TPM_ALG_SHA256 {
+        return Err(PodError::InvalidSignature);
+    }
+    let r = field_bytes(reader.sized()?)?;
+    let s = field_bytes(reader.sized()?)?;
+    if !reader.0.is_empty() {
+        return Err(PodError::InvalidSignature);
+    }
+    Signature::from_scalars(r, s).map_err(|_| PodError::InvalidSignature)
+}
+
+/// Left-pads a big-endian P-256 coordinate or scalar, which the TPM may
+/// return with its leading zeros stripped.
+fn field_bytes(bytes: &[u8]) -> Result<FieldBytes> {
+    let mut field_bytes = FieldBytes::default();
+    let offset = field_bytes
+        .len()
+        .checked_sub(bytes.len())
+        .ok_or(PodError::InvalidQuote("TPM ECC parameter too long"))?;
+    field_bytes[offset..].copy_from_slice(bytes);
+    Ok(field_bytes)
+}
+
+/// Reader of TPM structures, which are big-endian, with `TPM2B` buffers
+/// prefixed by their `u16` size.
+struct BeReader<'a>(&'a [u8]);
+
+impl<'a> BeReader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        if self.0.len() < size {
+            return Err(PodError::InvalidQuote("TPM structure truncated"));
+        }
+        let (taken, rest) = self.0.split_at(size);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, size: usize) -> Result<()> {
+        self.take(size).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn sized(&mut self) -> Result<&'a [u8]> {
+        let size = self.u16()?;
+        self.take(size.into())
+    }
+
+    /// Reads a `TPML_PCR_SELECTION`, returning it raw.
+    fn pcr_selection(&mut self) -> Result<&'a [u8]> {
+        let start = self.0;
+        for _ in 0..self.u32()? {
+            self.u16()?; // hash
+            let size = self.u8()?;
+            self.skip(size.into())?;
+        }
+        Ok(&start[..start.len() - self.0.len()])
+    }
+}
+
+/// Reader of the TPM evidence appended to the quote body, whose parts are
+/// prefixed by their `u32` (little-endian) size like the signature of SGX
+/// quotes.
+struct LeReader<'a>(&'a [u8]);
+
+impl<'a> LeReader<'a> {
+    fn sized(&mut self) -> Result<&'a [u8]> {
+        let part = self
+            .0
+            .get(..4)
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+            .and_then(|size| self.0[4..].get(..size))
+            .ok_or(PodError::InvalidQuote("TPM evidence truncated"))?;
+        self.0 = &self.0[4 + part.len()..];
+        Ok(part)
+    }
+}
+
+fn push_sized(bytes: &mut Vec<u8>, part: &[u8]) -> Result<()> {
+    let size =
+        u32::try_from(part.len()).map_err(|_| PodError::InvalidQuote("TPM evidence too large"))?;
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(part);
+    Ok(())
+}
+
+impl EnclaveBackend for TpmBackend {
+    fn init(&mut self) -> Result<Vec<u8>> {
+        let mut root_secret = [0u8; ROOT_SECRET_SIZE];
+        OsRng.fill_bytes(&mut root_secret);
+        let sealed_object = self.seal_root_secret(&root_secret)?;
+        let attestation_key = self.read_attestation_key()?;
+        let mut keys = SoftwareBackend::new(root_secret);
+        let sealed_keys = encode_sealed_keys(&sealed_object, keys.init()?)?;
+        self.loaded = Some(Loaded {
+            sealed_object,
+            keys,
+            attestation_key,
+        });
+        Ok(sealed_keys)
+    }
+
+    fn load(&mut self, sealed_keys: &[u8]) -> Result<()> {
+        let (sealed_object, keys_sealed) = decode_sealed_keys(sealed_keys)?;
+        let root_secret = self.unseal_root_secret(&sealed_object)?;
+        let mut keys = SoftwareBackend::new(root_secret);
+        keys.load(keys_sealed)?;
+        let attestation_key = self.read_attestation_key()?;
+        self.loaded = Some(Loaded {
+            sealed_object,
+            keys,
+            attestation_key,
+        });
+        Ok(())
+    }
+
+    fn reseal(&self) -> Result<Vec<u8>> {
+        let loaded = self.loaded()?;
+        encode_sealed_keys(&loaded.sealed_object, loaded.keys.reseal()?)
+    }
+
+    fn rotate_key(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+    ) -> Result<(Vec<u8>, Ed25519Signature)> {
+        let loaded = self.loaded_mut()?;
+        let (keys_sealed, signature) = loaded.keys.rotate_key(relying_party)?;
+        Ok((
+            encode_sealed_keys(&loaded.sealed_object, keys_sealed)?,
+            signature,
+        ))
+    }
+
+    fn unload(&mut self) -> Result<()> {
+        self.loaded = None;
+        Ok(())
+    }
+
+    fn get_quote(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        _spid: &Spid,
+        _quote_type: QuoteType,
+        _nonce: Option<&[u8; 16]>,
+    ) -> Result<Quote> {
+        // Neither the SPID, the quote type nor the nonce mean anything to a
+        // TPM. The qualifying data is hashed, as TPMs without SHA-512 accept
+        // 34 bytes of it at most.
+        let public_key = self.loaded()?.keys.public_key(relying_party)?;
+        let report_data = public_key.to_report_data(user_data);
+        let mut quote = quote_body(&report_data);
+
+        let work_dir = WorkDir::new()?;
+        let context = work_dir.path("ak.ctx");
+        let attest = work_dir.path("attest.bin");
+        let signature = work_dir.path("signature.bin");
+        let attestation_key = self.create_attestation_key(&work_dir, context.as_os_str())?;
+        let qualifying_data = hex::encode(Sha256::digest(&report_data));
+        self.run(
+            "tpm2_quote",
+            [
+                OsStr::new("-c"),
+                context.as_os_str(),
+                OsStr::new("-l"),
+                OsStr::new(PCR_SELECTION),
+                OsStr::new("-q"),
+                OsStr::new(&qualifying_data),
+                OsStr::new("-g"),
+                OsStr::new("sha256"),
+                OsStr::new("-m"),
+                attest.as_os_str(),
+                OsStr::new("-s"),
+                signature.as_os_str(),
+            ]
+            .iter(),
+            None,
+        )?;
+        push_sized(&mut quote, &fs::read(attest)?)?;
+        push_sized(&mut quote, &fs::read(signature)?)?;
+        push_sized(&mut quote, &attestation_key)?;
+        Ok(Quote::from(quote))
+    }
+
+    fn seal_data(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        self.loaded()?.keys.seal_data(data, aad)
+    }
+
+    fn unseal_data(&self, sealed_data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        self.loaded()?.keys.unseal_data(sealed_data)
+    }
+
+    fn sign(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        message: &[u8],
+    ) -> Result<Ed25519Signature> {
+        self.loaded()?.keys.sign(relying_party, message)
+    }
+
+    fn sign_prehashed(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        prehashed: Sha512,
+    ) -> Result<Ed25519Signature> {
+        self.loaded()?.keys.sign_prehashed(relying_party, prehashed)
+    }
+
+    fn public_key(&self, relying_party: Option<&RelyingParty>) -> Result<Ed25519PublicKey> {
+        self.loaded()?.keys.public_key(relying_party)
+    }
+
+    fn report(&self) -> Result<EnclaveReport> {
+        // There's no enclave to measure; use a fixed MRENCLAVE, and the
+        // attestation key as the signer, which ties sealed keys to the TPM.
+        let mut mr_enclave = [0u8; 32];
+        mr_enclave.copy_from_slice(&Sha256::digest(QUOTE_MARKER));
+        let identity = EnclaveIdentity::new(mr_enclave, *self.attestation_key()?.as_bytes());
+        Ok(EnclaveReport::new(identity, 0, 0, 0, 0))
+    }
+
+    fn seal_algorithm(&self) -> SealAlgorithm {
+        SealAlgorithm::TpmSealedObject
+    }
+}
@@ -1,26 +1,133 @@
+// Error codes returned (negated) by the `pod_*` functions, see `pod_sgx.h`.
+pub(super) const POD_ERR_GENERIC: libc::c_int = 1;
+pub(super) const POD_ERR_ENCLAVE_NOT_FOUND: libc::c_int = 3;
+pub(super) const POD_ERR_ENCLAVE_LOAD: libc::c_int = 4;
+pub(super) const POD_ERR_NOT_LOADED: libc::c_int = 5;
+pub(super) const POD_ERR_SEALED_STATE: libc::c_int = 6;
+pub(super) const POD_ERR_AESM_UNAVAILABLE: libc::c_int = 7;
+pub(super) const POD_ERR_INVALID_SPID: libc::c_int = 8;
+pub(super) const POD_ERR_BUFFER_TOO_SMALL: libc::c_int = 9;
+pub(super) const POD_ERR_QUOTE: libc::c_int = 10;
+pub(super) const POD_ERR_SIGN: libc::c_int = 11;
+pub(super) const POD_ERR_LAUNCH_DENIED: libc::c_int = 12;
+
+/// Opaque `pod_enclave_t` handle.
+#[repr(C)]
+pub(super) struct PodEnclave {
+    _private: [u8; 0],
+}
+
+// Log levels passed to the log callback, see `pod_sgx.h`.
+pub(super) const POD_LOG_ERROR: libc::c_int = 1;
+pub(super) const POD_LOG_WARN: libc::c_int = 2;
+pub(super) const POD_LOG_INFO: libc::c_int = 3;
+pub(super) const POD_LOG_DEBUG: libc::c_int = 4;
+
+/// `pod_log_callback_t`
+pub(super) type LogCallback = extern "C" fn(level: libc::c_int, message: *const libc::c_char);
+
 #[link(name = "pod_sgx")]
 extern "C" {
     pub(super) fn set_verbose(verbose: bool);
+    pub(super) fn pod_last_sgx_status() -> u32;
+    pub(super) fn pod_set_log_callback(callback: Option<LogCallback>);
     pub(super) fn pod_init_enclave(
         enclave_path: *const libc::c_char,
+        launch_mode: u16,
+        seal_policy: u16,
         sealed_keys: *mut u8,
         sealed_keys_size: usize,
+        enclave: *mut *mut PodEnclave,
     ) -> libc::c_int;
     pub(super) fn pod_load_enclave(
         enclave_path: *const libc::c_char,
+        launch_mode: u16,
         sealed_keys: *const u8,
         sealed_keys_size: usize,
+        enclave: *mut *mut PodEnclave,
+    ) -> libc::c_int;
+    pub(super) fn pod_reseal_keys(
+        enclave: *const PodEnclave,
+        seal_policy: u16,
+        sealed_keys: *mut u8,
+        sealed_keys_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_rotate_key(
+        enclave: *mut PodEnclave,
+        seal_policy: u16,
+        relying_party: *const u8,
+        relying_party_size: usize,
+        sealed_keys: *mut u8,
+        sealed_keys_size: usize,
+        signature: *mut libc::c_void,
+        signature_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_unload_enclave(enclave: *mut PodEnclave) -> libc::c_int;
+    pub(super) fn pod_get_public_key(
+        enclave: *const PodEnclave,
+        relying_party: *const u8,
+        relying_party_size: usize,
+        pubkey: *mut u8,
+        pubkey_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_get_report_body(
+        enclave: *const PodEnclave,
+        report_body: *mut u8,
+        report_body_size: usize,
     ) -> libc::c_int;
-    pub(super) fn pod_unload_enclave() -> libc::c_int;
     pub(super) fn pod_get_quote(
+        enclave: *const PodEnclave,
+        relying_party: *const u8,
+        relying_party_size: usize,
+        user_data: *const libc::c_void,
+        user_data_size: usize,
         sp_id_str: *const libc::c_char,
         sp_quote_type_str: *const libc::c_char,
+        nonce: *const u8,
         quote_buffer: *mut u8,
         quote_buffer_size: usize,
     ) -> libc::c_int;
     pub(super) fn pod_sign_buffer(
+        enclave: *const PodEnclave,
+        relying_party: *const u8,
+        relying_party_size: usize,
+        data: *const libc::c_void,
+        data_size: usize,
+        signature: *mut libc::c_void,
+        signature_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_sealed_data_size(aad_size: usize, data_size: usize) -> usize;
+    pub(super) fn pod_seal_data(
+        enclave: *const PodEnclave,
+        seal_policy: u16,
+        aad: *const libc::c_void,
+        aad_size: usize,
         data: *const libc::c_void,
         data_size: usize,
+        sealed_data: *mut u8,
+        sealed_data_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_get_sealed_data_sizes(
+        sealed_data: *const u8,
+        sealed_data_size: usize,
+        aad_size: *mut usize,
+        data_size: *mut usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_unseal_data(
+        enclave: *const PodEnclave,
+        sealed_data: *const u8,
+        sealed_data_size: usize,
+        aad: *mut libc::c_void,
+        aad_size: usize,
+        data: *mut libc::c_void,
+        data_size: usize,
+    ) -> libc::c_int;
+    pub(super) fn pod_sign_prehashed(
+        enclave: *const PodEnclave,
+        relying_party: *const u8,
+        relying_party_size: usize,
+        digest: *const u8,
+        digest_size: usize,
         signature: *mut libc::c_void,
         signature_size: usize,
     ) -> libc::c_int;
@@ -0,0 +1,98 @@
+use super::{QuoteType, RelyingParty, Spid};
+use rust_sgx_util::Quote;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Quotes are cached per relying party, i.e., per derived key, user data,
+/// SPID, quote type and nonce, so that a quote requested by one is never
+/// handed out to another.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct QuoteKey {
+    relying_party: Option<RelyingParty>,
+    user_data: Vec<u8>,
+    spid: Spid,
+    quote_type: QuoteType,
+    nonce: Option<[u8; 16]>,
+}
+
+impl QuoteKey {
+    fn new(
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+    ) -> Self {
+        Self {
+            relying_party: relying_party.cloned(),
+            user_data: user_data.to_vec(),
+            spid: *spid,
+            quote_type,
+            nonce: nonce.copied(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct QuoteCache {
+    lifetime: Duration,
+    entries: HashMap<QuoteKey, (Instant, Quote)>,
+}
+
+impl QuoteCache {
+    pub(crate) fn new(lifetime: Duration) -> Self {
+        Self {
+            lifetime,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn set_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime = lifetime;
+    }
+
+    pub(crate) fn get(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+    ) -> Option<Quote> {
+        self.purge_expired();
+        self.entries
+            .get(&QuoteKey::new(
+                relying_party,
+                user_data,
+                spid,
+                quote_type,
+                nonce,
+            ))
+            .map(|(_, quote)| quote.clone())
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+        quote: Quote,
+    ) {
+        self.entries.insert(
+            QuoteKey::new(relying_party, user_data, spid, quote_type, nonce),
+            (Instant::now(), quote),
+        );
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn purge_expired(&mut self) {
+        let lifetime = self.lifetime;
+        self.entries
+            .retain(|_, (created, _)| created.elapsed() < lifetime);
+    }
+}
@@ -0,0 +1,256 @@
+use super::{Ed25519PublicKey, Ed25519Signature, EnclaveIdentity, PodError, Result};
+#[cfg(feature = "with_serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+const CONTINUITY_DOMAIN: &[u8] = b"pod-enclave-continuity-v1\0";
+
+/// Statement that the enclave's private key moved from one enclave build to
+/// another, signed by that very key. Produced by [`PodEnclave::migrate`].
+///
+/// Since the key itself doesn't change, relying parties which only know the
+/// public key keep accepting the device as is. The statement is meant for
+/// those which also track the enclave's identity: it lets them accept the
+/// new MRENCLAVE without registering the device anew.
+///
+/// With `with_serde` feature enabled, `ContinuityStatement` can be serialized
+/// and deserialized as base64 `String`.
+///
+/// [`PodEnclave::migrate`]: struct.PodEnclave.html#method.migrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinuityStatement {
+    public_key: Ed25519PublicKey,
+    old_identity: EnclaveIdentity,
+    new_identity: EnclaveIdentity,
+    signature: Ed25519Signature,
+}
+
+impl ContinuityStatement {
+    /// Length of the encoded statement in bytes.
+    pub const LENGTH: usize = Ed25519PublicKey::LENGTH + 4 * 32 + Ed25519Signature::LENGTH;
+
+    pub(crate) fn new(
+        public_key: Ed25519PublicKey,
+        old_identity: EnclaveIdentity,
+        new_identity: EnclaveIdentity,
+        signature: Ed25519Signature,
+    ) -> Self {
+        Self {
+            public_key,
+            old_identity,
+            new_identity,
+            signature,
+        }
+    }
+
+    /// Returns the message signed by the statement.
+    pub(crate) fn message(
+        public_key: &Ed25519PublicKey,
+        old_identity: &EnclaveIdentity,
+        new_identity: &EnclaveIdentity,
+    ) -> Vec<u8> {
+        let mut message = Vec::with_capacity(CONTINUITY_DOMAIN.len() + Self::LENGTH);
+        message.extend_from_slice(CONTINUITY_DOMAIN);
+        message.extend_from_slice(public_key.as_bytes());
+        message.extend_from_slice(old_identity.mr_enclave());
+        message.extend_from_slice(old_identity.mr_signer());
+        message.extend_from_slice(new_identity.mr_enclave());
+        message.extend_from_slice(new_identity.mr_signer());
+        message
+    }
+
+    /// Returns the public key which was migrated.
+    pub fn public_key(&self) -> &Ed25519PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the identity of the enclave the key was migrated from.
+    pub fn old_identity(&self) -> &EnclaveIdentity {
+        &self.old_identity
+    }
+
+    /// Returns the identity of the enclave the key was migrated to.
+    pub fn new_identity(&self) -> &EnclaveIdentity {
+        &self.new_identity
+    }
+
+    /// Verifies that the statement was signed by [`public_key`].
+    ///
+    /// [`public_key`]: #method.public_key
+    pub fn verify(&self) -> Result<()> {
+        let message = Self::message(&self.public_key, &self.old_identity, &self.new_identity);
+        self.public_key.verify(&message, &self.signature)
+    }
+
+    /// Encodes the statement as raw bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::message(&self.public_key, &self.old_identity, &self.new_identity)
+            .split_off(CONTINUITY_DOMAIN.len());
+        bytes.extend_from_slice(self.signature.as_bytes());
+        bytes
+    }
+
+    /// Decodes the statement from raw bytes. Note that this doesn't
+    /// [`verify`] it.
+    ///
+    /// [`verify`]: #method.verify
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LENGTH {
+            return Err(PodError::InvalidContinuityStatement);
+        }
+        let (public_key, rest) = bytes.split_at(Ed25519PublicKey::LENGTH);
+        let (identities, signature) = rest.split_at(4 * 32);
+        let identity = |offset: usize| {
+            let mut mr_enclave = [0u8; 32];
+            mr_enclave.copy_from_slice(&identities[offset..offset + 32]);
+            let mut mr_signer = [0u8; 32];
+            mr_signer.copy_from_slice(&identities[offset + 32..offset + 64]);
+            EnclaveIdentity::new(mr_enclave, mr_signer)
+        };
+        Ok(Self {
+            public_key: Ed25519PublicKey::from_bytes(public_key)?,
+            old_identity: identity(0),
+            new_identity: identity(64),
+            signature: Ed25519Signature::from_bytes(signature)?,
+        })
+    }
+
+    /// Decodes the statement from base64 string.
+    pub fn from_base64<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&base64::decode(s)?)
+    }
+
+    /// Encodes the statement as base64 string.
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.to_bytes())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for ContinuityStatement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for ContinuityStatement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_base64(&string).map_err(de::Error::custom)
+    }
+}
+
+const KEY_ROTATION_DOMAIN: &[u8] = b"pod-enclave-key-rotation-v1\0";
+
+/// Proof that the holder of the old private key vouches for a new key pair,
+/// i.e., the signature of the new public key made with the old private key.
+/// Produced by [`PodEnclave::rotate_key`] and [`PodEnclave::rotate_key_for`].
+///
+/// With `with_serde` feature enabled, `KeyRotation` can be serialized and
+/// deserialized as base64 `String`.
+///
+/// [`PodEnclave::rotate_key`]: struct.PodEnclave.html#method.rotate_key
+/// [`PodEnclave::rotate_key_for`]: struct.PodEnclave.html#method.rotate_key_for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRotation {
+    old_public_key: Ed25519PublicKey,
+    new_public_key: Ed25519PublicKey,
+    signature: Ed25519Signature,
+}
+
+impl KeyRotation {
+    /// Length of the encoded proof in bytes.
+    pub const LENGTH: usize = 2 * Ed25519PublicKey::LENGTH + Ed25519Signature::LENGTH;
+
+    pub(crate) fn new(
+        old_public_key: Ed25519PublicKey,
+        new_public_key: Ed25519PublicKey,
+        signature: Ed25519Signature,
+    ) -> Self {
+        Self {
+            old_public_key,
+            new_public_key,
+            signature,
+        }
+    }
+
+    /// Returns the message signed with the old private key, i.e.,
+    /// `"pod-enclave-key-rotation-v1\0" || old public key || new public key`.
+    pub fn message(
+        old_public_key: &Ed25519PublicKey,
+        new_public_key: &Ed25519PublicKey,
+    ) -> Vec<u8> {
+        let mut message = Vec::with_capacity(KEY_ROTATION_DOMAIN.len() + 2 * 32);
+        message.extend_from_slice(KEY_ROTATION_DOMAIN);
+        message.extend_from_slice(old_public_key.as_bytes());
+        message.extend_from_slice(new_public_key.as_bytes());
+        message
+    }
+
+    /// Returns the public key which was replaced.
+    pub fn old_public_key(&self) -> &Ed25519PublicKey {
+        &self.old_public_key
+    }
+
+    /// Returns the public key which replaced the old one.
+    pub fn new_public_key(&self) -> &Ed25519PublicKey {
+        &self.new_public_key
+    }
+
+    /// Verifies that the new public key was signed with the old private key.
+    pub fn verify(&self) -> Result<()> {
+        let message = Self::message(&self.old_public_key, &self.new_public_key);
+        self.old_public_key.verify(&message, &self.signature)
+    }
+
+    /// Encodes the proof as raw bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::LENGTH);
+        bytes.extend_from_slice(self.old_public_key.as_bytes());
+        bytes.extend_from_slice(self.new_public_key.as_bytes());
+        bytes.extend_from_slice(self.signature.as_bytes());
+        bytes
+    }
+
+    /// Decodes the proof from raw bytes. Note that this doesn't [`verify`]
+    /// it.
+    ///
+    /// [`verify`]: #method.verify
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LENGTH {
+            return Err(PodError::InvalidKeyRotation);
+        }
+        let (old_public_key, rest) = bytes.split_at(Ed25519PublicKey::LENGTH);
+        let (new_public_key, signature) = rest.split_at(Ed25519PublicKey::LENGTH);
+        Ok(Self {
+            old_public_key: Ed25519PublicKey::from_bytes(old_public_key)?,
+            new_public_key: Ed25519PublicKey::from_bytes(new_public_key)?,
+            signature: Ed25519Signature::from_bytes(signature)?,
+        })
+    }
+
+    /// Decodes the proof from base64 string.
+    pub fn from_base64<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&base64::decode(s)?)
+    }
+
+    /// Encodes the proof as base64 string.
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.to_bytes())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for KeyRotation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for KeyRotation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_base64(&string).map_err(de::Error::custom)
+    }
+}
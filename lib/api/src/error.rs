@@ -0,0 +1,197 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Convenience wrapper around fallible operation.
+pub type Result<T> = std::result::Result<T, PodError>;
+
+/// Error type thrown by fallible operations in this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum PodError {
+    /// Enclave binary doesn't exist or couldn't be opened.
+    #[error("enclave not found at '{}'", _0.display())]
+    EnclaveNotFound(PathBuf),
+    /// Loading or initializing the enclave failed.
+    #[error("loading enclave failed: {}", _0)]
+    EnclaveLoadFailed(SgxStatus),
+    /// The enclave (or backend) is not loaded.
+    #[error("enclave not loaded")]
+    EnclaveNotLoaded,
+    /// Sealed state is corrupt, or was sealed by another enclave.
+    #[error("sealed state is corrupt or was sealed by another enclave")]
+    SealedStateInvalid,
+    /// Data to seal is empty, or too large together with its additional
+    /// authenticated data, see [`MAX_SEALED_DATA_SIZE`].
+    ///
+    /// [`MAX_SEALED_DATA_SIZE`]: constant.MAX_SEALED_DATA_SIZE.html
+    #[error("invalid size of data to seal: {} bytes", _0)]
+    InvalidSealDataSize(usize),
+    /// Sealed data is corrupt, or was sealed by another enclave or on
+    /// another machine.
+    #[error("sealed data is corrupt or was sealed by another enclave")]
+    SealedDataInvalid,
+    /// Sealed key file is truncated or fails its checksum.
+    #[error("sealed key file is corrupt: {}", _0)]
+    SealedFileCorrupt(&'static str),
+    /// Sealed key file uses a format version or algorithm this version of
+    /// `pod-api` doesn't know.
+    #[error("unsupported sealed key file: {}", _0)]
+    SealedFileUnsupported(String),
+    /// Sealed key file is intact, but doesn't belong to this enclave.
+    #[error("sealed key file doesn't belong to this enclave: {}", _0)]
+    SealedFileMismatch(String),
+    /// Accessing the sealed key store failed, e.g., the Secret Service
+    /// isn't running.
+    #[error("sealed key store: {}", _0)]
+    SealedStore(String),
+    /// Sealing policy is neither `mrenclave` nor `mrsigner`.
+    #[error("invalid sealing policy '{}'", _0)]
+    InvalidSealPolicy(String),
+    /// Measurement is not a 64 character hex string.
+    #[error("invalid measurement '{}'", _0)]
+    InvalidMeasurement(String),
+    /// Enclave binary doesn't contain a valid SIGSTRUCT.
+    #[error("invalid enclave SIGSTRUCT: {}", _0)]
+    InvalidSigStruct(&'static str),
+    /// Enclave binary isn't the expected one, e.g., it's signed with another
+    /// key.
+    #[error("untrusted enclave: {}", _0)]
+    UntrustedEnclave(String),
+    /// Launch mode is neither `production` nor `debug`.
+    #[error("invalid launch mode '{}'", _0)]
+    InvalidLaunchMode(String),
+    /// The enclave isn't signed with a key allowed to launch it in production
+    /// mode.
+    #[error("enclave is not allowed to run in production mode")]
+    LaunchDenied,
+    /// Sealing the private key failed.
+    #[error("sealing private key failed")]
+    SealingFailed,
+    /// AESM service is not running or couldn't be reached.
+    #[error("AESM service unavailable: {}", _0)]
+    AesmUnavailable(SgxStatus),
+    /// SPID is not a 32 character hex string.
+    #[error("invalid SPID '{}'", _0)]
+    InvalidSpid(String),
+    /// Relying party identifier is empty or too long.
+    #[error("invalid relying party '{}'", _0)]
+    InvalidRelyingParty(String),
+    /// Buffer provided for the quote (or sealed state) is too small.
+    #[error("quote buffer too small")]
+    QuoteBufferTooSmall,
+    /// Generating the quote failed.
+    #[error("generating quote failed: {}", _0)]
+    QuoteFailed(SgxStatus),
+    /// Signing failed.
+    #[error("signing failed: {}", _0)]
+    SigningFailed(SgxStatus),
+    /// Bytes don't encode a valid ED25519 public key.
+    #[error("invalid ED25519 public key")]
+    InvalidPublicKey,
+    /// Signature is malformed, or doesn't match the message and public key.
+    #[error("invalid ED25519 signature")]
+    InvalidSignature,
+    /// Continuity statement is malformed.
+    #[error("invalid continuity statement")]
+    InvalidContinuityStatement,
+    /// Signing purpose is not one of the known ones.
+    #[error("invalid signing purpose '{}'", _0)]
+    InvalidSigningPurpose(String),
+    /// Signing payload can't be encoded, e.g., its challenge is too long.
+    #[error("invalid signing payload")]
+    InvalidSigningPayload,
+    /// Raw signing was requested without opting in to it.
+    #[error("raw signing is disabled")]
+    RawSigningDisabled,
+    /// Call to `AsyncPodEnclave` didn't complete within its timeout.
+    #[error("enclave call timed out after {:?}", _0)]
+    Timeout(std::time::Duration),
+    /// Worker thread of `AsyncPodEnclave` stopped, e.g., because a call
+    /// panicked.
+    #[error("enclave worker thread stopped")]
+    WorkerStopped,
+    /// Key rotation proof is malformed.
+    #[error("invalid key rotation")]
+    InvalidKeyRotation,
+    /// Quote is malformed.
+    #[error("invalid quote: {}", _0)]
+    InvalidQuote(&'static str),
+    /// Running `tpm2-tools` failed, e.g., because the TPM couldn't be
+    /// reached.
+    #[error("TPM call failed: {}", _0)]
+    Tpm(String),
+    /// Unexpected failure within the SGX layer.
+    #[error("SGX call failed: {}", _0)]
+    Sgx(SgxStatus),
+    /// Error while decoding hex string.
+    #[error("decoding hex: {}", _0)]
+    Hex(#[from] hex::FromHexError),
+    /// Error while decoding base64 string.
+    #[error("decoding base64: {}", _0)]
+    Base64(#[from] base64::DecodeError),
+    /// Error while accessing `Quote`.
+    #[error("accessing quote: {}", _0)]
+    RustSgxUtil(#[from] rust_sgx_util::Error),
+    /// I/O error, e.g., while reading or writing sealed state.
+    #[error("I/O error: {}", _0)]
+    Io(#[from] io::Error),
+    /// Found unexpected interior nul byte.
+    #[error("unexpected interior nul byte: {}", _0)]
+    Nul(#[from] std::ffi::NulError),
+}
+
+/// Raw `sgx_status_t` code as returned by the SGX SDK.
+///
+/// `SgxStatus` displays as hex code, followed by its symbolic name if known.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::SgxStatus;
+/// assert_eq!(SgxStatus(0x4001).to_string(), "0x4001 (SGX_ERROR_SERVICE_UNAVAILABLE)");
+/// assert_eq!(SgxStatus(0x1234).to_string(), "0x1234");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SgxStatus(pub u32);
+
+impl SgxStatus {
+    /// Returns the symbolic name of this status, e.g. `SGX_ERROR_BUSY`, if known.
+    pub fn name(self) -> Option<&'static str> {
+        let name = match self.0 {
+            0x0000 => "SGX_SUCCESS",
+            0x0001 => "SGX_ERROR_UNEXPECTED",
+            0x0002 => "SGX_ERROR_INVALID_PARAMETER",
+            0x0003 => "SGX_ERROR_OUT_OF_MEMORY",
+            0x0004 => "SGX_ERROR_ENCLAVE_LOST",
+            0x2001 => "SGX_ERROR_INVALID_ENCLAVE",
+            0x2002 => "SGX_ERROR_INVALID_ENCLAVE_ID",
+            0x2003 => "SGX_ERROR_INVALID_SIGNATURE",
+            0x2004 => "SGX_ERROR_NDEBUG_ENCLAVE",
+            0x2005 => "SGX_ERROR_OUT_OF_EPC",
+            0x2006 => "SGX_ERROR_NO_DEVICE",
+            0x200f => "SGX_ERROR_ENCLAVE_FILE_ACCESS",
+            0x3001 => "SGX_ERROR_MAC_MISMATCH",
+            0x3002 => "SGX_ERROR_INVALID_ATTRIBUTE",
+            0x4001 => "SGX_ERROR_SERVICE_UNAVAILABLE",
+            0x4002 => "SGX_ERROR_SERVICE_TIMEOUT",
+            0x4003 => "SGX_ERROR_AE_INVALID_EPIDBLOB",
+            0x4004 => "SGX_ERROR_SERVICE_INVALID_PRIVILEGE",
+            0x4005 => "SGX_ERROR_EPID_MEMBER_REVOKED",
+            0x4006 => "SGX_ERROR_UPDATE_NEEDED",
+            0x4007 => "SGX_ERROR_NETWORK_FAILURE",
+            0x400a => "SGX_ERROR_BUSY",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for SgxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#06x}", self.0)?;
+        if let Some(name) = self.name() {
+            write!(f, " ({})", name)?;
+        }
+        Ok(())
+    }
+}
@@ -1,63 +1,639 @@
+#[cfg(feature = "async")]
+mod asynchronous;
+mod backend;
+#[cfg(feature = "sgx")]
 mod c;
+mod cache;
+mod continuity;
+mod error;
+mod payload;
+mod sealed;
+mod sigstruct;
+mod store;
+#[cfg(test)]
+mod testing;
+mod types;
+#[cfg(feature = "sgx")]
 mod wrappers;
 
-use anyhow::Result;
+use cache::QuoteCache;
 use rust_sgx_util::Quote;
-use std::cell::RefCell;
-use std::fs;
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 
+#[cfg(feature = "async")]
+pub use asynchronous::AsyncPodEnclave;
+#[cfg(feature = "sgx")]
+pub use backend::sgx::SgxBackend;
+pub use backend::software::SoftwareBackend;
+#[cfg(feature = "tpm")]
+pub use backend::tpm::{TpmAttestation, TpmBackend};
+pub use backend::EnclaveBackend;
+pub use continuity::{ContinuityStatement, KeyRotation};
+pub use error::{PodError, Result, SgxStatus};
+pub use payload::{SigningPayload, SigningPurpose};
+pub use sealed::{SealAlgorithm, SealPolicy};
+pub use sigstruct::SigStruct;
+pub use store::file::FileStore;
+pub use store::keyring::KeyringStore;
+pub use store::secret_service::SecretServiceStore;
+pub use store::{SealedStore, StoreLock};
+pub use types::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveIdentity, EnclaveReport, Fingerprint, LaunchMode,
+    Measurement, RelyingParty, Spid,
+};
+#[cfg(feature = "sgx")]
+#[allow(deprecated)]
 pub use wrappers::set_verbose;
 
+/// How long a generated quote is reused for by default before a fresh one is
+/// requested from the backend.
+pub const DEFAULT_QUOTE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum size of data sealed with [`PodEnclave::seal`], together with its
+/// additional authenticated data.
+///
+/// [`PodEnclave::seal`]: struct.PodEnclave.html#method.seal
+pub const MAX_SEALED_DATA_SIZE: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum QuoteType {
     Linkable,
     Unlinkable,
 }
 
+/// Handle to a loaded enclave.
+///
+/// `PodEnclave` is `Send + Sync`: calls into the backend are serialized
+/// internally, so a single instance can be shared across threads (e.g., in an
+/// `Arc`). Each instance loads its own enclave, so several of them can be
+/// loaded at the same time.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pod_api::{PodEnclave, SigningPayload, SigningPurpose, SoftwareBackend};
+/// # fn main() -> pod_api::Result<()> {
+/// let pod_enclave = PodEnclave::with_backend(SoftwareBackend::new("secret"), "private_key.sealed")?;
+/// let payload = SigningPayload::now(
+///     "https://example.com".parse()?,
+///     SigningPurpose::Authentication,
+///     &b"challenge"[..],
+/// );
+/// let signature = pod_enclave.sign_payload(&payload)?;
+/// payload.verify(&pod_enclave.public_key()?, &signature)?;
+/// # Ok(())
+/// # }
+/// ```
 pub struct PodEnclave {
-    quote: RefCell<Option<Quote>>,
+    backend: Mutex<Box<dyn EnclaveBackend>>,
+    quotes: Mutex<QuoteCache>,
+    store: Box<dyn SealedStore>,
+    raw_signing: bool,
 }
 
 impl PodEnclave {
+    #[cfg(feature = "sgx")]
     pub fn new<P: AsRef<Path>>(enclave_path: P, sealed_keys_path: P) -> Result<Self> {
-        if !sealed_keys_path.as_ref().exists() {
-            // Initialize enclave for the first time
-            let sealed_keys = wrappers::init_enclave(enclave_path)?;
-            // Save state to file
-            fs::write(sealed_keys_path.as_ref(), &sealed_keys)?;
-            sealed_keys
-        } else {
-            // Load state from file
-            let sealed_keys = fs::read(sealed_keys_path.as_ref())?;
-            wrappers::load_enclave(enclave_path, &sealed_keys)?;
-            sealed_keys
-        };
-        Ok(Self {
-            quote: RefCell::new(None),
-        })
-    }
-
-    pub fn get_quote<S: AsRef<str>>(&self, spid: S, quote_type: QuoteType) -> Result<Quote> {
-        let quote = match self.quote.borrow_mut().take() {
-            Some(quote) => quote,
-            None => {
-                let quote = wrappers::get_quote(spid.as_ref(), quote_type)?;
-                quote
-            }
+        Self::with_backend(SgxBackend::new(enclave_path), sealed_keys_path)
+    }
+
+    /// Loads the enclave using `backend`, restoring its private key from
+    /// `sealed_keys_path`. If the file doesn't exist yet, a fresh key pair is
+    /// generated and the sealed private key written there instead.
+    ///
+    /// The sealed keys are stored in a versioned, checksummed format which
+    /// records the identity of the enclave that sealed them. The file is
+    /// written atomically, readable by its owner only, and guarded by an
+    /// advisory lock (`.<file name>.lock` next to it) so that several
+    /// processes can safely start at once.
+    pub fn with_backend<B, P>(backend: B, sealed_keys_path: P) -> Result<Self>
+    where
+        B: EnclaveBackend + 'static,
+        P: AsRef<Path>,
+    {
+        Self::with_store(backend, FileStore::new(sealed_keys_path))
+    }
+
+    /// Same as [`with_backend`], but keeps the sealed private key in `store`,
+    /// e.g., the kernel keyring or the Secret Service, rather than in a file.
+    ///
+    /// [`with_backend`]: #method.with_backend
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pod_api::{PodEnclave, Result, SealedStore, SoftwareBackend, StoreLock};
+    /// # use std::sync::{Arc, Mutex};
+    /// #[derive(Clone, Default)]
+    /// struct MemoryStore(Arc<Mutex<Option<Vec<u8>>>>);
+    ///
+    /// impl SealedStore for MemoryStore {
+    ///     fn load(&self) -> Result<Option<Vec<u8>>> {
+    ///         Ok(self.0.lock().unwrap().clone())
+    ///     }
+    ///
+    ///     fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+    ///         *self.0.lock().unwrap() = Some(sealed_keys.to_vec());
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn lock(&self) -> Result<StoreLock> {
+    ///         // Not shared with other processes.
+    ///         Ok(StoreLock::new(()))
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> pod_api::Result<()> {
+    /// let store = MemoryStore::default();
+    /// let pub_key = PodEnclave::with_store(SoftwareBackend::new("secret"), store.clone())?.public_key()?;
+    /// let pod_enclave = PodEnclave::with_store(SoftwareBackend::new("secret"), store)?;
+    /// assert_eq!(pod_enclave.public_key()?, pub_key);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_store<B, S>(backend: B, store: S) -> Result<Self>
+    where
+        B: EnclaveBackend + 'static,
+        S: SealedStore + 'static,
+    {
+        // Box the backend up front so that it gets unloaded on `Drop`
+        // even if initialisation fails midway.
+        let pod_enclave = Self::from_backend(Box::new(backend), Box::new(store));
+        sealed::load_or_init(&mut **pod_enclave.backend(), &*pod_enclave.store)?;
+        Ok(pod_enclave)
+    }
+
+    /// Migrates the private key sealed at `sealed_keys_path` by the enclave at
+    /// `old_enclave_path` over to the enclave at `new_enclave_path`, e.g.,
+    /// after shipping a new version of `pod_enclave.signed.so`, and loads the
+    /// latter.
+    ///
+    /// Both enclaves need to be signed with the same key: the private key is
+    /// passed between them sealed with [`SealPolicy::MrSigner`]. The new
+    /// enclave then reseals it with its default policy.
+    ///
+    /// [`SealPolicy::MrSigner`]: enum.SealPolicy.html#variant.MrSigner
+    #[cfg(feature = "sgx")]
+    pub fn migrate<P: AsRef<Path>>(
+        old_enclave_path: P,
+        new_enclave_path: P,
+        sealed_keys_path: P,
+    ) -> Result<(Self, ContinuityStatement)> {
+        Self::migrate_with_backends(
+            SgxBackend::new(old_enclave_path).with_seal_policy(SealPolicy::MrSigner),
+            SgxBackend::new(new_enclave_path),
+            sealed_keys_path,
+        )
+    }
+
+    /// Same as [`migrate`] but with arbitrary backends. `old_backend` must
+    /// seal the private key such that `new_backend` can unseal it.
+    ///
+    /// Returns the loaded enclave, and a [`ContinuityStatement`] signed by the
+    /// migrated key.
+    ///
+    /// [`migrate`]: #method.migrate
+    /// [`ContinuityStatement`]: struct.ContinuityStatement.html
+    pub fn migrate_with_backends<B1, B2, P>(
+        old_backend: B1,
+        new_backend: B2,
+        sealed_keys_path: P,
+    ) -> Result<(Self, ContinuityStatement)>
+    where
+        B1: EnclaveBackend,
+        B2: EnclaveBackend + 'static,
+        P: AsRef<Path>,
+    {
+        Self::migrate_with_store(old_backend, new_backend, FileStore::new(sealed_keys_path))
+    }
+
+    /// Same as [`migrate_with_backends`], but with the sealed private key kept
+    /// in `store`, see [`with_store`].
+    ///
+    /// [`migrate_with_backends`]: #method.migrate_with_backends
+    /// [`with_store`]: #method.with_store
+    pub fn migrate_with_store<B1, B2, S>(
+        mut old_backend: B1,
+        new_backend: B2,
+        store: S,
+    ) -> Result<(Self, ContinuityStatement)>
+    where
+        B1: EnclaveBackend,
+        B2: EnclaveBackend + 'static,
+        S: SealedStore + 'static,
+    {
+        let pod_enclave = Self::from_backend(Box::new(new_backend), Box::new(store));
+        let identities = sealed::migrate(
+            &mut old_backend,
+            &mut **pod_enclave.backend(),
+            &*pod_enclave.store,
+        );
+        old_backend.unload()?;
+        let (old_identity, new_identity) = identities?;
+
+        let public_key = pod_enclave.public_key()?;
+        let message = ContinuityStatement::message(&public_key, &old_identity, &new_identity);
+        let signature = pod_enclave.backend().sign(None, &message)?;
+        let statement = ContinuityStatement::new(public_key, old_identity, new_identity, signature);
+        Ok((pod_enclave, statement))
+    }
+
+    fn from_backend(backend: Box<dyn EnclaveBackend>, store: Box<dyn SealedStore>) -> Self {
+        Self {
+            backend: Mutex::new(backend),
+            quotes: Mutex::new(QuoteCache::new(DEFAULT_QUOTE_LIFETIME)),
+            store,
+            raw_signing: false,
+        }
+    }
+
+    /// Replaces the enclave's key pair with a freshly generated one, and
+    /// persists it sealed in place of the old one.
+    ///
+    /// Returns a [`KeyRotation`] proving that the holder of the old key
+    /// vouches for the new one, and a fresh quote binding the new key to the
+    /// enclave (and to `user_data`, see [`get_quote`]), so that relying
+    /// parties can swap the registered key for the new one. Cached quotes are
+    /// dropped.
+    ///
+    /// [`KeyRotation`]: struct.KeyRotation.html
+    /// [`get_quote`]: #method.get_quote
+    pub fn rotate_key<B: AsRef<[u8]>>(
+        &self,
+        spid: &Spid,
+        quote_type: QuoteType,
+        user_data: B,
+    ) -> Result<(KeyRotation, Quote)> {
+        let rotation = self.rotate(None)?;
+        let quote = self.get_quote(spid, quote_type, user_data)?;
+        Ok((rotation, quote))
+    }
+
+    /// Same as [`rotate_key`] but the [`KeyRotation`] and the quote are for
+    /// the keys derived for `relying_party` (see [`public_key_for`]), i.e.,
+    /// the ones that relying party has registered. The master key, and with
+    /// it every derived key, is replaced all the same.
+    ///
+    /// [`rotate_key`]: #method.rotate_key
+    /// [`KeyRotation`]: struct.KeyRotation.html
+    /// [`public_key_for`]: #method.public_key_for
+    pub fn rotate_key_for<B: AsRef<[u8]>>(
+        &self,
+        relying_party: &RelyingParty,
+        spid: &Spid,
+        quote_type: QuoteType,
+        user_data: B,
+    ) -> Result<(KeyRotation, Quote)> {
+        let rotation = self.rotate(Some(relying_party))?;
+        let quote = self.get_quote_for(relying_party, spid, quote_type, user_data)?;
+        Ok((rotation, quote))
+    }
+
+    fn rotate(&self, relying_party: Option<&RelyingParty>) -> Result<KeyRotation> {
+        let rotation = {
+            let mut backend = self.backend();
+            let old_public_key = backend.public_key(relying_party)?;
+            let signature = sealed::rotate_key(&mut **backend, &*self.store, relying_party)?;
+            KeyRotation::new(
+                old_public_key,
+                backend.public_key(relying_party)?,
+                signature,
+            )
         };
-        let ret = quote.clone();
-        *self.quote.borrow_mut() = Some(quote);
-        Ok(ret)
+        self.invalidate_quotes();
+        Ok(rotation)
+    }
+
+    /// Returns a quote for remote attestation with the IAS.
+    ///
+    /// The quote's `report_data` holds the public key followed by
+    /// `SHA-256(public key || user_data)`. Passing a nonce issued by the
+    /// verifier as `user_data` ties the quote to that verifier's request, so
+    /// that it can't be replayed; the verifier checks the binding with
+    /// [`Ed25519PublicKey::from_report_data`]. `user_data` may be empty.
+    ///
+    /// Quotes are cached per SPID, quote type and user data for the duration
+    /// of the quote lifetime (see [`set_quote_lifetime`]).
+    ///
+    /// [`Ed25519PublicKey::from_report_data`]: struct.Ed25519PublicKey.html#method.from_report_data
+    /// [`set_quote_lifetime`]: #method.set_quote_lifetime
+    pub fn get_quote<B: AsRef<[u8]>>(
+        &self,
+        spid: &Spid,
+        quote_type: QuoteType,
+        user_data: B,
+    ) -> Result<Quote> {
+        self.cached_quote(None, user_data.as_ref(), spid, quote_type, None)
+    }
+
+    /// Same as [`get_quote`] but the quote binds the key derived for
+    /// `relying_party` (see [`public_key_for`]) instead of the master key.
+    ///
+    /// [`get_quote`]: #method.get_quote
+    /// [`public_key_for`]: #method.public_key_for
+    pub fn get_quote_for<B: AsRef<[u8]>>(
+        &self,
+        relying_party: &RelyingParty,
+        spid: &Spid,
+        quote_type: QuoteType,
+        user_data: B,
+    ) -> Result<Quote> {
+        self.cached_quote(
+            Some(relying_party),
+            user_data.as_ref(),
+            spid,
+            quote_type,
+            None,
+        )
     }
 
-    pub fn sign<B: AsRef<[u8]>>(&self, message: B) -> Result<Vec<u8>> {
-        let signature = wrappers::sign_buffer(message)?;
-        Ok(signature)
+    /// Same as [`get_quote`] but uses the provided `nonce` for the Quoting
+    /// Enclave's report instead of a random one. Quotes generated with different
+    /// nonces are cached separately.
+    ///
+    /// [`get_quote`]: #method.get_quote
+    pub fn get_quote_with_nonce<B: AsRef<[u8]>>(
+        &self,
+        spid: &Spid,
+        quote_type: QuoteType,
+        user_data: B,
+        nonce: &[u8; 16],
+    ) -> Result<Quote> {
+        self.cached_quote(None, user_data.as_ref(), spid, quote_type, Some(nonce))
+    }
+
+    /// Sets how long generated quotes are reused for. Defaults to
+    /// [`DEFAULT_QUOTE_LIFETIME`]. A zero lifetime disables caching.
+    ///
+    /// [`DEFAULT_QUOTE_LIFETIME`]: constant.DEFAULT_QUOTE_LIFETIME.html
+    pub fn set_quote_lifetime(&mut self, lifetime: Duration) {
+        self.quotes().set_lifetime(lifetime);
+    }
+
+    /// Drops all cached quotes, forcing the next call to [`get_quote`] to
+    /// generate a fresh one.
+    ///
+    /// [`get_quote`]: #method.get_quote
+    pub fn invalidate_quotes(&self) {
+        self.quotes().clear();
+    }
+
+    fn cached_quote(
+        &self,
+        relying_party: Option<&RelyingParty>,
+        user_data: &[u8],
+        spid: &Spid,
+        quote_type: QuoteType,
+        nonce: Option<&[u8; 16]>,
+    ) -> Result<Quote> {
+        let cached = self
+            .quotes()
+            .get(relying_party, user_data, spid, quote_type, nonce);
+        if let Some(quote) = cached {
+            return Ok(quote);
+        }
+        let quote = self
+            .backend()
+            .get_quote(relying_party, user_data, spid, quote_type, nonce)?;
+        self.quotes().insert(
+            relying_party,
+            user_data,
+            spid,
+            quote_type,
+            nonce,
+            quote.clone(),
+        );
+        Ok(quote)
+    }
+
+    // A panic while holding either lock leaves nothing half-updated that we
+    // care about, so recover from poisoning rather than propagate it.
+    fn backend(&self) -> MutexGuard<'_, Box<dyn EnclaveBackend>> {
+        self.backend.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn quotes(&self) -> MutexGuard<'_, QuoteCache> {
+        self.quotes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the report of the loaded enclave: its MRENCLAVE and MRSIGNER,
+    /// ISVPRODID, ISVSVN and attributes, e.g., whether it runs in debug mode.
+    /// Like [`public_key`], this doesn't need the AESM service, and is meant
+    /// for telling which enclave build is actually in use.
+    ///
+    /// [`public_key`]: #method.public_key
+    pub fn identity(&self) -> Result<EnclaveReport> {
+        self.backend().report()
+    }
+
+    /// Returns the public key of the enclave.
+    ///
+    /// Unlike extracting it from a quote's `report_data`, this requires
+    /// neither an SPID nor the AESM service.
+    pub fn public_key(&self) -> Result<Ed25519PublicKey> {
+        self.backend().public_key(None)
+    }
+
+    /// Returns the public key derived for `relying_party`.
+    ///
+    /// Every relying party gets its own key pair, derived inside the enclave
+    /// from the master key, so relying parties can't link the device by its
+    /// public key. The same relying party always gets the same key, until
+    /// the master key is rotated (see [`rotate_key`]).
+    ///
+    /// [`rotate_key`]: #method.rotate_key
+    pub fn public_key_for(&self, relying_party: &RelyingParty) -> Result<Ed25519PublicKey> {
+        self.backend().public_key(Some(relying_party))
+    }
+
+    /// Signs `payload` with the private key.
+    ///
+    /// This is the preferred way of signing challenges: the signature is only
+    /// valid for the origin, purpose and time named in the payload, see
+    /// [`SigningPayload`].
+    ///
+    /// [`SigningPayload`]: struct.SigningPayload.html
+    pub fn sign_payload(&self, payload: &SigningPayload) -> Result<Ed25519Signature> {
+        self.backend().sign(None, &payload.to_bytes()?)
+    }
+
+    /// Same as [`sign_payload`] but uses the key derived for `relying_party`
+    /// (see [`public_key_for`]).
+    ///
+    /// [`sign_payload`]: #method.sign_payload
+    /// [`public_key_for`]: #method.public_key_for
+    pub fn sign_payload_for(
+        &self,
+        relying_party: &RelyingParty,
+        payload: &SigningPayload,
+    ) -> Result<Ed25519Signature> {
+        self.backend()
+            .sign(Some(relying_party), &payload.to_bytes()?)
+    }
+
+    /// Signs everything read from `reader` with the private key, using
+    /// Ed25519ph (RFC 8032), i.e., signing the SHA-512 hash of the message.
+    ///
+    /// The message is hashed as it is read, and only the hash is passed to
+    /// the enclave, so messages of any size (e.g., documents or release
+    /// artifacts) can be signed without loading them into memory. Verify the
+    /// signature with [`Ed25519PublicKey::verify_reader`], or any other
+    /// Ed25519ph implementation with empty context.
+    ///
+    /// Ed25519ph signatures can't be mistaken for plain Ed25519 ones, so unlike
+    /// [`sign`], this is always allowed.
+    ///
+    /// [`Ed25519PublicKey::verify_reader`]: struct.Ed25519PublicKey.html#method.verify_reader
+    /// [`sign`]: #method.sign
+    pub fn sign_reader<R: Read>(&self, reader: R) -> Result<Ed25519Signature> {
+        let prehashed = types::prehash(reader)?;
+        self.backend().sign_prehashed(None, prehashed)
+    }
+
+    /// Same as [`sign_reader`] but uses the key derived for `relying_party`
+    /// (see [`public_key_for`]).
+    ///
+    /// [`sign_reader`]: #method.sign_reader
+    /// [`public_key_for`]: #method.public_key_for
+    pub fn sign_reader_for<R: Read>(
+        &self,
+        relying_party: &RelyingParty,
+        reader: R,
+    ) -> Result<Ed25519Signature> {
+        let prehashed = types::prehash(reader)?;
+        self.backend()
+            .sign_prehashed(Some(relying_party), prehashed)
+    }
+
+    /// Signs the contents of the file at `path`, see [`sign_reader`].
+    ///
+    /// [`sign_reader`]: #method.sign_reader
+    pub fn sign_file<P: AsRef<Path>>(&self, path: P) -> Result<Ed25519Signature> {
+        self.sign_reader(File::open(path)?)
+    }
+
+    /// Seals `data`, e.g., an access token or a vault key, so that it can only
+    /// be unsealed with [`unseal`] by this enclave on this machine (or, with
+    /// [`SealPolicy::MrSigner`], by any enclave signed with the same key).
+    ///
+    /// `aad` is stored alongside in plain text, and authenticated together
+    /// with `data`, e.g., to record what the secret is for. Sealed data can't
+    /// be mistaken for the sealed private key, and vice versa.
+    ///
+    /// `data` must not be empty, and together with `aad` fit in
+    /// [`MAX_SEALED_DATA_SIZE`], otherwise [`PodError::InvalidSealDataSize`]
+    /// is returned.
+    ///
+    /// [`unseal`]: #method.unseal
+    /// [`SealPolicy::MrSigner`]: enum.SealPolicy.html#variant.MrSigner
+    /// [`MAX_SEALED_DATA_SIZE`]: constant.MAX_SEALED_DATA_SIZE.html
+    /// [`PodError::InvalidSealDataSize`]: enum.PodError.html#variant.InvalidSealDataSize
+    pub fn seal<B: AsRef<[u8]>, A: AsRef<[u8]>>(&self, data: B, aad: A) -> Result<Vec<u8>> {
+        let (data, aad) = (data.as_ref(), aad.as_ref());
+        let size = data.len().saturating_add(aad.len());
+        if data.is_empty() || size > MAX_SEALED_DATA_SIZE {
+            return Err(PodError::InvalidSealDataSize(size));
+        }
+        self.backend().seal_data(data, aad)
+    }
+
+    /// Unseals data sealed with [`seal`], returning the data and its
+    /// additional authenticated data. Fails with
+    /// [`PodError::SealedDataInvalid`] if the sealed data was tampered with,
+    /// or sealed by another enclave or on another machine.
+    ///
+    /// [`seal`]: #method.seal
+    /// [`PodError::SealedDataInvalid`]: enum.PodError.html#variant.SealedDataInvalid
+    pub fn unseal<B: AsRef<[u8]>>(&self, sealed_data: B) -> Result<(Vec<u8>, Vec<u8>)> {
+        self.backend().unseal_data(sealed_data.as_ref())
+    }
+
+    /// Allows signing arbitrary bytes with [`sign`] and [`sign_for`]. Disabled
+    /// by default, since whoever feeds those can get any other service's
+    /// challenge signed; prefer [`sign_payload`] instead.
+    ///
+    /// [`sign`]: #method.sign
+    /// [`sign_for`]: #method.sign_for
+    /// [`sign_payload`]: #method.sign_payload
+    pub fn set_raw_signing(&mut self, allowed: bool) {
+        self.raw_signing = allowed;
+    }
+
+    /// Signs raw `message` with the private key. Fails with
+    /// [`PodError::RawSigningDisabled`] unless enabled with
+    /// [`set_raw_signing`].
+    ///
+    /// [`PodError::RawSigningDisabled`]: enum.PodError.html#variant.RawSigningDisabled
+    /// [`set_raw_signing`]: #method.set_raw_signing
+    pub fn sign<B: AsRef<[u8]>>(&self, message: B) -> Result<Ed25519Signature> {
+        self.check_raw_signing()?;
+        self.backend().sign(None, message.as_ref())
+    }
+
+    /// Same as [`sign`] but uses the key derived for `relying_party` (see
+    /// [`public_key_for`]).
+    ///
+    /// [`sign`]: #method.sign
+    /// [`public_key_for`]: #method.public_key_for
+    pub fn sign_for<B: AsRef<[u8]>>(
+        &self,
+        relying_party: &RelyingParty,
+        message: B,
+    ) -> Result<Ed25519Signature> {
+        self.check_raw_signing()?;
+        self.backend().sign(Some(relying_party), message.as_ref())
+    }
+
+    fn check_raw_signing(&self) -> Result<()> {
+        if self.raw_signing {
+            Ok(())
+        } else {
+            Err(PodError::RawSigningDisabled)
+        }
     }
 }
 
 impl Drop for PodEnclave {
     fn drop(&mut self) {
-        wrappers::unload_enclave().expect("unloading enclave should succeed")
+        // Panicking here could abort the process, e.g., when dropped while
+        // unwinding from another panic.
+        if let Err(err) = self.backend().unload() {
+            log::error!("Couldn't unload the enclave: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{payload, TempDir};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn instances_are_independent_and_shareable() {
+        let dir = TempDir::new();
+        let first = dir.load("first.sealed", "secret").unwrap();
+        let second = dir.load("second.sealed", "secret").unwrap();
+        assert_ne!(first.public_key().unwrap(), second.public_key().unwrap());
+
+        let first = Arc::new(first);
+        let payload = payload("https://example.com");
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let first = Arc::clone(&first);
+                let payload = payload.clone();
+                thread::spawn(move || first.sign_payload(&payload))
+            })
+            .collect();
+        for handle in handles {
+            let signature = handle.join().unwrap().unwrap();
+            payload
+                .verify(&first.public_key().unwrap(), &signature)
+                .unwrap();
+        }
     }
 }
@@ -0,0 +1,232 @@
+use super::{Ed25519PublicKey, Ed25519Signature, PodError, RelyingParty, Result};
+#[cfg(feature = "with_serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PROTOCOL_TAG: &[u8] = b"pod-signing-payload-v1\0";
+
+/// What a [`SigningPayload`] is signed for.
+///
+/// `SigningPurpose` parses from and displays as `"authentication"` or
+/// `"confirmation"`. With `with_serde` feature enabled, it is also
+/// serialized as such.
+///
+/// [`SigningPayload`]: struct.SigningPayload.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SigningPurpose {
+    /// Proving possession of the key in a challenge-response login.
+    Authentication,
+    /// Confirming an action (e.g., a transaction) described by the challenge.
+    Confirmation,
+}
+
+impl SigningPurpose {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Authentication => 1,
+            Self::Confirmation => 2,
+        }
+    }
+}
+
+impl FromStr for SigningPurpose {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "authentication" => Ok(Self::Authentication),
+            "confirmation" => Ok(Self::Confirmation),
+            _ => Err(PodError::InvalidSigningPurpose(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for SigningPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Authentication => "authentication",
+            Self::Confirmation => "confirmation",
+        })
+    }
+}
+
+/// Domain-separated message signed instead of a raw challenge.
+///
+/// Besides the challenge itself, the payload names the origin of the relying
+/// party which issued it, what it is signed for, and when. A signature over a
+/// challenge issued by one service is thus useless to any other, since the
+/// latter verifies the payload against its own origin.
+///
+/// The payload is encoded as
+/// `"pod-signing-payload-v1\0" || purpose (u8) || origin length (u16) ||
+/// origin || challenge length (u32) || challenge || timestamp (u64)`, with
+/// integers in big endian, and the timestamp in seconds since the UNIX epoch.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pod_api::{PodEnclave, SigningPayload, SigningPurpose, SoftwareBackend};
+/// # fn main() -> pod_api::Result<()> {
+/// # let pod_enclave = PodEnclave::with_backend(SoftwareBackend::new("secret"), "private_key.sealed")?;
+/// let origin = "https://example.com".parse()?;
+/// let payload = SigningPayload::now(origin, SigningPurpose::Authentication, &b"challenge"[..]);
+/// let signature = pod_enclave.sign_payload(&payload)?;
+///
+/// // The relying party rebuilds the payload from what it expects.
+/// let expected = SigningPayload::new(
+///     "https://example.com".parse()?,
+///     SigningPurpose::Authentication,
+///     &b"challenge"[..],
+///     payload.timestamp(),
+/// );
+/// expected.verify(&pod_enclave.public_key()?, &signature)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningPayload {
+    origin: RelyingParty,
+    purpose: SigningPurpose,
+    challenge: Vec<u8>,
+    timestamp: u64,
+}
+
+impl SigningPayload {
+    /// Creates new payload. `timestamp` is in seconds since the UNIX epoch.
+    pub fn new<B: Into<Vec<u8>>>(
+        origin: RelyingParty,
+        purpose: SigningPurpose,
+        challenge: B,
+        timestamp: u64,
+    ) -> Self {
+        Self {
+            origin,
+            purpose,
+            challenge: challenge.into(),
+            timestamp,
+        }
+    }
+
+    /// Same as [`new`] but timestamped with the current time.
+    ///
+    /// [`new`]: #method.new
+    pub fn now<B: Into<Vec<u8>>>(
+        origin: RelyingParty,
+        purpose: SigningPurpose,
+        challenge: B,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        Self::new(origin, purpose, challenge, timestamp)
+    }
+
+    /// Returns the origin of the relying party which issued the challenge.
+    pub fn origin(&self) -> &RelyingParty {
+        &self.origin
+    }
+
+    /// Returns what the payload is signed for.
+    pub fn purpose(&self) -> SigningPurpose {
+        self.purpose
+    }
+
+    /// Returns the challenge issued by the relying party.
+    pub fn challenge(&self) -> &[u8] {
+        &self.challenge
+    }
+
+    /// Returns when the payload was created, in seconds since the UNIX epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Encodes the payload as the bytes which get signed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let origin = self.origin.as_bytes();
+        let origin_length = u16::try_from(origin.len())
+            .map_err(|_| PodError::InvalidRelyingParty(self.origin.to_string()))?;
+        let challenge_length =
+            u32::try_from(self.challenge.len()).map_err(|_| PodError::InvalidSigningPayload)?;
+
+        let mut bytes = Vec::with_capacity(
+            PROTOCOL_TAG.len() + 1 + 2 + origin.len() + 4 + self.challenge.len() + 8,
+        );
+        bytes.extend_from_slice(PROTOCOL_TAG);
+        bytes.push(self.purpose.to_u8());
+        bytes.extend_from_slice(&origin_length.to_be_bytes());
+        bytes.extend_from_slice(origin);
+        bytes.extend_from_slice(&challenge_length.to_be_bytes());
+        bytes.extend_from_slice(&self.challenge);
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        Ok(bytes)
+    }
+
+    /// Verifies that `signature` was made over this payload with the private
+    /// key matching `public_key`. Note that checking whether the timestamp is
+    /// recent enough is up to the caller.
+    pub fn verify(
+        &self,
+        public_key: &Ed25519PublicKey,
+        signature: &Ed25519Signature,
+    ) -> Result<()> {
+        public_key.verify(self.to_bytes()?, signature)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for SigningPurpose {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for SigningPurpose {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{payload, software_enclave};
+    use crate::PodError;
+
+    #[test]
+    fn payload_signatures_are_bound_to_origin() {
+        let (_dir, pod_enclave) = software_enclave();
+        let pub_key = pod_enclave.public_key().unwrap();
+        let signature = pod_enclave
+            .sign_payload(&payload("https://example.com"))
+            .unwrap();
+        payload("https://example.com")
+            .verify(&pub_key, &signature)
+            .unwrap();
+        assert!(payload("https://other.example")
+            .verify(&pub_key, &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn raw_signing_is_disabled_by_default() {
+        let (_dir, mut pod_enclave) = software_enclave();
+        match pod_enclave.sign(b"message") {
+            Err(PodError::RawSigningDisabled) => {}
+            _ => panic!("raw signing should be disabled"),
+        }
+
+        pod_enclave.set_raw_signing(true);
+        let signature = pod_enclave.sign(b"message").unwrap();
+        pod_enclave
+            .public_key()
+            .unwrap()
+            .verify(b"message", &signature)
+            .unwrap();
+    }
+}
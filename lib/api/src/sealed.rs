@@ -0,0 +1,498 @@
+//! Stored format of the sealed keys.
+//!
+//! The blob returned by [`EnclaveBackend::init`] is wrapped in a versioned
+//! envelope which records what sealed it:
+//!
+//! ```text
+//! magic "PODSEAL\0" | version: u16 | algorithm: u16 | MRENCLAVE: [u8; 32]
+//!   | MRSIGNER: [u8; 32] | created: u64 (UNIX time) | payload length: u32
+//!   | SHA-256 of all of the above and the payload: [u8; 32] | payload
+//! ```
+//!
+//! All integers are little-endian. Sealed keys without the magic were
+//! written by older versions of `pod-api`; they are loaded as-is and then
+//! rewritten in the new format. Likewise, keys sealed under another SGX
+//! sealing policy than the backend's are resealed after loading.
+//!
+//! [`EnclaveBackend::init`]: ../trait.EnclaveBackend.html#tymethod.init
+use super::{
+    Ed25519Signature, EnclaveBackend, EnclaveIdentity, PodError, RelyingParty, Result, SealedStore,
+};
+#[cfg(feature = "with_serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8] = b"PODSEAL\0";
+const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 8 + 2 + 2 + 32 + 32 + 8 + 4;
+const CHECKSUM_SIZE: usize = 32;
+
+/// Which enclave identity the SGX sealing key is derived from.
+///
+/// `SealPolicy` parses from and displays as `mrenclave` or `mrsigner`. With
+/// `with_serde` feature enabled, it is also serialized as such.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::SealPolicy;
+/// # fn main() -> pod_api::Result<()> {
+/// assert_eq!("mrsigner".parse::<SealPolicy>()?, SealPolicy::MrSigner);
+/// assert_eq!(SealPolicy::default().to_string(), "mrenclave");
+/// assert!("mrsomething".parse::<SealPolicy>().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SealPolicy {
+    /// Only this exact enclave build can unseal the keys. Upgrading the
+    /// enclave requires [`PodEnclave::migrate`]. This is the default.
+    ///
+    /// [`PodEnclave::migrate`]: struct.PodEnclave.html#method.migrate
+    #[default]
+    MrEnclave,
+    /// Any enclave signed with the same key, including future versions, can
+    /// unseal the keys.
+    MrSigner,
+}
+
+impl SealPolicy {
+    // SGX_KEYPOLICY_* values
+    #[cfg(feature = "sgx")]
+    pub(crate) fn to_raw(self) -> u16 {
+        match self {
+            Self::MrEnclave => 0x0001,
+            Self::MrSigner => 0x0002,
+        }
+    }
+}
+
+impl FromStr for SealPolicy {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mrenclave" => Ok(Self::MrEnclave),
+            "mrsigner" => Ok(Self::MrSigner),
+            _ => Err(PodError::InvalidSealPolicy(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for SealPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::MrEnclave => "mrenclave",
+            Self::MrSigner => "mrsigner",
+        })
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for SealPolicy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for SealPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Algorithm protecting the sealed keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SealAlgorithm {
+    /// `sgx_seal_data` (AES-GCM) with a key derived from MRENCLAVE.
+    SgxMrEnclave,
+    /// `sgx_seal_data` (AES-GCM) with a key derived from MRSIGNER.
+    SgxMrSigner,
+    /// ChaCha20-Poly1305 with a host-side key, see `SoftwareBackend`.
+    SoftwareChaCha20Poly1305,
+    /// TPM 2.0 sealed data object under the storage hierarchy, see
+    /// `TpmBackend`.
+    TpmSealedObject,
+}
+
+impl SealAlgorithm {
+    fn to_u16(self) -> u16 {
+        match self {
+            Self::SgxMrEnclave => 1,
+            Self::SoftwareChaCha20Poly1305 => 2,
+            Self::SgxMrSigner => 3,
+            Self::TpmSealedObject => 4,
+        }
+    }
+
+    fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            1 => Some(Self::SgxMrEnclave),
+            2 => Some(Self::SoftwareChaCha20Poly1305),
+            3 => Some(Self::SgxMrSigner),
+            4 => Some(Self::TpmSealedObject),
+            _ => None,
+        }
+    }
+
+    /// Whether keys sealed with `self` can be loaded by a backend sealing
+    /// with `other`. SGX unsealing doesn't depend on the policy.
+    fn can_load(self, other: Self) -> bool {
+        let is_sgx = |algorithm| matches!(algorithm, Self::SgxMrEnclave | Self::SgxMrSigner);
+        self == other || (is_sgx(self) && is_sgx(other))
+    }
+}
+
+struct Envelope {
+    algorithm: SealAlgorithm,
+    identity: EnclaveIdentity,
+    payload: Vec<u8>,
+}
+
+impl Envelope {
+    fn is_envelope(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + CHECKSUM_SIZE + self.payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.algorithm.to_u16().to_le_bytes());
+        bytes.extend_from_slice(self.identity.mr_enclave());
+        bytes.extend_from_slice(self.identity.mr_signer());
+        bytes.extend_from_slice(&created.to_le_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        let checksum = checksum(&bytes, &self.payload);
+        bytes.extend_from_slice(&checksum);
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE {
+            return Err(PodError::SealedFileCorrupt("file is truncated"));
+        }
+        let (header, rest) = bytes.split_at(HEADER_SIZE);
+        let (stored_checksum, payload) = rest.split_at(CHECKSUM_SIZE);
+        let payload_len = to_u32(&header[HEADER_SIZE - 4..]) as usize;
+        if payload.len() != payload_len {
+            return Err(PodError::SealedFileCorrupt("file is truncated"));
+        }
+        if checksum(header, payload)[..] != stored_checksum[..] {
+            return Err(PodError::SealedFileCorrupt("checksum mismatch"));
+        }
+
+        let version = to_u16(&header[8..10]);
+        if version != VERSION {
+            return Err(PodError::SealedFileUnsupported(format!(
+                "format version {}",
+                version
+            )));
+        }
+        let algorithm = to_u16(&header[10..12]);
+        let algorithm = SealAlgorithm::from_u16(algorithm).ok_or_else(|| {
+            PodError::SealedFileUnsupported(format!("sealing algorithm {}", algorithm))
+        })?;
+        let identity = EnclaveIdentity::new(to_hash(&header[12..44]), to_hash(&header[44..76]));
+        Ok(Self {
+            algorithm,
+            identity,
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+fn checksum(header: &[u8], payload: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(header);
+    hasher.update(payload);
+    to_hash(&hasher.finalize())
+}
+
+fn to_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes(<[u8; 2]>::try_from(bytes).expect("slice is 2 bytes long"))
+}
+
+fn to_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(<[u8; 4]>::try_from(bytes).expect("slice is 4 bytes long"))
+}
+
+fn to_hash(bytes: &[u8]) -> [u8; 32] {
+    <[u8; 32]>::try_from(bytes).expect("slice is 32 bytes long")
+}
+
+/// Loads the enclave from the sealed keys in `store`, or initializes it and
+/// stores the freshly sealed keys there if there are none yet.
+///
+/// The store is locked throughout, so that concurrently starting processes
+/// don't both generate a key.
+pub(crate) fn load_or_init(
+    backend: &mut dyn EnclaveBackend,
+    store: &dyn SealedStore,
+) -> Result<()> {
+    let _lock = store.lock()?;
+    match store.load()? {
+        None => {
+            // Initialize enclave for the first time
+            let payload = backend.init()?;
+            write(store, backend, payload)
+        }
+        Some(bytes) => {
+            if load(backend, &bytes)? {
+                let payload = backend.reseal()?;
+                write(store, backend, payload)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Moves the private key sealed in `store` from `old_backend` over to
+/// `new_backend`, and reseals it there. `old_backend` must seal such that
+/// `new_backend` can unseal, e.g., with `SealPolicy::MrSigner`.
+///
+/// Returns the identities of the old and the new enclave.
+pub(crate) fn migrate(
+    old_backend: &mut dyn EnclaveBackend,
+    new_backend: &mut dyn EnclaveBackend,
+    store: &dyn SealedStore,
+) -> Result<(EnclaveIdentity, EnclaveIdentity)> {
+    let _lock = store.lock()?;
+    let bytes = store.load()?.ok_or_else(|| {
+        PodError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no sealed keys to migrate",
+        ))
+    })?;
+    load(old_backend, &bytes)?;
+    let old_identity = *old_backend.report()?.identity();
+    let public_key = old_backend.public_key(None)?;
+    let transfer = old_backend.reseal()?;
+    old_backend.unload()?;
+
+    new_backend.load(&transfer)?;
+    if new_backend.public_key(None)? != public_key {
+        return Err(PodError::SealedFileMismatch(
+            "new enclave restored a different key".to_owned(),
+        ));
+    }
+    let payload = new_backend.reseal()?;
+    write(store, new_backend, payload)?;
+    Ok((old_identity, *new_backend.report()?.identity()))
+}
+
+/// Rotates the key of `backend`, and writes the new sealed keys to `store`.
+/// Returns the signature of the new public key made with the old private key,
+/// both those of `relying_party` if given.
+///
+/// If the new sealed keys can't be written, `backend` is restored to the old
+/// key, so that it keeps using the key `store` holds.
+pub(crate) fn rotate_key(
+    backend: &mut dyn EnclaveBackend,
+    store: &dyn SealedStore,
+    relying_party: Option<&RelyingParty>,
+) -> Result<Ed25519Signature> {
+    let _lock = store.lock()?;
+    let old_keys = backend.reseal()?;
+    let (payload, signature) = backend.rotate_key(relying_party)?;
+    if let Err(err) = write(store, backend, payload) {
+        if let Err(restore_err) = backend.load(&old_keys) {
+            log::error!(
+                "Couldn't restore the key after failed rotation: {}",
+                restore_err
+            );
+        }
+        return Err(err);
+    }
+    Ok(signature)
+}
+
+/// Loads `backend` from the stored sealed keys `bytes`. Returns whether they
+/// should be rewritten, i.e., they are in the legacy format, or were sealed
+/// with another algorithm than the backend's.
+fn load(backend: &mut dyn EnclaveBackend, bytes: &[u8]) -> Result<bool> {
+    if !Envelope::is_envelope(bytes) {
+        // Written by an older version
+        backend.load(bytes)?;
+        return Ok(true);
+    }
+
+    let envelope = Envelope::from_bytes(bytes)?;
+    if !envelope.algorithm.can_load(backend.seal_algorithm()) {
+        return Err(PodError::SealedFileMismatch(format!(
+            "sealed with {:?}, but this backend uses {:?}",
+            envelope.algorithm,
+            backend.seal_algorithm()
+        )));
+    }
+    // The checksum rules out corruption, so failing to unseal means the keys
+    // were sealed by another enclave (or on another machine).
+    backend.load(&envelope.payload).map_err(|err| match err {
+        PodError::SealedStateInvalid => PodError::SealedFileMismatch(format!(
+            "sealed by enclave with MRENCLAVE {}",
+            hex::encode(envelope.identity.mr_enclave())
+        )),
+        err => err,
+    })?;
+    Ok(envelope.algorithm != backend.seal_algorithm())
+}
+
+fn write(store: &dyn SealedStore, backend: &dyn EnclaveBackend, payload: Vec<u8>) -> Result<()> {
+    let envelope = Envelope {
+        algorithm: backend.seal_algorithm(),
+        identity: *backend.report()?.identity(),
+        payload,
+    };
+    store.store(&envelope.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::software_enclave;
+    use crate::{Ed25519PublicKey, PodEnclave, QuoteType, SoftwareBackend, StoreLock};
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory store whose writes fail while `failing` is set.
+    #[derive(Clone, Default)]
+    struct FailingStore {
+        sealed_keys: Arc<Mutex<Option<Vec<u8>>>>,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl SealedStore for FailingStore {
+        fn load(&self) -> Result<Option<Vec<u8>>> {
+            Ok(self.sealed_keys.lock().unwrap().clone())
+        }
+
+        fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(PodError::SealedStore("store unavailable".to_owned()));
+            }
+            *self.sealed_keys.lock().unwrap() = Some(sealed_keys.to_vec());
+            Ok(())
+        }
+
+        fn lock(&self) -> Result<StoreLock> {
+            Ok(StoreLock::new(()))
+        }
+    }
+
+    #[test]
+    fn failed_rotation_keeps_stored_key() {
+        let store = FailingStore::default();
+        let pod_enclave = PodEnclave::with_store(SoftwareBackend::new("secret"), store.clone())
+            .expect("enclave should load");
+        let public_key = pod_enclave.public_key().unwrap();
+        let stored = store.load().unwrap();
+
+        store.failing.store(true, Ordering::SeqCst);
+        let spid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+        assert!(pod_enclave
+            .rotate_key(&spid, QuoteType::Unlinkable, b"nonce")
+            .is_err());
+        assert_eq!(pod_enclave.public_key().unwrap(), public_key);
+        assert_eq!(store.load().unwrap(), stored);
+
+        store.failing.store(false, Ordering::SeqCst);
+        let (rotation, _) = pod_enclave
+            .rotate_key(&spid, QuoteType::Unlinkable, b"nonce")
+            .unwrap();
+        assert_eq!(rotation.old_public_key(), &public_key);
+        assert_ne!(pod_enclave.public_key().unwrap(), public_key);
+    }
+
+    #[test]
+    fn sealed_keys_are_reloaded_and_checked() {
+        let (dir, pod_enclave) = software_enclave();
+        let pub_key = pod_enclave.public_key().unwrap();
+        drop(pod_enclave);
+        let pod_enclave = dir.load("sealed_keys", "secret").unwrap();
+        assert_eq!(pod_enclave.public_key().unwrap(), pub_key);
+        drop(pod_enclave);
+
+        let path = dir.path("sealed_keys");
+        let mut sealed_keys = fs::read(&path).unwrap();
+        *sealed_keys.last_mut().unwrap() ^= 1;
+        fs::write(&path, &sealed_keys).unwrap();
+        match dir.load("sealed_keys", "secret") {
+            Err(PodError::SealedFileCorrupt(_)) => {}
+            _ => panic!("corruption should be detected"),
+        }
+    }
+
+    #[test]
+    fn migration_keeps_the_key() {
+        let (dir, pod_enclave) = software_enclave();
+        let pub_key = pod_enclave.public_key().unwrap();
+        drop(pod_enclave);
+
+        let (pod_enclave, statement) = PodEnclave::migrate_with_backends(
+            SoftwareBackend::new("secret"),
+            SoftwareBackend::new("secret"),
+            dir.path("sealed_keys"),
+        )
+        .unwrap();
+        assert_eq!(pod_enclave.public_key().unwrap(), pub_key);
+        assert_eq!(statement.public_key(), &pub_key);
+        statement.verify().unwrap();
+    }
+
+    #[test]
+    fn rotated_key_is_persisted() {
+        let (dir, pod_enclave) = software_enclave();
+        let old_pub_key = pod_enclave.public_key().unwrap();
+
+        let spid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+        let (rotation, _quote) = pod_enclave
+            .rotate_key(&spid, QuoteType::Unlinkable, b"nonce")
+            .unwrap();
+        rotation.verify().unwrap();
+        assert_eq!(rotation.old_public_key(), &old_pub_key);
+        assert_eq!(
+            rotation.new_public_key(),
+            &pod_enclave.public_key().unwrap()
+        );
+        drop(pod_enclave);
+
+        let pod_enclave = dir.load("sealed_keys", "secret").unwrap();
+        assert_eq!(
+            &pod_enclave.public_key().unwrap(),
+            rotation.new_public_key()
+        );
+    }
+
+    #[test]
+    fn rotation_for_relying_party_uses_derived_keys() {
+        let (_dir, pod_enclave) = software_enclave();
+        let origin = "https://example.com".parse().unwrap();
+        let old_pub_key = pod_enclave.public_key_for(&origin).unwrap();
+
+        let spid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+        let (rotation, quote) = pod_enclave
+            .rotate_key_for(&origin, &spid, QuoteType::Unlinkable, b"nonce")
+            .unwrap();
+        rotation.verify().unwrap();
+        assert_eq!(rotation.old_public_key(), &old_pub_key);
+        let new_pub_key = pod_enclave.public_key_for(&origin).unwrap();
+        assert_eq!(rotation.new_public_key(), &new_pub_key);
+        assert_eq!(
+            Ed25519PublicKey::from_report_data(quote.report_data().unwrap(), b"nonce").unwrap(),
+            new_pub_key
+        );
+    }
+}
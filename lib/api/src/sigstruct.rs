@@ -0,0 +1,150 @@
+use super::{EnclaveIdentity, Measurement, PodError, Result};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// `metadata_t` is stored by `sgx_sign` in the `.note.sgxmeta` section of the
+// signed enclave, and embeds the SIGSTRUCT (`enclave_css_t`).
+const METADATA_MAGIC: u64 = 0x86a8_0294_635d_0e4c;
+const METADATA_CSS_OFFSET: usize = 64;
+
+// Layout of `enclave_css_t`.
+const CSS_SIZE: usize = 1808;
+const HEADER: [u8; 12] = [6, 0, 0, 0, 0xe1, 0, 0, 0, 0, 0, 1, 0];
+const HEADER2: [u8; 16] = [1, 1, 0, 0, 0x60, 0, 0, 0, 0x60, 0, 0, 0, 1, 0, 0, 0];
+const HEADER2_OFFSET: usize = 24;
+const MODULUS_OFFSET: usize = 128;
+const MODULUS_SIZE: usize = 384;
+const ENCLAVE_HASH_OFFSET: usize = 960;
+const ISV_PROD_ID_OFFSET: usize = 1024;
+const ISV_SVN_OFFSET: usize = 1026;
+
+/// Enclave signature structure (SIGSTRUCT) of a signed enclave binary, e.g.,
+/// `pod_enclave.signed.so`.
+///
+/// It tells which enclave the binary is without loading it: its MRENCLAVE,
+/// and the MRSIGNER derived from the key it was signed with. The signature
+/// itself is checked by the CPU when the enclave is loaded, which also
+/// refuses to load a binary that doesn't match its MRENCLAVE.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::{PodError, SigStruct};
+/// # fn main() -> pod_api::Result<()> {
+/// // Signed enclave binaries are many times larger, but only the metadata
+/// // embedding the SIGSTRUCT matters.
+/// let mut enclave = vec![0u8; 4096];
+/// enclave[1000..1008].copy_from_slice(&0x86a8_0294_635d_0e4cu64.to_le_bytes());
+/// let css = &mut enclave[1064..];
+/// css[..12].copy_from_slice(&[6, 0, 0, 0, 0xe1, 0, 0, 0, 0, 0, 1, 0]);
+/// css[24..40].copy_from_slice(&[1, 1, 0, 0, 0x60, 0, 0, 0, 0x60, 0, 0, 0, 1, 0, 0, 0]);
+/// css[960..992].copy_from_slice(&[0xaa; 32]);
+/// css[1026..1028].copy_from_slice(&2u16.to_le_bytes());
+///
+/// let sigstruct = SigStruct::from_enclave_bytes(&enclave)?;
+/// assert_eq!(sigstruct.mr_enclave().as_bytes(), &[0xaa; 32]);
+/// assert_eq!(sigstruct.isv_svn(), 2);
+///
+/// match SigStruct::from_enclave_bytes(&enclave[..2000]) {
+///     Err(PodError::InvalidSigStruct(_)) => {}
+///     _ => panic!("truncated enclave should be rejected"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SigStruct {
+    mr_enclave: Measurement,
+    mr_signer: Measurement,
+    isv_prod_id: u16,
+    isv_svn: u16,
+}
+
+impl SigStruct {
+    /// Reads the SIGSTRUCT from the signed enclave binary at `enclave_path`.
+    pub fn from_enclave_file<P: AsRef<Path>>(enclave_path: P) -> Result<Self> {
+        let enclave_path = enclave_path.as_ref();
+        let enclave = fs::read(enclave_path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => PodError::EnclaveNotFound(enclave_path.to_owned()),
+            _ => PodError::Io(err),
+        })?;
+        Self::from_enclave_bytes(&enclave)
+    }
+
+    /// Finds the SIGSTRUCT in the contents of a signed enclave binary.
+    pub fn from_enclave_bytes(enclave: &[u8]) -> Result<Self> {
+        let magic = METADATA_MAGIC.to_le_bytes();
+        let mut found_metadata = false;
+        for (offset, _) in enclave
+            .windows(magic.len())
+            .enumerate()
+            .filter(|(_, window)| *window == magic)
+        {
+            found_metadata = true;
+            if let Some(css) = enclave
+                .get(offset + METADATA_CSS_OFFSET..)
+                .and_then(|css| css.get(..CSS_SIZE))
+            {
+                if css.starts_with(&HEADER) && css[HEADER2_OFFSET..].starts_with(&HEADER2) {
+                    return Ok(Self::from_css(css));
+                }
+            }
+        }
+        Err(PodError::InvalidSigStruct(if found_metadata {
+            "enclave metadata doesn't contain a valid SIGSTRUCT"
+        } else {
+            "enclave metadata not found, is the enclave signed?"
+        }))
+    }
+
+    fn from_css(css: &[u8]) -> Self {
+        let mut mr_enclave = [0u8; Measurement::LENGTH];
+        mr_enclave.copy_from_slice(&css[ENCLAVE_HASH_OFFSET..][..Measurement::LENGTH]);
+        // MRSIGNER is the SHA-256 of the signing key's modulus, as stored in
+        // SIGSTRUCT (little-endian).
+        let modulus = &css[MODULUS_OFFSET..][..MODULUS_SIZE];
+        let mr_signer: [u8; Measurement::LENGTH] = Sha256::digest(modulus).into();
+        let read_u16 = |offset: usize| {
+            u16::from_le_bytes(
+                css[offset..offset + 2]
+                    .try_into()
+                    .expect("SIGSTRUCT field should be 2 bytes long"),
+            )
+        };
+        Self {
+            mr_enclave: mr_enclave.into(),
+            mr_signer: mr_signer.into(),
+            isv_prod_id: read_u16(ISV_PROD_ID_OFFSET),
+            isv_svn: read_u16(ISV_SVN_OFFSET),
+        }
+    }
+
+    /// Returns the MRENCLAVE the enclave will have once loaded.
+    pub fn mr_enclave(&self) -> &Measurement {
+        &self.mr_enclave
+    }
+
+    /// Returns the MRSIGNER the enclave will have once loaded, i.e., the hash
+    /// of the key it was signed with.
+    pub fn mr_signer(&self) -> &Measurement {
+        &self.mr_signer
+    }
+
+    /// Returns the product ID of the enclave (ISVPRODID).
+    pub fn isv_prod_id(&self) -> u16 {
+        self.isv_prod_id
+    }
+
+    /// Returns the security version number of the enclave (ISVSVN).
+    pub fn isv_svn(&self) -> u16 {
+        self.isv_svn
+    }
+
+    /// Returns the identity the enclave will have once loaded.
+    pub fn identity(&self) -> EnclaveIdentity {
+        EnclaveIdentity::new(*self.mr_enclave.as_bytes(), *self.mr_signer.as_bytes())
+    }
+}
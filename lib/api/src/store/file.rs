@@ -0,0 +1,125 @@
+use super::{SealedStore, StoreLock};
+use crate::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+const FILE_MODE: u32 = 0o600;
+
+/// Store keeping the sealed keys in a file.
+///
+/// The file is written atomically and readable by its owner only. It's locked
+/// with an advisory lock on `.<file name>.lock` next to it, since the file
+/// itself gets replaced on write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Creates new instance storing the sealed keys at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    /// Returns the path of the sealed keys file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SealedStore for FileStore {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(sealed_keys) => Ok(Some(sealed_keys)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+        write_atomic(&self.path, sealed_keys)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        StoreLock::file(sibling(&self.path, "lock"))
+    }
+}
+
+/// Writes `contents` to a temporary file readable by the owner only, and
+/// renames it over `path`, so that `path` never holds partial contents.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = sibling(path, "tmp");
+    // A stale temporary file, e.g., left behind by a crash, keeps its
+    // permissions when opened, so it's never reused.
+    match fs::remove_file(&tmp_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let written = write_new(&tmp_path, contents).and_then(|_| fs::rename(&tmp_path, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    written?;
+    // Persist the rename itself
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Writes `contents` to `path`, which must not exist yet, and creates it
+/// readable by the owner only.
+fn write_new(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(FILE_MODE)
+        .open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Returns `.<file name>.<extension>` next to `path`.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", file_name, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn stale_tmp_file_is_replaced() {
+        let dir = TempDir::new();
+        let store = FileStore::new(dir.path("sealed_keys"));
+        let tmp_path = sibling(store.path(), "tmp");
+        fs::write(&tmp_path, b"stale").unwrap();
+        fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        store.store(b"sealed keys").unwrap();
+        assert_eq!(store.load().unwrap().unwrap(), b"sealed keys");
+        let mode = fs::metadata(store.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, FILE_MODE);
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn tmp_file_is_removed_on_failure() {
+        let dir = TempDir::new();
+        // Renaming a file over a directory fails.
+        let store = FileStore::new(dir.path("sealed_keys"));
+        fs::create_dir(store.path()).unwrap();
+
+        assert!(store.store(b"sealed keys").is_err());
+        assert!(!sibling(store.path(), "tmp").exists());
+    }
+}
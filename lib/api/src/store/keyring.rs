@@ -0,0 +1,169 @@
+use super::{SealedStore, StoreLock};
+use crate::{PodError, Result};
+use std::ffi::CString;
+use std::io;
+
+// See `keyctl(2)` and `add_key(2)`.
+const KEY_TYPE: &[u8] = b"user\0";
+const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+const KEYCTL_SETPERM: libc::c_long = 5;
+const KEYCTL_UNLINK: libc::c_long = 9;
+const KEYCTL_SEARCH: libc::c_long = 10;
+const KEYCTL_READ: libc::c_long = 11;
+// All permissions for the possessor, and all but setting attributes for the
+// owning user, so that the key can be read back without possessing it.
+const KEY_PERMISSIONS: libc::c_long = 0x3f1f_0000;
+// Maximum payload of a "user" key.
+const MAX_PAYLOAD_SIZE: usize = 32767;
+
+/// Store keeping the sealed keys in the Linux kernel keyring of the user, as
+/// a `user` key, e.g., listed by `keyctl show @u`.
+///
+/// The user keyring is shared by all processes of the user, and follows the
+/// user rather than their home directory. Note that it lives in kernel memory
+/// only: its contents are lost on reboot, and with them the private key.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::{KeyringStore, SealedStore};
+/// # fn main() -> pod_api::Result<()> {
+/// let store = KeyringStore::new(format!("pod-api-doctest-{}", std::process::id()));
+/// # // The keyring may be unavailable, e.g., when seccomp filters `keyctl`.
+/// # if store.load().is_err() {
+/// #     return Ok(());
+/// # }
+/// assert_eq!(store.load()?, None);
+/// store.store(b"sealed keys")?;
+/// assert_eq!(store.load()?.as_deref(), Some(&b"sealed keys"[..]));
+/// # store.remove()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyringStore {
+    description: String,
+}
+
+impl KeyringStore {
+    /// Creates new instance storing the sealed keys in the key with
+    /// `description`, e.g., `pod-app`.
+    pub fn new<S: Into<String>>(description: S) -> Self {
+        Self {
+            description: description.into(),
+        }
+    }
+
+    /// Returns the description of the key holding the sealed keys.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Removes the key holding the sealed keys from the keyring, if any.
+    pub fn remove(&self) -> Result<()> {
+        if let Some(key) = self.search()? {
+            keyctl(KEYCTL_UNLINK, key, KEY_SPEC_USER_KEYRING, 0)?;
+        }
+        Ok(())
+    }
+
+    fn c_description(&self) -> Result<CString> {
+        Ok(CString::new(self.description.as_str())?)
+    }
+
+    /// Returns the serial number of the key, if it exists.
+    fn search(&self) -> Result<Option<libc::c_long>> {
+        let description = self.c_description()?;
+        match keyctl(
+            KEYCTL_SEARCH,
+            KEY_SPEC_USER_KEYRING,
+            KEY_TYPE.as_ptr() as libc::c_long,
+            description.as_ptr() as libc::c_long,
+        ) {
+            Ok(key) => Ok(Some(key)),
+            Err(err) if is_missing(&err) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl SealedStore for KeyringStore {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        let key = match self.search()? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        // The key may be updated between getting its size and reading it.
+        let mut payload = Vec::new();
+        loop {
+            let size = match keyctl(
+                KEYCTL_READ,
+                key,
+                payload.as_mut_ptr() as libc::c_long,
+                payload.len() as libc::c_long,
+            ) {
+                Ok(size) => size as usize,
+                Err(err) if is_missing(&err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            if size <= payload.len() {
+                payload.truncate(size);
+                return Ok(Some(payload));
+            }
+            payload.resize(size, 0);
+        }
+    }
+
+    fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+        if sealed_keys.len() > MAX_PAYLOAD_SIZE {
+            return Err(PodError::SealedStore(format!(
+                "{} bytes don't fit in a keyring key",
+                sealed_keys.len()
+            )));
+        }
+        let description = self.c_description()?;
+        // Adding a key with the description of an existing one updates the
+        // latter atomically.
+        let key = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                KEY_TYPE.as_ptr(),
+                description.as_ptr(),
+                sealed_keys.as_ptr(),
+                sealed_keys.len(),
+                KEY_SPEC_USER_KEYRING,
+            )
+        };
+        if key < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        keyctl(KEYCTL_SETPERM, key, KEY_PERMISSIONS, 0)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        StoreLock::named("keyring", &self.description)
+    }
+}
+
+fn keyctl(
+    operation: libc::c_long,
+    arg2: libc::c_long,
+    arg3: libc::c_long,
+    arg4: libc::c_long,
+) -> io::Result<libc::c_long> {
+    // Operations taking fewer arguments ignore the rest, except for
+    // `KEYCTL_SEARCH` which takes a keyring to link the key to as fifth.
+    let result = unsafe { libc::syscall(libc::SYS_keyctl, operation, arg2, arg3, arg4, 0) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result)
+}
+
+fn is_missing(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOKEY) | Some(libc::EKEYREVOKED) | Some(libc::EKEYEXPIRED)
+    )
+}
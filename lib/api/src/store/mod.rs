@@ -0,0 +1,121 @@
+pub(crate) mod file;
+pub(crate) mod keyring;
+pub(crate) mod secret_service;
+
+use super::Result;
+use sha2::{Digest, Sha256};
+use std::any::Any;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE_MODE: u32 = 0o600;
+
+/// Abstraction over where `PodEnclave` keeps the sealed private key.
+///
+/// [`FileStore`] keeps it in a file, which is what [`PodEnclave::with_backend`]
+/// uses. [`KeyringStore`] keeps it in the Linux kernel keyring of the user,
+/// and [`SecretServiceStore`] in the freedesktop Secret Service (e.g., GNOME
+/// Keyring or KWallet), see [`PodEnclave::with_store`].
+///
+/// The sealed keys are opaque to the store: they are already protected by the
+/// backend, and wrapped in a checksummed envelope by `PodEnclave`.
+///
+/// [`FileStore`]: struct.FileStore.html
+/// [`KeyringStore`]: struct.KeyringStore.html
+/// [`SecretServiceStore`]: struct.SecretServiceStore.html
+/// [`PodEnclave::with_backend`]: struct.PodEnclave.html#method.with_backend
+/// [`PodEnclave::with_store`]: struct.PodEnclave.html#method.with_store
+pub trait SealedStore: Send + Sync {
+    /// Returns the stored sealed keys, or `None` if none were stored yet.
+    fn load(&self) -> Result<Option<Vec<u8>>>;
+
+    /// Replaces the stored sealed keys with `sealed_keys`. Readers must see
+    /// either the old or the new sealed keys, never partial ones.
+    fn store(&self, sealed_keys: &[u8]) -> Result<()>;
+
+    /// Takes an exclusive lock on the store, held until the returned
+    /// [`StoreLock`] is dropped, so that concurrently starting processes don't
+    /// both generate a key.
+    ///
+    /// [`StoreLock`]: struct.StoreLock.html
+    fn lock(&self) -> Result<StoreLock>;
+}
+
+impl<S: SealedStore + ?Sized> SealedStore for Box<S> {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        (**self).load()
+    }
+
+    fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+        (**self).store(sealed_keys)
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        (**self).lock()
+    }
+}
+
+/// Lock on a [`SealedStore`], released on `Drop`.
+///
+/// [`SealedStore`]: trait.SealedStore.html
+pub struct StoreLock {
+    _guard: Box<dyn Any + Send>,
+}
+
+impl StoreLock {
+    /// Wraps `guard`, which releases the lock when dropped.
+    pub fn new<G: Send + 'static>(guard: G) -> Self {
+        Self {
+            _guard: Box::new(guard),
+        }
+    }
+
+    /// Takes an advisory lock on `path`, creating the file if need be.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(FileLock::acquire(path.as_ref())?))
+    }
+
+    /// Takes an advisory lock on a per-user lock file named after `kind` and
+    /// `name`, for stores which can't be locked themselves.
+    pub(crate) fn named(kind: &str, name: &str) -> Result<Self> {
+        let name_hash = hex::encode(&Sha256::digest(name.as_bytes())[..8]);
+        // `XDG_RUNTIME_DIR` is private to the user; elsewhere, the user ID
+        // keeps users from sharing the lock.
+        let path = match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => PathBuf::from(dir).join(format!("pod-api-{}-{}.lock", kind, name_hash)),
+            None => std::env::temp_dir().join(format!(
+                "pod-api-{}-{}-{}.lock",
+                kind,
+                unsafe { libc::getuid() },
+                name_hash
+            )),
+        };
+        Self::file(path)
+    }
+}
+
+/// Exclusive `flock` on a lock file; released on `Drop`.
+struct FileLock(File);
+
+impl FileLock {
+    fn acquire(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .mode(LOCK_FILE_MODE)
+            .open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(file))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
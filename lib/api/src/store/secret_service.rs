@@ -0,0 +1,111 @@
+use super::{SealedStore, StoreLock};
+use crate::{PodError, Result};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+const SECRET_TOOL: &str = "secret-tool";
+// Attributes identifying the item, alongside the store's name.
+const APPLICATION_ATTRIBUTE: &str = "application";
+const APPLICATION: &str = "pod-api";
+const NAME_ATTRIBUTE: &str = "sealed-keys";
+
+/// Store keeping the sealed keys in the freedesktop Secret Service, e.g.,
+/// GNOME Keyring or KWallet, driven with the `secret-tool` command line tool
+/// of `libsecret`.
+///
+/// The sealed keys are stored base64 encoded, in an item with attributes
+/// `application` = `pod-api` and `sealed-keys` = the store's name, e.g.:
+///
+/// ```text
+/// secret-tool lookup application pod-api sealed-keys pod-app
+/// ```
+///
+/// The Secret Service has to be running and unlocked, which usually means
+/// the user is logged into a desktop session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretServiceStore {
+    name: String,
+}
+
+impl SecretServiceStore {
+    /// Creates new instance storing the sealed keys in the item named
+    /// `name`, e.g., `pod-app`.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// Returns the name of the item holding the sealed keys.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs `secret-tool` with `args` followed by the item's attributes,
+    /// feeding it `input` on stdin. Returns its stdout, or `None` if it
+    /// failed without complaining, which is how `lookup` tells that there's
+    /// no such item.
+    fn secret_tool(&self, args: &[&str], input: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        let mut child = Command::new(SECRET_TOOL)
+            .args(args)
+            .args([
+                APPLICATION_ATTRIBUTE,
+                APPLICATION,
+                NAME_ATTRIBUTE,
+                &self.name,
+            ])
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => PodError::SealedStore(format!(
+                    "{} not found, is libsecret installed?",
+                    SECRET_TOOL
+                )),
+                _ => PodError::Io(err),
+            })?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input)?;
+        }
+        let output = child.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match (output.status.success(), stderr.trim()) {
+            (true, _) => Ok(Some(output.stdout)),
+            (false, "") => Ok(None),
+            (false, stderr) => Err(PodError::SealedStore(format!(
+                "{} failed: {}",
+                SECRET_TOOL, stderr
+            ))),
+        }
+    }
+}
+
+impl SealedStore for SecretServiceStore {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        match self.secret_tool(&["lookup"], None)? {
+            Some(sealed_keys) => {
+                let sealed_keys = String::from_utf8_lossy(&sealed_keys);
+                Ok(Some(base64::decode(sealed_keys.trim())?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+        // Storing an item with the same attributes replaces it.
+        let label = format!("--label=pod-api sealed keys ({})", self.name);
+        self.secret_tool(
+            &["store", &label],
+            Some(base64::encode(sealed_keys).as_bytes()),
+        )?
+        .ok_or_else(|| PodError::SealedStore(format!("{} store failed", SECRET_TOOL)))?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        StoreLock::named("secret-service", &self.name)
+    }
+}
@@ -0,0 +1,59 @@
+//! Fixtures shared by the unit tests.
+use crate::{PodEnclave, Result, SigningPayload, SigningPurpose, SoftwareBackend};
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Temporary directory, removed on drop, i.e., also when the test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pod-api-test-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).expect("temp dir should be created");
+        Self(path)
+    }
+
+    pub(crate) fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Loads a `SoftwareBackend` enclave deriving its sealing key from
+    /// `secret`, with its sealed keys in the file `name` of this directory.
+    pub(crate) fn load(&self, name: &str, secret: &str) -> Result<PodEnclave> {
+        PodEnclave::with_backend(SoftwareBackend::new(secret), self.path(name))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Returns a freshly initialised `SoftwareBackend` enclave, and the directory
+/// holding its sealed keys (`sealed_keys`), which must outlive it.
+pub(crate) fn software_enclave() -> (TempDir, PodEnclave) {
+    let dir = TempDir::new();
+    let pod_enclave = dir
+        .load("sealed_keys", "secret")
+        .expect("software enclave should load");
+    (dir, pod_enclave)
+}
+
+/// Returns an authentication payload for `origin` with a fixed challenge and
+/// timestamp.
+pub(crate) fn payload(origin: &str) -> SigningPayload {
+    SigningPayload::new(
+        origin.parse().expect("valid origin"),
+        SigningPurpose::Authentication,
+        &b"challenge"[..],
+        1_600_000_000,
+    )
+}
@@ -0,0 +1,763 @@
+use super::{PodError, Result};
+use ed25519_dalek::Verifier;
+#[cfg(feature = "with_serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+/// Service Provider ID as assigned by the IAS.
+///
+/// `Spid` parses from and displays as a 32 character hex string. With
+/// `with_serde` feature enabled, it is also serialized as one.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::Spid;
+/// # fn main() -> pod_api::Result<()> {
+/// let spid: Spid = "0123456789ABCDEF0123456789abcdef".parse()?;
+/// assert_eq!(spid.to_string(), "0123456789abcdef0123456789abcdef");
+///
+/// assert!("0123456789abcdef".parse::<Spid>().is_err());
+/// assert!("0123456789abcdef0123456789abcdeg".parse::<Spid>().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spid([u8; Spid::LENGTH]);
+
+impl Spid {
+    /// Length of the SPID in bytes.
+    pub const LENGTH: usize = 16;
+
+    /// Returns raw bytes of this SPID.
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+}
+
+impl FromStr for Spid {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut inner = [0u8; Self::LENGTH];
+        hex::decode_to_slice(s, &mut inner).map_err(|_| PodError::InvalidSpid(s.to_owned()))?;
+        Ok(Self(inner))
+    }
+}
+
+impl fmt::Display for Spid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+/// Identifier of a relying party, e.g., a web origin or an SPID, which gets
+/// its own key pair derived from the enclave's master key.
+///
+/// Keys derived for different relying parties are unlinkable, so services
+/// can't tell whether they see the same device. Identifiers are compared
+/// byte for byte, so callers should normalize them first (e.g., lowercase the
+/// origin and drop any trailing slash). With `with_serde` feature enabled,
+/// `RelyingParty` is serialized as `String`.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::{RelyingParty, Spid};
+/// # fn main() -> pod_api::Result<()> {
+/// let relying_party: RelyingParty = "https://example.com".parse()?;
+/// assert_eq!(relying_party.to_string(), "https://example.com");
+///
+/// let spid: Spid = "0123456789abcdef0123456789abcdef".parse()?;
+/// assert_eq!(RelyingParty::from(spid).as_bytes(), b"0123456789abcdef0123456789abcdef");
+///
+/// assert!("".parse::<RelyingParty>().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RelyingParty(String);
+
+impl RelyingParty {
+    /// Maximum length of the identifier in bytes.
+    pub const MAX_LENGTH: usize = 256;
+
+    /// Returns raw bytes of this identifier, i.e., the ones the key is
+    /// derived from.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl FromStr for RelyingParty {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() || s.len() > Self::MAX_LENGTH {
+            return Err(PodError::InvalidRelyingParty(s.to_owned()));
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl From<Spid> for RelyingParty {
+    fn from(spid: Spid) -> Self {
+        Self(spid.to_string())
+    }
+}
+
+impl fmt::Display for RelyingParty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Length of a quote's `report_data` in bytes.
+const REPORT_DATA_LENGTH: usize = 64;
+
+/// ED25519 public key of the enclave.
+///
+/// With `with_serde` feature enabled, `Ed25519PublicKey` can be serialized and
+/// deserialized as base64 `String`.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::Ed25519PublicKey;
+/// # fn main() -> pod_api::Result<()> {
+/// let bytes = [
+///     215, 90, 152, 1, 130, 177, 10, 183, 213, 75, 254, 211, 201, 100, 7, 58,
+///     14, 225, 114, 243, 218, 166, 35, 37, 175, 2, 26, 104, 247, 7, 81, 26,
+/// ];
+/// let pub_key = Ed25519PublicKey::from_bytes(&bytes)?;
+/// assert_eq!(pub_key.as_bytes(), &bytes);
+/// assert_eq!(pub_key.fingerprint().short(), "21:fe:31:df:a1:54:a2:61");
+///
+/// assert!(Ed25519PublicKey::from_bytes(&bytes[..31]).is_err());
+///
+/// let encoded = pub_key.to_base64();
+/// assert_eq!(Ed25519PublicKey::from_base64(&encoded)?, pub_key);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ed25519PublicKey([u8; Ed25519PublicKey::LENGTH]);
+
+impl Ed25519PublicKey {
+    /// Length of the public key in bytes.
+    pub const LENGTH: usize = 32;
+
+    /// Creates new instance from raw bytes, checking that they actually
+    /// encode a valid ED25519 public key.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LENGTH {
+            return Err(PodError::InvalidPublicKey);
+        }
+        ed25519_dalek::PublicKey::from_bytes(bytes).map_err(|_| PodError::InvalidPublicKey)?;
+        let mut inner = [0u8; Self::LENGTH];
+        inner.copy_from_slice(bytes);
+        Ok(Self(inner))
+    }
+
+    /// Returns raw bytes of this public key.
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+
+    /// Decodes the public key from hex string.
+    pub fn from_hex<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+
+    /// Decodes the public key from base64 string.
+    pub fn from_base64<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&base64::decode(s)?)
+    }
+
+    /// Encodes this public key as hex string.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Encodes this public key as base64 string.
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.0)
+    }
+
+    /// Verifies that `signature` is a valid signature of `message` made with
+    /// the private key matching this public key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pod_api::{EnclaveBackend, SoftwareBackend};
+    /// # fn main() -> pod_api::Result<()> {
+    /// let mut backend = SoftwareBackend::new("some secret");
+    /// backend.init()?;
+    /// let signature = backend.sign(None, b"challenge")?;
+    ///
+    /// let pub_key = backend.public_key(None)?;
+    /// assert!(pub_key.verify(b"challenge", &signature).is_ok());
+    /// assert!(pub_key.verify(b"other challenge", &signature).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify<B: AsRef<[u8]>>(&self, message: B, signature: &Ed25519Signature) -> Result<()> {
+        let pub_key = ed25519_dalek::PublicKey::from_bytes(&self.0)
+            .map_err(|_| PodError::InvalidPublicKey)?;
+        let signature = ed25519_dalek::Signature::from(signature.0);
+        pub_key
+            .verify(message.as_ref(), &signature)
+            .map_err(|_| PodError::InvalidSignature)
+    }
+
+    /// Verifies that `signature` is a valid Ed25519ph signature of everything
+    /// read from `reader`, as made by [`PodEnclave::sign_reader`]. The
+    /// message is hashed as it is read, so it doesn't have to fit in memory.
+    ///
+    /// [`PodEnclave::sign_reader`]: struct.PodEnclave.html#method.sign_reader
+    pub fn verify_reader<R: Read>(&self, reader: R, signature: &Ed25519Signature) -> Result<()> {
+        let pub_key = ed25519_dalek::PublicKey::from_bytes(&self.0)
+            .map_err(|_| PodError::InvalidPublicKey)?;
+        let signature = ed25519_dalek::Signature::from(signature.0);
+        pub_key
+            .verify_prehashed(prehash(reader)?, None, &signature)
+            .map_err(|_| PodError::InvalidSignature)
+    }
+
+    /// Returns the `report_data` of a quote binding this public key to
+    /// `user_data`, i.e., `public key || SHA-256(public key || user data)`.
+    pub fn to_report_data<B: AsRef<[u8]>>(&self, user_data: B) -> [u8; REPORT_DATA_LENGTH] {
+        let mut report_data = [0u8; REPORT_DATA_LENGTH];
+        report_data[..Self::LENGTH].copy_from_slice(&self.0);
+        let binding = Sha256::new()
+            .chain(self.0)
+            .chain(user_data.as_ref())
+            .finalize();
+        report_data[Self::LENGTH..].copy_from_slice(&binding);
+        report_data
+    }
+
+    /// Extracts the public key from a quote's `report_data`, checking that
+    /// the quote was generated for `user_data` (e.g., a nonce issued by the
+    /// verifier), so that quotes can't be replayed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pod_api::{Ed25519PublicKey, EnclaveBackend, QuoteType, SoftwareBackend};
+    /// # fn main() -> pod_api::Result<()> {
+    /// let mut backend = SoftwareBackend::new("some secret");
+    /// backend.init()?;
+    /// let spid = "0123456789abcdef0123456789abcdef".parse()?;
+    /// let quote = backend.get_quote(None, b"nonce", &spid, QuoteType::Unlinkable, None)?;
+    ///
+    /// let report_data = quote.report_data()?;
+    /// let pub_key = Ed25519PublicKey::from_report_data(report_data, b"nonce")?;
+    /// assert_eq!(pub_key, backend.public_key(None)?);
+    /// assert!(Ed25519PublicKey::from_report_data(report_data, b"other nonce").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_report_data<B: AsRef<[u8]>>(report_data: &[u8], user_data: B) -> Result<Self> {
+        if report_data.len() != REPORT_DATA_LENGTH {
+            return Err(PodError::InvalidQuote("invalid report data length"));
+        }
+        let pub_key = Self::from_bytes(&report_data[..Self::LENGTH])?;
+        if pub_key.to_report_data(user_data)[..] != report_data[..] {
+            return Err(PodError::InvalidQuote("report data doesn't bind user data"));
+        }
+        Ok(pub_key)
+    }
+
+    /// Returns SHA-256 fingerprint of this public key.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut fingerprint = [0u8; Fingerprint::LENGTH];
+        fingerprint.copy_from_slice(&Sha256::digest(&self.0));
+        Fingerprint(fingerprint)
+    }
+}
+
+/// ED25519 signature produced by the enclave.
+///
+/// With `with_serde` feature enabled, `Ed25519Signature` can be serialized and
+/// deserialized as base64 `String`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ed25519Signature([u8; Ed25519Signature::LENGTH]);
+
+impl Ed25519Signature {
+    /// Length of the signature in bytes.
+    pub const LENGTH: usize = 64;
+
+    /// Creates new instance from raw bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LENGTH {
+            return Err(PodError::InvalidSignature);
+        }
+        let mut inner = [0u8; Self::LENGTH];
+        inner.copy_from_slice(bytes);
+        Ok(Self(inner))
+    }
+
+    /// Decodes the signature from hex string.
+    pub fn from_hex<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+
+    /// Decodes the signature from base64 string.
+    pub fn from_base64<S: AsRef<[u8]>>(s: S) -> Result<Self> {
+        Self::from_bytes(&base64::decode(s)?)
+    }
+
+    /// Returns raw bytes of this signature.
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+
+    /// Encodes this signature as hex string.
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0[..])
+    }
+
+    /// Encodes this signature as base64 string.
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.0[..])
+    }
+}
+
+/// SHA-256 fingerprint of an [`Ed25519PublicKey`], meant for displaying and
+/// comparing keys by humans.
+///
+/// `Fingerprint` displays as colon-separated hex bytes.
+///
+/// [`Ed25519PublicKey`]: struct.Ed25519PublicKey.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; Fingerprint::LENGTH]);
+
+impl Fingerprint {
+    /// Length of the fingerprint in bytes.
+    pub const LENGTH: usize = 32;
+    const SHORT_LENGTH: usize = 8;
+
+    /// Returns raw bytes of this fingerprint.
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+
+    /// Returns only the first 8 bytes of the fingerprint as colon-separated
+    /// hex string. Handy for quick visual comparison in UIs.
+    pub fn short(&self) -> String {
+        to_colon_hex(&self.0[..Self::SHORT_LENGTH])
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&to_colon_hex(&self.0))
+    }
+}
+
+fn to_colon_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for Spid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for Spid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for RelyingParty {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for RelyingParty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for Ed25519PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for Ed25519PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_base64(&string).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for Ed25519Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for Ed25519Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_base64(&string).map_err(de::Error::custom)
+    }
+}
+
+/// SGX measurement, i.e., an MRENCLAVE or an MRSIGNER value.
+///
+/// `Measurement` parses from and displays as a 64 character hex string. With
+/// `with_serde` feature enabled, it is also serialized as one.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::Measurement;
+/// # fn main() -> pod_api::Result<()> {
+/// let measurement: Measurement = "AB".repeat(32).parse()?;
+/// assert_eq!(measurement.as_bytes(), &[0xab; 32]);
+/// assert_eq!(measurement.to_string(), "ab".repeat(32));
+///
+/// assert!("abcd".parse::<Measurement>().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Measurement([u8; Measurement::LENGTH]);
+
+impl Measurement {
+    /// Length of the measurement in bytes.
+    pub const LENGTH: usize = 32;
+
+    /// Returns raw bytes of this measurement.
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+}
+
+impl From<[u8; Measurement::LENGTH]> for Measurement {
+    fn from(bytes: [u8; Measurement::LENGTH]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl FromStr for Measurement {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut inner = [0u8; Self::LENGTH];
+        hex::decode_to_slice(s, &mut inner)
+            .map_err(|_| PodError::InvalidMeasurement(s.to_owned()))?;
+        Ok(Self(inner))
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for Measurement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for Measurement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Identity of an enclave, as found in the body of its SGX report.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::EnclaveIdentity;
+/// # fn main() -> pod_api::Result<()> {
+/// let mut report_body = [0u8; 384];
+/// report_body[64..96].copy_from_slice(&[0xaa; 32]);
+/// report_body[128..160].copy_from_slice(&[0xbb; 32]);
+///
+/// let identity = EnclaveIdentity::from_report_body(&report_body)?;
+/// assert_eq!(identity.mr_enclave(), &[0xaa; 32]);
+/// assert_eq!(identity.mr_signer(), &[0xbb; 32]);
+///
+/// assert!(EnclaveIdentity::from_report_body(&report_body[..100]).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnclaveIdentity {
+    mr_enclave: [u8; 32],
+    mr_signer: [u8; 32],
+}
+
+impl EnclaveIdentity {
+    /// Size of `sgx_report_body_t` in bytes.
+    pub const REPORT_BODY_LENGTH: usize = 384;
+    const MR_ENCLAVE_OFFSET: usize = 64;
+    const MR_SIGNER_OFFSET: usize = 128;
+
+    pub(crate) fn new(mr_enclave: [u8; 32], mr_signer: [u8; 32]) -> Self {
+        Self {
+            mr_enclave,
+            mr_signer,
+        }
+    }
+
+    /// Extracts the identity from a raw `sgx_report_body_t`, e.g., the
+    /// report body embedded in a quote.
+    pub fn from_report_body(report_body: &[u8]) -> Result<Self> {
+        if report_body.len() != Self::REPORT_BODY_LENGTH {
+            return Err(PodError::InvalidQuote("report body has invalid length"));
+        }
+        let mut mr_enclave = [0u8; 32];
+        mr_enclave.copy_from_slice(&report_body[Self::MR_ENCLAVE_OFFSET..][..32]);
+        let mut mr_signer = [0u8; 32];
+        mr_signer.copy_from_slice(&report_body[Self::MR_SIGNER_OFFSET..][..32]);
+        Ok(Self::new(mr_enclave, mr_signer))
+    }
+
+    /// Returns the measurement of the enclave's code and data (MRENCLAVE).
+    pub fn mr_enclave(&self) -> &[u8; 32] {
+        &self.mr_enclave
+    }
+
+    /// Returns the hash of the key the enclave was signed with (MRSIGNER).
+    pub fn mr_signer(&self) -> &[u8; 32] {
+        &self.mr_signer
+    }
+}
+
+/// Fields of an enclave's own SGX report body, describing which enclave build
+/// is loaded and how.
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::EnclaveReport;
+/// # fn main() -> pod_api::Result<()> {
+/// let mut report_body = [0u8; 384];
+/// report_body[48] = 0x07; // INITTED | DEBUG | MODE64BIT
+/// report_body[64..96].copy_from_slice(&[0xaa; 32]);
+/// report_body[256..258].copy_from_slice(&3u16.to_le_bytes());
+/// report_body[258..260].copy_from_slice(&5u16.to_le_bytes());
+///
+/// let report = EnclaveReport::from_report_body(&report_body)?;
+/// assert_eq!(report.identity().mr_enclave(), &[0xaa; 32]);
+/// assert_eq!(report.isv_prod_id(), 3);
+/// assert_eq!(report.isv_svn(), 5);
+/// assert!(report.is_debug());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnclaveReport {
+    identity: EnclaveIdentity,
+    isv_prod_id: u16,
+    isv_svn: u16,
+    flags: u64,
+    xfrm: u64,
+}
+
+impl EnclaveReport {
+    /// `SGX_FLAGS_DEBUG` attribute flag.
+    pub const FLAG_DEBUG: u64 = 0x02;
+    const FLAGS_OFFSET: usize = 48;
+    const XFRM_OFFSET: usize = 56;
+    const ISV_PROD_ID_OFFSET: usize = 256;
+    const ISV_SVN_OFFSET: usize = 258;
+
+    pub(crate) fn new(
+        identity: EnclaveIdentity,
+        isv_prod_id: u16,
+        isv_svn: u16,
+        flags: u64,
+        xfrm: u64,
+    ) -> Self {
+        Self {
+            identity,
+            isv_prod_id,
+            isv_svn,
+            flags,
+            xfrm,
+        }
+    }
+
+    /// Extracts the report fields from a raw `sgx_report_body_t`.
+    pub fn from_report_body(report_body: &[u8]) -> Result<Self> {
+        let identity = EnclaveIdentity::from_report_body(report_body)?;
+        let u16_at =
+            |offset: usize| u16::from_le_bytes([report_body[offset], report_body[offset + 1]]);
+        let u64_at = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&report_body[offset..][..8]);
+            u64::from_le_bytes(bytes)
+        };
+        Ok(Self::new(
+            identity,
+            u16_at(Self::ISV_PROD_ID_OFFSET),
+            u16_at(Self::ISV_SVN_OFFSET),
+            u64_at(Self::FLAGS_OFFSET),
+            u64_at(Self::XFRM_OFFSET),
+        ))
+    }
+
+    /// Returns the enclave's MRENCLAVE and MRSIGNER.
+    pub fn identity(&self) -> &EnclaveIdentity {
+        &self.identity
+    }
+
+    /// Returns the product ID assigned by the enclave's signer (ISVPRODID).
+    pub fn isv_prod_id(&self) -> u16 {
+        self.isv_prod_id
+    }
+
+    /// Returns the security version number of the enclave (ISVSVN).
+    pub fn isv_svn(&self) -> u16 {
+        self.isv_svn
+    }
+
+    /// Returns the enclave's attribute flags (`SGX_FLAGS_*`).
+    pub fn flags(&self) -> u64 {
+        self.flags
+    }
+
+    /// Returns the enclave's XSAVE feature request mask (XFRM).
+    pub fn xfrm(&self) -> u64 {
+        self.xfrm
+    }
+
+    /// Returns `true` if the enclave runs in debug mode, i.e., its memory can
+    /// be inspected by a debugger, and it offers NO protection.
+    pub fn is_debug(&self) -> bool {
+        self.flags & Self::FLAG_DEBUG != 0
+    }
+}
+
+/// Mode the SGX enclave is launched in.
+///
+/// Defaults to [`LaunchMode::Debug`], or to [`LaunchMode::Production`] with
+/// the `production` feature enabled. `LaunchMode` parses from and displays as
+/// `production` or `debug`. With `with_serde` feature enabled, it is also
+/// serialized as such.
+///
+/// [`LaunchMode::Debug`]: enum.LaunchMode.html#variant.Debug
+/// [`LaunchMode::Production`]: enum.LaunchMode.html#variant.Production
+///
+/// # Examples
+///
+/// ```
+/// # use pod_api::LaunchMode;
+/// # fn main() -> pod_api::Result<()> {
+/// assert_eq!("production".parse::<LaunchMode>()?, LaunchMode::Production);
+/// assert_eq!(LaunchMode::Debug.to_string(), "debug");
+/// assert!("release".parse::<LaunchMode>().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaunchMode {
+    /// Enclave memory is protected. The enclave has to be signed with a key
+    /// allowed to launch production enclaves, unless the platform supports
+    /// Flexible Launch Control.
+    Production,
+    /// Enclave can be debugged, which NULLIFIES ENCLAVE MEMORY PROTECTION.
+    /// Quotes of debug enclaves are rejected by `pod-server` by default.
+    Debug,
+}
+
+impl LaunchMode {
+    // POD_LAUNCH_* values
+    #[cfg(feature = "sgx")]
+    pub(crate) fn to_raw(self) -> u16 {
+        match self {
+            Self::Production => 0,
+            Self::Debug => 1,
+        }
+    }
+}
+
+impl Default for LaunchMode {
+    fn default() -> Self {
+        if cfg!(feature = "production") {
+            Self::Production
+        } else {
+            Self::Debug
+        }
+    }
+}
+
+impl FromStr for LaunchMode {
+    type Err = PodError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "production" => Ok(Self::Production),
+            "debug" => Ok(Self::Debug),
+            _ => Err(PodError::InvalidLaunchMode(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for LaunchMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Production => "production",
+            Self::Debug => "debug",
+        })
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl Serialize for LaunchMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'de> Deserialize<'de> for LaunchMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Hashes everything read from `reader` with SHA-512, i.e., computes the
+/// prehash signed with Ed25519ph.
+pub(crate) fn prehash<R: Read>(mut reader: R) -> Result<Sha512> {
+    let mut hasher = Sha512::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher)
+}
@@ -1,77 +1,342 @@
-use super::{c, QuoteType};
-use anyhow::{anyhow, Result};
+use super::{
+    c, LaunchMode, PodError, QuoteType, RelyingParty, Result, SealPolicy, SgxStatus, Spid,
+};
 use rust_sgx_util::Quote;
-use std::convert::TryFrom;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::sync::Once;
 
 const MAX_SEALED_KEYS_SIZE: usize = 1024; // Should be plenty for ed25519 private key
 const MAX_QUOTE_SIZE: usize = 2048; // Usually around 1200 bytes required
 const EC_SIGNATURE: usize = 64; // EC signature should always be 64bytes long!
+const EC_PUBLIC_KEY: usize = 32; // ED25519 public key is 32bytes long
+const REPORT_BODY_SIZE: usize = 384; // sizeof(sgx_report_body_t)
 
+/// Handle of an enclave loaded by `libpod_sgx`.
+pub(super) struct EnclaveHandle(NonNull<c::PodEnclave>);
+
+// The handle is only ever used by one thread at a time (`PodEnclave` serializes
+// access to its backend), and `libpod_sgx` keeps no thread-affine state in it.
+unsafe impl Send for EnclaveHandle {}
+
+impl EnclaveHandle {
+    fn from_raw(raw: *mut c::PodEnclave) -> Self {
+        Self(NonNull::new(raw).expect("libpod_sgx should return a valid enclave handle"))
+    }
+
+    fn as_ptr(&self) -> *mut c::PodEnclave {
+        self.0.as_ptr()
+    }
+}
+
+/// Turns on printing debug messages of `libpod_sgx` and the enclave to
+/// stdout. Has no effect once an enclave is loaded, since from then on all
+/// messages are forwarded to the `log` crate.
+#[deprecated(note = "messages are forwarded to the `log` crate, filter them there instead")]
 pub fn set_verbose(verbose: bool) {
     unsafe { c::set_verbose(verbose) }
 }
 
-pub(super) fn init_enclave<P: AsRef<Path>>(enclave_path: P) -> Result<Vec<u8>> {
-    let enclave_path = path_to_c_string(enclave_path)?;
+static LOG_CALLBACK: Once = Once::new();
+
+/// Forwards messages of `libpod_sgx` and the enclave to the `log` crate
+/// (with `pod_sgx` target), rather than printing them to stdout, which might
+/// be in use, e.g., for native messaging.
+fn install_log_callback() {
+    LOG_CALLBACK.call_once(|| unsafe { c::pod_set_log_callback(Some(log_callback)) });
+}
+
+extern "C" fn log_callback(level: libc::c_int, message: *const libc::c_char) {
+    if message.is_null() {
+        return;
+    }
+    let level = match level {
+        c::POD_LOG_ERROR => log::Level::Error,
+        c::POD_LOG_WARN => log::Level::Warn,
+        c::POD_LOG_INFO => log::Level::Info,
+        c::POD_LOG_DEBUG => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    log::log!(target: "pod_sgx", level, "{}", message.trim_end());
+}
+
+/// Converts negated `POD_ERR_*` code returned by the C layer into `PodError`.
+fn last_error(ret: libc::c_int) -> PodError {
+    let status = SgxStatus(unsafe { c::pod_last_sgx_status() });
+    match -ret {
+        c::POD_ERR_ENCLAVE_LOAD => PodError::EnclaveLoadFailed(status),
+        c::POD_ERR_NOT_LOADED => PodError::EnclaveNotLoaded,
+        c::POD_ERR_SEALED_STATE => PodError::SealedStateInvalid,
+        c::POD_ERR_AESM_UNAVAILABLE => PodError::AesmUnavailable(status),
+        c::POD_ERR_BUFFER_TOO_SMALL => PodError::QuoteBufferTooSmall,
+        c::POD_ERR_QUOTE => PodError::QuoteFailed(status),
+        c::POD_ERR_SIGN => PodError::SigningFailed(status),
+        // Callers which can provide more context handle the remaining codes
+        // (e.g., `POD_ERR_ENCLAVE_NOT_FOUND`) themselves.
+        _ => PodError::Sgx(status),
+    }
+}
+
+/// Splits optional `relying_party` into pointer and size understood by
+/// `libpod_sgx`, i.e., `NULL` and zero for the master key.
+fn relying_party_raw(relying_party: Option<&RelyingParty>) -> (*const u8, usize) {
+    relying_party.map_or((ptr::null(), 0), |relying_party| {
+        let bytes = relying_party.as_bytes();
+        (bytes.as_ptr(), bytes.len())
+    })
+}
+
+fn enclave_load_error(ret: libc::c_int, enclave_path: &Path) -> PodError {
+    match -ret {
+        c::POD_ERR_ENCLAVE_NOT_FOUND => PodError::EnclaveNotFound(enclave_path.to_owned()),
+        c::POD_ERR_LAUNCH_DENIED => PodError::LaunchDenied,
+        _ => last_error(ret),
+    }
+}
+
+pub(super) fn init_enclave<P: AsRef<Path>>(
+    enclave_path: P,
+    launch_mode: LaunchMode,
+    seal_policy: SealPolicy,
+) -> Result<(EnclaveHandle, Vec<u8>)> {
+    install_log_callback();
+    let enclave_path = enclave_path.as_ref();
+    let c_enclave_path = path_to_c_string(enclave_path)?;
     let sealed_keys_buffer = &mut [0u8; MAX_SEALED_KEYS_SIZE];
+    let mut enclave = ptr::null_mut();
     let ret = unsafe {
         c::pod_init_enclave(
-            enclave_path.as_ptr(),
+            c_enclave_path.as_ptr(),
+            launch_mode.to_raw(),
+            seal_policy.to_raw(),
             sealed_keys_buffer.as_mut_ptr(),
             sealed_keys_buffer.len(),
+            &mut enclave,
         )
     };
 
     if ret < 0 {
-        return Err(anyhow!(
-            "pod_init_enclave returned non-zero exit code: {}",
-            ret
-        ));
+        return Err(enclave_load_error(ret, enclave_path));
     }
 
-    let sealed_keys_size = usize::try_from(ret)?;
-    Ok(Vec::from(&sealed_keys_buffer[..sealed_keys_size]))
+    let sealed_keys_size = ret as usize;
+    Ok((
+        EnclaveHandle::from_raw(enclave),
+        Vec::from(&sealed_keys_buffer[..sealed_keys_size]),
+    ))
 }
 
 pub(super) fn load_enclave<P: AsRef<Path>, B: AsRef<[u8]>>(
     enclave_path: P,
+    launch_mode: LaunchMode,
     sealed_keys: B,
-) -> Result<()> {
-    let enclave_path = path_to_c_string(enclave_path)?;
+) -> Result<EnclaveHandle> {
+    install_log_callback();
+    let enclave_path = enclave_path.as_ref();
+    let c_enclave_path = path_to_c_string(enclave_path)?;
     let sealed_keys = sealed_keys.as_ref();
+    let mut enclave = ptr::null_mut();
     let ret = unsafe {
         c::pod_load_enclave(
-            enclave_path.as_ptr(),
+            c_enclave_path.as_ptr(),
+            launch_mode.to_raw(),
             sealed_keys.as_ptr(),
             sealed_keys.len(),
+            &mut enclave,
         )
     };
     if ret < 0 {
-        Err(anyhow!(
-            "pod_load_enclave returned non-zero exit code: {}",
-            ret
-        ))
+        Err(enclave_load_error(ret, enclave_path))
     } else {
-        Ok(())
+        Ok(EnclaveHandle::from_raw(enclave))
+    }
+}
+
+pub(super) fn reseal_keys(enclave: &EnclaveHandle, seal_policy: SealPolicy) -> Result<Vec<u8>> {
+    let sealed_keys_buffer = &mut [0u8; MAX_SEALED_KEYS_SIZE];
+    let ret = unsafe {
+        c::pod_reseal_keys(
+            enclave.as_ptr(),
+            seal_policy.to_raw(),
+            sealed_keys_buffer.as_mut_ptr(),
+            sealed_keys_buffer.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(match -ret {
+            c::POD_ERR_BUFFER_TOO_SMALL => PodError::SealingFailed,
+            _ => last_error(ret),
+        });
+    }
+    Ok(sealed_keys_buffer[..ret as usize].to_vec())
+}
+
+/// Seals application `data` together with additional authenticated `aad`.
+pub(super) fn seal_data(
+    enclave: &EnclaveHandle,
+    seal_policy: SealPolicy,
+    data: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    let mut sealed_data = vec![0u8; unsafe { c::pod_sealed_data_size(aad.len(), data.len()) }];
+    let ret = unsafe {
+        c::pod_seal_data(
+            enclave.as_ptr(),
+            seal_policy.to_raw(),
+            aad.as_ptr() as *const _,
+            aad.len(),
+            data.as_ptr() as *const _,
+            data.len(),
+            sealed_data.as_mut_ptr(),
+            sealed_data.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(match -ret {
+            c::POD_ERR_GENERIC | c::POD_ERR_BUFFER_TOO_SMALL => PodError::SealingFailed,
+            _ => last_error(ret),
+        });
+    }
+    sealed_data.truncate(ret as usize);
+    Ok(sealed_data)
+}
+
+/// Unseals application data sealed with [`seal_data`]. Returns the data and
+/// its additional authenticated data.
+///
+/// [`seal_data`]: fn.seal_data.html
+pub(super) fn unseal_data(
+    enclave: &EnclaveHandle,
+    sealed_data: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (mut aad_size, mut data_size) = (0, 0);
+    let ret = unsafe {
+        c::pod_get_sealed_data_sizes(
+            sealed_data.as_ptr(),
+            sealed_data.len(),
+            &mut aad_size,
+            &mut data_size,
+        )
+    };
+    if ret < 0 || data_size == 0 {
+        return Err(PodError::SealedDataInvalid);
+    }
+    let mut aad = vec![0u8; aad_size];
+    let mut data = vec![0u8; data_size];
+    let ret = unsafe {
+        c::pod_unseal_data(
+            enclave.as_ptr(),
+            sealed_data.as_ptr(),
+            sealed_data.len(),
+            aad.as_mut_ptr() as *mut _,
+            aad.len(),
+            data.as_mut_ptr() as *mut _,
+            data.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(match -ret {
+            c::POD_ERR_SEALED_STATE => PodError::SealedDataInvalid,
+            _ => last_error(ret),
+        });
     }
+    Ok((data, aad))
 }
 
-pub(super) fn unload_enclave() -> Result<()> {
-    let ret = unsafe { c::pod_unload_enclave() };
+/// Replaces the enclave's key pair. Returns the new sealed keys, and the
+/// signature of the new public key made with the old private key, both those
+/// derived for `relying_party` if given.
+pub(super) fn rotate_key(
+    enclave: &EnclaveHandle,
+    seal_policy: SealPolicy,
+    relying_party: Option<&RelyingParty>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (relying_party, relying_party_size) = relying_party_raw(relying_party);
+    let sealed_keys_buffer = &mut [0u8; MAX_SEALED_KEYS_SIZE];
+    let signature = &mut [0u8; EC_SIGNATURE];
+    let ret = unsafe {
+        c::pod_rotate_key(
+            enclave.as_ptr(),
+            seal_policy.to_raw(),
+            relying_party,
+            relying_party_size,
+            sealed_keys_buffer.as_mut_ptr(),
+            sealed_keys_buffer.len(),
+            signature.as_mut_ptr() as *mut _,
+            signature.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(match -ret {
+            c::POD_ERR_BUFFER_TOO_SMALL => PodError::SealingFailed,
+            _ => last_error(ret),
+        });
+    }
+    Ok((
+        sealed_keys_buffer[..ret as usize].to_vec(),
+        signature.to_vec(),
+    ))
+}
+
+/// Unloads the enclave. On success, the handle is freed and must not be
+/// used anymore.
+pub(super) fn unload_enclave(enclave: &EnclaveHandle) -> Result<()> {
+    let ret = unsafe { c::pod_unload_enclave(enclave.as_ptr()) };
     if ret != 0 {
-        Err(anyhow!(
-            "pod_unload_enclave returned non-zero exit code: {}",
-            ret
-        ))
+        Err(last_error(ret))
     } else {
         Ok(())
     }
 }
 
-pub(super) fn get_quote<S: AsRef<str>>(spid: S, quote_type: QuoteType) -> Result<Quote> {
-    let spid = CString::new(spid.as_ref())?;
+pub(super) fn get_public_key(
+    enclave: &EnclaveHandle,
+    relying_party: Option<&RelyingParty>,
+) -> Result<Vec<u8>> {
+    let (relying_party, relying_party_size) = relying_party_raw(relying_party);
+    let pubkey = &mut [0u8; EC_PUBLIC_KEY];
+    let ret = unsafe {
+        c::pod_get_public_key(
+            enclave.as_ptr(),
+            relying_party,
+            relying_party_size,
+            pubkey.as_mut_ptr(),
+            pubkey.len(),
+        )
+    };
+    if ret != 0 {
+        return Err(last_error(ret));
+    }
+    Ok(pubkey.to_vec())
+}
+
+pub(super) fn get_report_body(enclave: &EnclaveHandle) -> Result<Vec<u8>> {
+    let report_body = &mut [0u8; REPORT_BODY_SIZE];
+    let ret = unsafe {
+        c::pod_get_report_body(
+            enclave.as_ptr(),
+            report_body.as_mut_ptr(),
+            report_body.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(last_error(ret));
+    }
+    Ok(report_body[..ret as usize].to_vec())
+}
+
+pub(super) fn get_quote(
+    enclave: &EnclaveHandle,
+    relying_party: Option<&RelyingParty>,
+    user_data: &[u8],
+    spid: &Spid,
+    quote_type: QuoteType,
+    nonce: Option<&[u8; 16]>,
+) -> Result<Quote> {
+    let (relying_party, relying_party_size) = relying_party_raw(relying_party);
+    let c_spid = CString::new(spid.to_string())?;
     let quote_type = match quote_type {
         QuoteType::Linkable => CString::new("l")?,
         QuoteType::Unlinkable => CString::new("u")?,
@@ -79,28 +344,42 @@ pub(super) fn get_quote<S: AsRef<str>>(spid: S, quote_type: QuoteType) -> Result
     let quote_buffer = &mut [0u8; MAX_QUOTE_SIZE];
     let ret = unsafe {
         c::pod_get_quote(
-            spid.as_ptr(),
+            enclave.as_ptr(),
+            relying_party,
+            relying_party_size,
+            user_data.as_ptr() as *const _,
+            user_data.len(),
+            c_spid.as_ptr(),
             quote_type.as_ptr(),
+            nonce.map_or(ptr::null(), |nonce| nonce.as_ptr()),
             quote_buffer.as_mut_ptr(),
             MAX_QUOTE_SIZE,
         )
     };
 
     if ret < 0 {
-        return Err(anyhow!(
-            "pod_init_enclave returned non-zero exit code: {}",
-            ret
-        ));
+        return Err(match -ret {
+            c::POD_ERR_INVALID_SPID => PodError::InvalidSpid(spid.to_string()),
+            _ => last_error(ret),
+        });
     }
-    let quote_size = usize::try_from(ret)?;
+    let quote_size = ret as usize;
     Ok(Quote::from(&quote_buffer[..quote_size]))
 }
 
-pub(super) fn sign_buffer<B: AsRef<[u8]>>(message: B) -> Result<Vec<u8>> {
+pub(super) fn sign_buffer<B: AsRef<[u8]>>(
+    enclave: &EnclaveHandle,
+    relying_party: Option<&RelyingParty>,
+    message: B,
+) -> Result<Vec<u8>> {
+    let (relying_party, relying_party_size) = relying_party_raw(relying_party);
     let message = message.as_ref();
     let signature = &mut [0u8; EC_SIGNATURE];
     let ret = unsafe {
         c::pod_sign_buffer(
+            enclave.as_ptr(),
+            relying_party,
+            relying_party_size,
             message.as_ptr() as *const _,
             message.len(),
             signature.as_mut_ptr() as *mut _,
@@ -108,10 +387,32 @@ pub(super) fn sign_buffer<B: AsRef<[u8]>>(message: B) -> Result<Vec<u8>> {
         )
     };
     if ret != 0 {
-        return Err(anyhow!(
-            "pod_sign_buffer returned non-zero exit code: {}",
-            ret
-        ));
+        return Err(last_error(ret));
+    }
+    Ok(signature.to_vec())
+}
+
+/// Signs SHA-512 `digest` of a message with Ed25519ph.
+pub(super) fn sign_prehashed(
+    enclave: &EnclaveHandle,
+    relying_party: Option<&RelyingParty>,
+    digest: &[u8],
+) -> Result<Vec<u8>> {
+    let (relying_party, relying_party_size) = relying_party_raw(relying_party);
+    let signature = &mut [0u8; EC_SIGNATURE];
+    let ret = unsafe {
+        c::pod_sign_prehashed(
+            enclave.as_ptr(),
+            relying_party,
+            relying_party_size,
+            digest.as_ptr(),
+            digest.len(),
+            signature.as_mut_ptr() as *mut _,
+            signature.len(),
+        )
+    };
+    if ret != 0 {
+        return Err(last_error(ret));
     }
     Ok(signature.to_vec())
 }
@@ -0,0 +1,63 @@
+//! Exercises the SGX backend against the enclave built by `build.rs` in
+//! simulation mode. Run with `cargo test --features sgx-sim`.
+#![cfg(feature = "sgx-sim")]
+
+use pod_api::{LaunchMode, PodEnclave, RelyingParty, Result, SealedStore, SgxBackend, StoreLock};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct MemoryStore(Arc<Mutex<Option<Vec<u8>>>>);
+
+impl SealedStore for MemoryStore {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn store(&self, sealed_keys: &[u8]) -> Result<()> {
+        *self.0.lock().unwrap() = Some(sealed_keys.to_vec());
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<StoreLock> {
+        Ok(StoreLock::new(()))
+    }
+}
+
+fn load_enclave() -> PodEnclave {
+    let enclave_path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../pod-enclave/pod_enclave.signed.so");
+    let backend = SgxBackend::new(enclave_path).with_launch_mode(LaunchMode::Debug);
+    PodEnclave::with_store(backend, MemoryStore::default()).expect("enclave should load")
+}
+
+#[test]
+fn sign_reader_is_ed25519ph() {
+    let pod_enclave = load_enclave();
+    let pub_key = pod_enclave.public_key().unwrap();
+    for size in &[0, 1, 4096, 1 << 20] {
+        let document = vec![0x42; *size];
+        let signature = pod_enclave.sign_reader(&document[..]).unwrap();
+        pub_key.verify_reader(&document[..], &signature).unwrap();
+        assert!(pub_key
+            .verify_reader(&b"other document"[..], &signature)
+            .is_err());
+    }
+}
+
+#[test]
+fn sign_reader_for_uses_derived_key() {
+    let pod_enclave = load_enclave();
+    let relying_party: RelyingParty = "https://example.com".parse().unwrap();
+    let document = b"release artifact";
+    let signature = pod_enclave
+        .sign_reader_for(&relying_party, &document[..])
+        .unwrap();
+    let pub_key = pod_enclave.public_key_for(&relying_party).unwrap();
+    pub_key.verify_reader(&document[..], &signature).unwrap();
+    assert!(pod_enclave
+        .public_key()
+        .unwrap()
+        .verify_reader(&document[..], &signature)
+        .is_err());
+}
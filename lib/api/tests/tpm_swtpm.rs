@@ -0,0 +1,134 @@
+//! Exercises the TPM backend against a `swtpm` simulator started for each
+//! test, so `swtpm` and `tpm2-tools` have to be installed. Run with
+//! `cargo test --no-default-features --features tpm`.
+#![cfg(feature = "tpm")]
+
+use pod_api::{Ed25519PublicKey, EnclaveBackend, QuoteType, RelyingParty, TpmBackend};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{self, Child, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use std::{env, fs};
+
+/// `swtpm` instance listening on a free port, killed on drop.
+struct Swtpm {
+    child: Child,
+    state_dir: PathBuf,
+    port: u16,
+}
+
+impl Swtpm {
+    fn start() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let state_dir = env::temp_dir().join(format!(
+            "pod-api-swtpm-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&state_dir).unwrap();
+        // The swtpm TCTI expects the control port right after the server one.
+        let port = free_port_pair();
+        let child = Command::new("swtpm")
+            .arg("socket")
+            .arg("--tpm2")
+            .arg("--tpmstate")
+            .arg(format!("dir={}", state_dir.display()))
+            .args(["--flags", "not-need-init,startup-clear"])
+            .arg("--server")
+            .arg(format!("type=tcp,port={}", port))
+            .arg("--ctrl")
+            .arg(format!("type=tcp,port={}", port + 1))
+            .spawn()
+            .expect("swtpm has to be installed to test the TPM backend");
+        let swtpm = Self {
+            child,
+            state_dir,
+            port,
+        };
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return swtpm;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("swtpm didn't start listening on port {}", port);
+    }
+
+    fn backend(&self) -> TpmBackend {
+        TpmBackend::new().with_tcti(format!("swtpm:port={}", self.port))
+    }
+}
+
+impl Drop for Swtpm {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.state_dir);
+    }
+}
+
+fn free_port_pair() -> u16 {
+    loop {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        if port < u16::MAX && TcpListener::bind(("127.0.0.1", port + 1)).is_ok() {
+            return port;
+        }
+    }
+}
+
+#[test]
+fn sealed_keys_only_load_on_same_tpm() {
+    let swtpm = Swtpm::start();
+    let mut backend = swtpm.backend();
+    let sealed_keys = backend.init().unwrap();
+    let pub_key = backend.public_key(None).unwrap();
+
+    let mut backend = swtpm.backend();
+    backend.load(&sealed_keys).unwrap();
+    assert_eq!(backend.public_key(None).unwrap(), pub_key);
+    let signature = backend.sign(None, b"message").unwrap();
+    pub_key.verify(b"message", &signature).unwrap();
+
+    let other_swtpm = Swtpm::start();
+    assert!(other_swtpm.backend().load(&sealed_keys).is_err());
+}
+
+#[test]
+fn quotes_verify_and_bind_key() {
+    let swtpm = Swtpm::start();
+    let mut backend = swtpm.backend();
+    backend.init().unwrap();
+    let spid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+    let origin: RelyingParty = "https://example.com".parse().unwrap();
+
+    let quote = backend
+        .get_quote(Some(&origin), b"nonce", &spid, QuoteType::Unlinkable, None)
+        .unwrap();
+    assert!(TpmBackend::is_tpm_quote(&quote));
+    let attestation = TpmBackend::verify_quote(&quote).unwrap();
+    assert_eq!(
+        attestation.attestation_key(),
+        &backend.attestation_key().unwrap()
+    );
+    assert_eq!(
+        backend.report().unwrap().identity().mr_signer(),
+        backend.attestation_key().unwrap().as_bytes()
+    );
+    assert_eq!(
+        Ed25519PublicKey::from_report_data(quote.report_data().unwrap(), b"nonce").unwrap(),
+        backend.public_key(Some(&origin)).unwrap()
+    );
+
+    // The attestation key is recreated for each quote, but from the same
+    // template, so it's the same key every time.
+    let quote = backend
+        .get_quote(None, b"", &spid, QuoteType::Unlinkable, None)
+        .unwrap();
+    assert_eq!(
+        TpmBackend::verify_quote(&quote).unwrap().attestation_key(),
+        attestation.attestation_key()
+    );
+}
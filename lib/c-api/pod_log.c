@@ -1,9 +1,15 @@
+#include <stdarg.h>
+
 #include "pod_log.h"
 
+#define LOG_BUFFER_MAX 4096
+
 int g_stdout_fd = 1;
 int g_stderr_fd = 2;
 bool g_verbose = false;
 
+static pod_log_callback_t g_log_callback = NULL;
+
 void set_verbose(bool verbose) {
   g_verbose = verbose;
   if (verbose)
@@ -11,3 +17,25 @@ void set_verbose(bool verbose) {
   else
     DBG("Verbose output disabled\n");
 }
+
+void pod_set_log_callback(pod_log_callback_t callback) {
+  g_log_callback = callback;
+}
+
+void pod_log(int level, const char* fmt, ...) {
+  char buf[LOG_BUFFER_MAX];
+  va_list ap;
+
+  va_start(ap, fmt);
+  vsnprintf(buf, sizeof(buf), fmt, ap);
+  va_end(ap);
+
+  if (g_log_callback) {
+    g_log_callback(level, buf);
+    return;
+  }
+
+  if (level == POD_LOG_DEBUG && !g_verbose)
+    return;
+  dprintf(level == POD_LOG_ERROR ? g_stderr_fd : g_stdout_fd, "%s", buf);
+}
@@ -4,14 +4,20 @@
 #include <stdio.h>
 #include <stdbool.h>
 
+#include "pod_sgx.h"
+
 // Source: https://github.com/oscarlab/graphene/blob/master/Pal/src/host/Linux-SGX/tools/common/util.h#L33
 extern int g_stdout_fd;
 extern int g_stderr_fd;
 extern bool g_verbose;
 
-#define DBG(fmt, ...)   do { if (g_verbose) dprintf(g_stdout_fd, fmt, ##__VA_ARGS__); } while (0)
-#define INFO(fmt, ...)  do { dprintf(g_stdout_fd, fmt, ##__VA_ARGS__); } while (0)
-#define ERROR(fmt, ...) do { dprintf(g_stderr_fd, "%s: " fmt, __FUNCTION__, ##__VA_ARGS__); } while (0)
+/* Passes the message to the log callback if one is set, otherwise prints it to stdout (or stderr
+ * for errors). Debug messages are only printed in verbose mode. */
+void pod_log(int level, const char* fmt, ...) __attribute__((format(printf, 2, 3)));
+
+#define DBG(fmt, ...)   pod_log(POD_LOG_DEBUG, fmt, ##__VA_ARGS__)
+#define INFO(fmt, ...)  pod_log(POD_LOG_INFO, fmt, ##__VA_ARGS__)
+#define ERROR(fmt, ...) pod_log(POD_LOG_ERROR, "%s: " fmt, __FUNCTION__, ##__VA_ARGS__)
 
 void set_verbose(bool verbose);
 
@@ -97,6 +97,19 @@ out:
     return status;
 }
 
+// Status of the last failed SGX call, see pod_last_sgx_status()
+static __thread sgx_status_t g_last_sgx_status = SGX_SUCCESS;
+
+/* Records the failed SGX call status and returns negated POD_ERR_* code */
+static int sgx_error(sgx_status_t sgx_ret, int err) {
+    g_last_sgx_status = sgx_ret;
+    return -err;
+}
+
+uint32_t pod_last_sgx_status(void) {
+    return g_last_sgx_status;
+}
+
 /* Returns 0 on failure */
 static sgx_enclave_id_t enclave_load(const char* enclave_path, bool debug_enabled) {
     int is_token_updated = 0;
@@ -109,6 +122,7 @@ static sgx_enclave_id_t enclave_load(const char* enclave_path, bool debug_enable
     sgx_status_t sgx_ret = sgx_create_enclave(enclave_path, debug_enabled, &launch_token,
                                               &is_token_updated, &enclave_id, &misc_attribs);
     if (sgx_ret != SGX_SUCCESS) {
+        g_last_sgx_status = sgx_ret;
         ERROR("Failed to load enclave: %d\n", sgx_ret);
     } else {
         DBG("Enclave loaded successfully, id = 0x%lx\n", enclave_id);
@@ -117,6 +131,16 @@ static sgx_enclave_id_t enclave_load(const char* enclave_path, bool debug_enable
     return enclave_id;
 }
 
+/* Maps failure of enclave_load() to POD_ERR_* code */
+static int enclave_load_error(void) {
+    if (g_last_sgx_status == SGX_ERROR_ENCLAVE_FILE_ACCESS)
+        return -POD_ERR_ENCLAVE_NOT_FOUND;
+    // Production launch of an enclave whose signing key isn't allowed to do so
+    if (g_last_sgx_status == SGX_ERROR_SERVICE_INVALID_PRIVILEGE)
+        return -POD_ERR_LAUNCH_DENIED;
+    return -POD_ERR_ENCLAVE_LOAD;
+}
+
 static sgx_status_t enclave_unload(sgx_enclave_id_t enclave_id) {
     sgx_status_t sgx_ret = sgx_destroy_enclave(enclave_id);
     if (sgx_ret != SGX_SUCCESS)
@@ -127,84 +151,137 @@ static sgx_status_t enclave_unload(sgx_enclave_id_t enclave_id) {
     return sgx_ret;
 }
 
-static sgx_enclave_id_t g_enclave_id = 0;
-static uint8_t* g_sealed_state = NULL;
-static size_t g_sealed_state_size = 0;
+struct pod_enclave {
+    sgx_enclave_id_t enclave_id;
+    uint8_t public_key[EC_PUBLIC_KEY_SIZE];
+};
 
-static int load_pod_enclave_fresh(const char* enclave_path, bool debug_enabled,
-                                  uint8_t* sealed_state, size_t sealed_state_size) {
-    int ret = -1;
-    uint8_t* sealed_keys = NULL;
+// Output buffer for o_store_sealed_data(). OCALLs run on the thread which made the ECALL, so
+// keeping it thread-local lets several enclaves be initialized concurrently.
+static __thread uint8_t* g_sealed_state = NULL;
+static __thread size_t g_sealed_state_size = 0;
 
-    if (g_enclave_id != 0) {
-        ERROR("Enclave already loaded with id %lu\n", g_enclave_id);
+/* Loads the enclave and calls e_initialize() on it. On success, stores the new instance in
+ * `enclave`. */
+static int load_pod_enclave(const char* enclave_path, bool debug_enabled, uint16_t seal_policy,
+                            const uint8_t* sealed_keys, size_t sealed_keys_size,
+                            pod_enclave_t** enclave) {
+    int ret = -POD_ERR_ENCLAVE_LOAD;
+    pod_enclave_t* pod_enclave = NULL;
+
+    if (!enclave_path || !enclave) {
+        ret = -POD_ERR_INVALID_ARGUMENT;
         goto out;
     }
 
-    g_sealed_state = sealed_state;
-    g_sealed_state_size = sealed_state_size;
-
-    g_enclave_id = enclave_load(enclave_path, debug_enabled);
-    if (g_enclave_id == 0)
+    pod_enclave = calloc(1, sizeof(*pod_enclave));
+    if (!pod_enclave) {
+        ERROR("No memory\n");
         goto out;
+    }
 
-    size_t sealed_size = 0;
+    pod_enclave->enclave_id = enclave_load(enclave_path, debug_enabled);
+    if (pod_enclave->enclave_id == 0) {
+        ret = enclave_load_error();
+        goto out;
+    }
 
     // ECALL: enclave initialization
-    sgx_status_t sgx_ret = e_initialize(g_enclave_id, &ret, sealed_keys, sealed_size, NULL, 0);
+    sgx_status_t sgx_ret = e_initialize(pod_enclave->enclave_id, &ret, (uint8_t*) sealed_keys,
+                                        sealed_keys_size, seal_policy, pod_enclave->public_key,
+                                        sizeof(pod_enclave->public_key));
 
     if (sgx_ret != SGX_SUCCESS) {
         ERROR("Failed to call enclave initialization\n");
+        ret = sgx_error(sgx_ret, POD_ERR_ENCLAVE_LOAD);
         goto out;
     }
 
     if (ret < 0) {
         ERROR("Enclave initialization failed\n");
+        // Unsealing fails if the state is corrupt or was sealed by a different enclave
+        ret = sealed_keys ? -POD_ERR_SEALED_STATE : -POD_ERR_ENCLAVE_LOAD;
         goto out;
     }
 
-    ret = g_sealed_state_size;
+    *enclave = pod_enclave;
+    pod_enclave = NULL;
+    ret = 0;
 out:
-    free(sealed_keys);
+    if (pod_enclave) {
+        if (pod_enclave->enclave_id != 0)
+            enclave_unload(pod_enclave->enclave_id);
+        free(pod_enclave);
+    }
     return ret;
 }
 
-static int load_pod_enclave_from_state(const char* enclave_path, bool debug_enabled,
-                                       const uint8_t* sealed_state, size_t sealed_state_size) {
-    int ret = -1;
-
-    if (g_enclave_id != 0) {
-        ERROR("Enclave already loaded with id %lu\n", g_enclave_id);
-        goto out;
-    }
+static bool valid_seal_policy(uint16_t seal_policy) {
+    return seal_policy == POD_SEAL_MRENCLAVE || seal_policy == POD_SEAL_MRSIGNER;
+}
 
-    g_enclave_id = enclave_load(enclave_path, debug_enabled);
-    if (g_enclave_id == 0)
-        goto out;
+static bool valid_launch_mode(uint16_t launch_mode) {
+    return launch_mode == POD_LAUNCH_PRODUCTION || launch_mode == POD_LAUNCH_DEBUG;
+}
 
-    DBG("Loading sealed enclave state from provided buffer\n");
+static int load_pod_enclave_fresh(const char* enclave_path, bool debug_enabled,
+                                  uint16_t seal_policy, uint8_t* sealed_state,
+                                  size_t sealed_state_size, pod_enclave_t** enclave) {
+    if (!valid_seal_policy(seal_policy))
+        return -POD_ERR_INVALID_ARGUMENT;
 
-    // ECALL: enclave initialization
-    sgx_status_t sgx_ret = e_initialize(g_enclave_id, &ret, (uint8_t*) sealed_state, sealed_state_size, NULL, 0);
+    g_sealed_state = sealed_state;
+    g_sealed_state_size = sealed_state_size;
 
-    if (sgx_ret != SGX_SUCCESS) {
-        ERROR("Failed to call enclave initialization\n");
-        goto out;
+    int ret = load_pod_enclave(enclave_path, debug_enabled, seal_policy, NULL, 0, enclave);
+    if (ret == -POD_ERR_ENCLAVE_LOAD && g_sealed_state_size > sealed_state_size) {
+        // o_store_sealed_data() records the required size if the buffer was too small
+        ret = -POD_ERR_BUFFER_TOO_SMALL;
+    } else if (ret == 0) {
+        ret = g_sealed_state_size;
     }
 
-    if (ret < 0) {
-        ERROR("Enclave initialization failed\n");
-        goto out;
+    g_sealed_state = NULL;
+    g_sealed_state_size = 0;
+    return ret;
+}
+
+static int load_pod_enclave_from_state(const char* enclave_path, bool debug_enabled,
+                                       const uint8_t* sealed_state, size_t sealed_state_size,
+                                       pod_enclave_t** enclave) {
+    if (!sealed_state || sealed_state_size == 0)
+        return -POD_ERR_INVALID_ARGUMENT;
+
+    DBG("Loading sealed enclave state from provided buffer\n");
+    // The policy only matters for sealing, unsealing works regardless
+    return load_pod_enclave(enclave_path, debug_enabled, POD_SEAL_MRENCLAVE, sealed_state,
+                            sealed_state_size, enclave);
+}
+
+/* Maps failure of quoting related SGX calls to POD_ERR_* code */
+static int quote_error(sgx_status_t sgx_ret) {
+    switch (sgx_ret) {
+        case SGX_ERROR_SERVICE_UNAVAILABLE:
+        case SGX_ERROR_SERVICE_TIMEOUT:
+        case SGX_ERROR_BUSY:
+            return sgx_error(sgx_ret, POD_ERR_AESM_UNAVAILABLE);
+        default:
+            return sgx_error(sgx_ret, POD_ERR_QUOTE);
     }
+}
 
-    ret = 0;
-out:
-    return ret;
+static bool valid_relying_party(const uint8_t* relying_party, size_t relying_party_size) {
+    if (!relying_party)
+        return relying_party_size == 0;
+    return relying_party_size > 0 && relying_party_size <= POD_MAX_RELYING_PARTY_SIZE;
 }
 
-static int generate_enclave_quote(sgx_spid_t sp_id, sgx_quote_sign_type_t quote_type,
+static int generate_enclave_quote(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                                  size_t relying_party_size, const void* user_data,
+                                  size_t user_data_size, sgx_spid_t sp_id,
+                                  sgx_quote_sign_type_t quote_type, const uint8_t* nonce,
                                   uint8_t* quote_buffer, size_t quote_buffer_size) {
-    int ret = -1;
+    int ret = -POD_ERR_QUOTE;
     sgx_status_t sgx_ret = SGX_ERROR_UNEXPECTED;
     sgx_epid_group_id_t epid_group_id = { 0 };
     sgx_target_info_t qe_info = { 0 };
@@ -213,8 +290,9 @@ static int generate_enclave_quote(sgx_spid_t sp_id, sgx_quote_sign_type_t quote_
     sgx_report_t qe_report = { 0 };
     uint32_t quote_size = 0;
 
-    if (g_enclave_id == 0) {
+    if (!enclave) {
         ERROR("Enclave not loaded\n");
+        ret = -POD_ERR_NOT_LOADED;
         goto out;
     }
 
@@ -222,6 +300,7 @@ static int generate_enclave_quote(sgx_spid_t sp_id, sgx_quote_sign_type_t quote_
     sgx_ret = sgx_init_quote(&qe_info, &epid_group_id);
     if (sgx_ret != SGX_SUCCESS) {
         ERROR("Failed to initialize quoting process\n");
+        ret = quote_error(sgx_ret);
         goto out;
     }
 
@@ -230,30 +309,35 @@ static int generate_enclave_quote(sgx_spid_t sp_id, sgx_quote_sign_type_t quote_
 
     if (sgx_ret != SGX_SUCCESS) {
         ERROR("Failed to calculate quote size\n");
+        ret = quote_error(sgx_ret);
         goto out;
     }
 
     if (quote_buffer_size < quote_size) {
         ERROR("Provided buffer size is too small to fit the quote of size %d\n", quote_size);
-        ret = -1;
+        ret = -POD_ERR_BUFFER_TOO_SMALL;
         goto out;
     }
 
     // ECALL: generate enclave's report, targeted to Quoting Enclave (QE)
-    sgx_ret = e_get_report(g_enclave_id, &ret, &qe_info, &report);
+    sgx_ret = e_get_report(enclave->enclave_id, &ret, &qe_info, relying_party,
+                           relying_party_size, user_data, user_data_size, &report);
     if (sgx_ret != SGX_SUCCESS || ret < 0) {
-        ret = -1;
+        ret = sgx_error(sgx_ret, POD_ERR_QUOTE);
         ERROR("Failed to get enclave's report\n");
         goto out;
     }
 
-    // Prepare random nonce
-    // TODO: ideally this nonce would be received from a 3rd party on a different system
-    // that will verify the QE report
-    size_t nonce_size = sizeof(qe_nonce);
-    if (!read_file(&qe_nonce, "/dev/urandom", &nonce_size)) {
-        ret = -1;
-        goto out;
+    // Use the nonce provided by the caller (ideally received from a 3rd party on a different
+    // system that will verify the QE report), otherwise prepare a random one
+    if (nonce) {
+        memcpy(&qe_nonce, nonce, sizeof(qe_nonce));
+    } else {
+        size_t nonce_size = sizeof(qe_nonce);
+        if (!read_file(&qe_nonce, "/dev/urandom", &nonce_size)) {
+            ret = -POD_ERR_GENERIC;
+            goto out;
+        }
     }
 
     // Get enclave's quote. TODO: use revocation list
@@ -269,12 +353,13 @@ static int generate_enclave_quote(sgx_spid_t sp_id, sgx_quote_sign_type_t quote_
 
     if (sgx_ret != SGX_SUCCESS) {
         ERROR("Failed to get enclave quote: %d\n", sgx_ret);
+        ret = quote_error(sgx_ret);
         goto out;
     }
 
     // Calculate expected qe_report.body.report_data
     // It should be sha256(nonce||quote)
-    ret = -1;
+    ret = -POD_ERR_QUOTE;
     uint8_t hash[SHA256_DIGEST_LENGTH];
     SHA256_CTX sha;
 
@@ -308,28 +393,309 @@ out:
     return ret;
 }
 
-int pod_init_enclave(const char* enclave_path, uint8_t* sealed_state, size_t sealed_state_size) {
-    return load_pod_enclave_fresh(enclave_path, ENCLAVE_DEBUG_ENABLED, sealed_state, sealed_state_size);
+int pod_init_enclave(const char* enclave_path, uint16_t launch_mode, uint16_t seal_policy,
+                     uint8_t* sealed_state, size_t sealed_state_size, pod_enclave_t** enclave) {
+    g_last_sgx_status = SGX_SUCCESS;
+    if (!valid_launch_mode(launch_mode))
+        return -POD_ERR_INVALID_ARGUMENT;
+    return load_pod_enclave_fresh(enclave_path, launch_mode == POD_LAUNCH_DEBUG, seal_policy,
+                                  sealed_state, sealed_state_size, enclave);
+}
+
+int pod_load_enclave(const char* enclave_path, uint16_t launch_mode, const uint8_t* sealed_state,
+                     size_t sealed_state_size, pod_enclave_t** enclave) {
+    g_last_sgx_status = SGX_SUCCESS;
+    if (!valid_launch_mode(launch_mode))
+        return -POD_ERR_INVALID_ARGUMENT;
+    return load_pod_enclave_from_state(enclave_path, launch_mode == POD_LAUNCH_DEBUG,
+                                       sealed_state, sealed_state_size, enclave);
+}
+
+int pod_reseal_keys(const pod_enclave_t* enclave, uint16_t seal_policy, uint8_t* sealed_state,
+                    size_t sealed_state_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        return -POD_ERR_NOT_LOADED;
+    }
+
+    if (!valid_seal_policy(seal_policy) || !sealed_state || sealed_state_size == 0)
+        return -POD_ERR_INVALID_ARGUMENT;
+
+    g_sealed_state = sealed_state;
+    g_sealed_state_size = sealed_state_size;
+
+    // ECALL: seal keys, they are passed back via o_store_sealed_data()
+    sgx_status_t sgx_ret = e_seal_keys(enclave->enclave_id, &ret, seal_policy);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to call enclave sealing\n");
+        ret = sgx_error(sgx_ret, POD_ERR_GENERIC);
+    } else if (ret < 0) {
+        ERROR("Sealing enclave keys failed\n");
+        // o_store_sealed_data() records the required size if the buffer was too small
+        ret = g_sealed_state_size > sealed_state_size ? -POD_ERR_BUFFER_TOO_SMALL
+                                                      : -POD_ERR_GENERIC;
+    } else {
+        ret = g_sealed_state_size;
+    }
+
+    g_sealed_state = NULL;
+    g_sealed_state_size = 0;
+    return ret;
+}
+
+size_t pod_sealed_data_size(size_t aad_size, size_t data_size) {
+    return sizeof(sgx_sealed_data_t) + sizeof(SEALED_DATA_DOMAIN) + aad_size + data_size;
+}
+
+int pod_seal_data(const pod_enclave_t* enclave, uint16_t seal_policy, const void* aad,
+                  size_t aad_size, const void* data, size_t data_size, uint8_t* sealed_data,
+                  size_t sealed_data_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        return -POD_ERR_NOT_LOADED;
+    }
+
+    if (!valid_seal_policy(seal_policy) || (!aad && aad_size > 0) || !data || data_size == 0
+        || aad_size > POD_MAX_SEALED_DATA_SIZE || data_size > POD_MAX_SEALED_DATA_SIZE - aad_size) {
+        ERROR("Invalid data to seal\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    if (!sealed_data || sealed_data_size < pod_sealed_data_size(aad_size, data_size)) {
+        ERROR("Sealed data buffer too small\n");
+        return -POD_ERR_BUFFER_TOO_SMALL;
+    }
+
+    // ECALL: seal data
+    sgx_status_t sgx_ret = e_seal_data(enclave->enclave_id, &ret, seal_policy, aad, aad_size,
+                                       data, data_size, sealed_data, sealed_data_size);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to call enclave sealing\n");
+        ret = sgx_error(sgx_ret, POD_ERR_GENERIC);
+    } else if (ret < 0) {
+        ERROR("Sealing data failed\n");
+        ret = -POD_ERR_GENERIC;
+    }
+
+    return ret;
+}
+
+int pod_get_sealed_data_sizes(const uint8_t* sealed_data, size_t sealed_data_size,
+                              size_t* aad_size, size_t* data_size) {
+    if (!sealed_data || !aad_size || !data_size)
+        return -POD_ERR_INVALID_ARGUMENT;
+
+    if (sealed_data_size < sizeof(sgx_sealed_data_t))
+        return -POD_ERR_SEALED_STATE;
+
+    sgx_sealed_data_t header;
+    memcpy(&header, sealed_data, sizeof(header));
+    uint32_t payload_size = header.aes_data.payload_size;
+    uint32_t text_size = header.plain_text_offset;
+    if (text_size > payload_size || sealed_data_size - sizeof(header) < payload_size
+        || payload_size - text_size < sizeof(SEALED_DATA_DOMAIN))
+        return -POD_ERR_SEALED_STATE;
+
+    *aad_size = payload_size - text_size - sizeof(SEALED_DATA_DOMAIN);
+    *data_size = text_size;
+    return 0;
 }
 
-int pod_load_enclave(const char* enclave_path, const uint8_t* sealed_state, size_t sealed_state_size) {
-    return load_pod_enclave_from_state(enclave_path, ENCLAVE_DEBUG_ENABLED, sealed_state, sealed_state_size);
+int pod_unseal_data(const pod_enclave_t* enclave, const uint8_t* sealed_data,
+                    size_t sealed_data_size, void* aad, size_t aad_size, void* data,
+                    size_t data_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        return -POD_ERR_NOT_LOADED;
+    }
+
+    if (!sealed_data || (!aad && aad_size > 0) || !data || data_size == 0) {
+        ERROR("Invalid buffers to unseal to\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    // ECALL: unseal data
+    sgx_status_t sgx_ret = e_unseal_data(enclave->enclave_id, &ret, sealed_data, sealed_data_size,
+                                         aad, aad_size, data, data_size);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to call enclave unsealing\n");
+        ret = sgx_error(sgx_ret, POD_ERR_GENERIC);
+    } else if (ret < 0) {
+        ERROR("Unsealing data failed\n");
+        ret = -POD_ERR_SEALED_STATE;
+    }
+
+    return ret;
 }
 
-int pod_unload_enclave(void) {
-    if (g_enclave_id == 0)
+int pod_rotate_key(pod_enclave_t* enclave, uint16_t seal_policy, const uint8_t* relying_party,
+                   size_t relying_party_size, uint8_t* sealed_state, size_t sealed_state_size,
+                   void* signature, size_t signature_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+    uint8_t public_key[EC_PUBLIC_KEY_SIZE];
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        return -POD_ERR_NOT_LOADED;
+    }
+
+    if (!valid_seal_policy(seal_policy) || !sealed_state || sealed_state_size == 0)
+        return -POD_ERR_INVALID_ARGUMENT;
+
+    if (!signature || signature_size != EC_SIGNATURE_SIZE) {
+        ERROR("Invalid signature buffer\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    if (!valid_relying_party(relying_party, relying_party_size)) {
+        ERROR("Invalid relying party\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    g_sealed_state = sealed_state;
+    g_sealed_state_size = sealed_state_size;
+
+    // ECALL: rotate keys, the new sealed keys are passed back via o_store_sealed_data()
+    sgx_status_t sgx_ret =
+        e_rotate_key(enclave->enclave_id, &ret, seal_policy, relying_party, relying_party_size,
+                     public_key, sizeof(public_key), signature, signature_size);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to call enclave key rotation\n");
+        ret = sgx_error(sgx_ret, POD_ERR_GENERIC);
+    } else if (ret < 0) {
+        ERROR("Rotating enclave keys failed\n");
+        ret = g_sealed_state_size > sealed_state_size ? -POD_ERR_BUFFER_TOO_SMALL
+                                                      : -POD_ERR_GENERIC;
+    } else {
+        memcpy(enclave->public_key, public_key, sizeof(public_key));
+        ret = g_sealed_state_size;
+    }
+
+    g_sealed_state = NULL;
+    g_sealed_state_size = 0;
+    return ret;
+}
+
+int pod_unload_enclave(pod_enclave_t* enclave) {
+    g_last_sgx_status = SGX_SUCCESS;
+    if (!enclave)
+        return 0;
+    sgx_status_t sgx_ret = enclave_unload(enclave->enclave_id);
+    if (sgx_ret != SGX_SUCCESS)
+        return sgx_error(sgx_ret, POD_ERR_GENERIC);
+    memset(enclave, 0, sizeof(*enclave));
+    free(enclave);
+    return 0;
+}
+
+int pod_get_public_key(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                       size_t relying_party_size, uint8_t* pubkey, size_t pubkey_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        return -POD_ERR_NOT_LOADED;
+    }
+
+    if (!pubkey || pubkey_size < sizeof(enclave->public_key)) {
+        ERROR("Invalid public key buffer\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    if (!valid_relying_party(relying_party, relying_party_size)) {
+        ERROR("Invalid relying party\n");
+        return -POD_ERR_INVALID_ARGUMENT;
+    }
+
+    // The master public key is cached, derived ones have to be asked for
+    if (!relying_party) {
+        memcpy(pubkey, enclave->public_key, sizeof(enclave->public_key));
         return 0;
-    int ret = enclave_unload(g_enclave_id);
-    if (ret == 0)
-        g_enclave_id = 0;
+    }
+
+    // ECALL: derive relying party's public key
+    sgx_status_t sgx_ret = e_get_public_key(enclave->enclave_id, &ret, relying_party,
+                                            relying_party_size, pubkey, sizeof(enclave->public_key));
+    if (sgx_ret != SGX_SUCCESS || ret < 0) {
+        ERROR("Failed to derive public key\n");
+        return sgx_error(sgx_ret, POD_ERR_GENERIC);
+    }
+
+    return 0;
+}
+
+int pod_get_report_body(const pod_enclave_t* enclave, uint8_t* report_body,
+                        size_t report_body_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+    // Zeroed target info: we only need our own measurements, nobody verifies the report MAC
+    sgx_target_info_t target_info = { 0 };
+    sgx_report_t report = { 0 };
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        ret = -POD_ERR_NOT_LOADED;
+        goto out;
+    }
+
+    if (!report_body || report_body_size < sizeof(report.body)) {
+        ERROR("Invalid report body buffer\n");
+        goto out;
+    }
+
+    // ECALL: generate enclave's report
+    sgx_status_t sgx_ret = e_get_report(enclave->enclave_id, &ret, &target_info, NULL, 0, NULL,
+                                        0, &report);
+    if (sgx_ret != SGX_SUCCESS || ret < 0) {
+        ret = sgx_error(sgx_ret, POD_ERR_GENERIC);
+        ERROR("Failed to get enclave's report\n");
+        goto out;
+    }
+
+    memcpy(report_body, &report.body, sizeof(report.body));
+    ret = sizeof(report.body);
+out:
     return ret;
 }
 
-int pod_get_quote(const char* sp_id_str, const char* sp_quote_type_str, uint8_t* quote_buffer,
+int pod_get_quote(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                  size_t relying_party_size, const void* user_data, size_t user_data_size,
+                  const char* sp_id_str,
+                  const char* sp_quote_type_str, const uint8_t* nonce, uint8_t* quote_buffer,
                   size_t quote_buffer_size) {
     sgx_spid_t sp_id = { 0 };
     sgx_quote_sign_type_t sp_quote_type;
-    int ret = -1;
+    int ret = -POD_ERR_INVALID_SPID;
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!valid_relying_party(relying_party, relying_party_size)) {
+        ERROR("Invalid relying party\n");
+        ret = -POD_ERR_INVALID_ARGUMENT;
+        goto out;
+    }
+
+    if (!user_data && user_data_size > 0) {
+        ERROR("Invalid user data buffer\n");
+        ret = -POD_ERR_INVALID_ARGUMENT;
+        goto out;
+    }
 
     // parse SPID
     if (strlen(sp_id_str) != 32) {
@@ -356,16 +722,23 @@ int pod_get_quote(const char* sp_id_str, const char* sp_quote_type_str, uint8_t*
         goto out;
     }
 
-    ret = generate_enclave_quote(sp_id, sp_quote_type, quote_buffer, quote_buffer_size);
+    ret = generate_enclave_quote(enclave, relying_party, relying_party_size, user_data,
+                                 user_data_size, sp_id, sp_quote_type, nonce, quote_buffer,
+                                 quote_buffer_size);
 out:
     return ret;
 }
 
-int pod_sign_buffer(const void* data, size_t data_size, void* signature, size_t signature_size) {
-    int ret = -1;
+int pod_sign_buffer(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                    size_t relying_party_size, const void* data, size_t data_size,
+                    void* signature, size_t signature_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
 
-    if (g_enclave_id == 0) {
+    if (!enclave) {
         ERROR("PoD enclave not loaded\n");
+        ret = -POD_ERR_NOT_LOADED;
         goto out;
     }
 
@@ -379,11 +752,17 @@ int pod_sign_buffer(const void* data, size_t data_size, void* signature, size_t
         goto out;
     }
 
+    if (!valid_relying_party(relying_party, relying_party_size)) {
+        ERROR("Invalid relying party\n");
+        goto out;
+    }
+
     // ECALL: sign data
-    sgx_status_t sgx_ret = e_sign_data(g_enclave_id, &ret, data, data_size, signature,
+    sgx_status_t sgx_ret = e_sign_data(enclave->enclave_id, &ret, relying_party,
+                                       relying_party_size, data, data_size, signature,
                                        signature_size);
     if (sgx_ret != SGX_SUCCESS || ret < 0) {
-        ret = -1;
+        ret = sgx_error(sgx_ret, POD_ERR_SIGN);
         ERROR("Failed to sign data\n");
         goto out;
     }
@@ -392,8 +771,81 @@ out:
     return ret;
 }
 
-int pod_sign_file(const char* input_path, const char* signature_path) {
-    int ret = -1;
+int pod_sign_prehashed(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                       size_t relying_party_size, const uint8_t* digest, size_t digest_size,
+                       void* signature, size_t signature_size) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+
+    g_last_sgx_status = SGX_SUCCESS;
+
+    if (!enclave) {
+        ERROR("PoD enclave not loaded\n");
+        ret = -POD_ERR_NOT_LOADED;
+        goto out;
+    }
+
+    if (!digest || digest_size != EC_PREHASH_SIZE) {
+        ERROR("Invalid digest buffer\n");
+        goto out;
+    }
+
+    if (!signature || signature_size == 0) {
+        ERROR("Invalid signature buffer\n");
+        goto out;
+    }
+
+    if (!valid_relying_party(relying_party, relying_party_size)) {
+        ERROR("Invalid relying party\n");
+        goto out;
+    }
+
+    // ECALL: sign digest
+    sgx_status_t sgx_ret = e_sign_prehashed(enclave->enclave_id, &ret, relying_party,
+                                            relying_party_size, digest, digest_size, signature,
+                                            signature_size);
+    if (sgx_ret != SGX_SUCCESS || ret < 0) {
+        ret = sgx_error(sgx_ret, POD_ERR_SIGN);
+        ERROR("Failed to sign digest\n");
+        goto out;
+    }
+
+out:
+    return ret;
+}
+
+/* Hash the file at path with SHA-512 in chunks, so that it never has to fit in memory. */
+static int hash_file(const char* path, uint8_t digest[EC_PREHASH_SIZE]) {
+    int ret = -POD_ERR_GENERIC;
+    uint8_t chunk[4096];
+    SHA512_CTX sha;
+
+    FILE* f = fopen(path, "rb");
+    if (!f) {
+        ERROR("Failed to open file '%s': %s\n", path, strerror(errno));
+        goto out;
+    }
+
+    SHA512_Init(&sha);
+    size_t size;
+    while ((size = fread(chunk, 1, sizeof(chunk), f)) > 0)
+        SHA512_Update(&sha, chunk, size);
+
+    if (ferror(f)) {
+        ERROR("Failed to read file '%s'\n", path);
+        goto out;
+    }
+
+    SHA512_Final(digest, &sha);
+    ret = 0;
+
+out:
+    if (f)
+        fclose(f);
+    return ret;
+}
+
+int pod_sign_file(const pod_enclave_t* enclave, const char* input_path, const char* signature_path) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
     uint8_t signature[EC_SIGNATURE_SIZE];
 
     if (!input_path || !signature_path) {
@@ -403,17 +855,53 @@ int pod_sign_file(const char* input_path, const char* signature_path) {
 
     size_t input_size = 0;
     void* input = read_file(NULL, input_path, &input_size);
-    if (!input)
+    if (!input) {
+        ret = -POD_ERR_GENERIC;
         goto out;
+    }
 
-    ret = pod_sign_buffer(input, input_size, &signature, sizeof(signature));
+    ret = pod_sign_buffer(enclave, NULL, 0, input, input_size, &signature, sizeof(signature));
+    free(input);
     if (ret < 0)
         goto out;
 
-    ret = write_file(signature_path, sizeof(signature), &signature);
+    if (write_file(signature_path, sizeof(signature), &signature) != 0) {
+        ret = -POD_ERR_GENERIC;
+        goto out;
+    }
+
+    DBG("Saved signature to '%s'\n", signature_path);
+    ret = 0;
+
+out:
+    return ret;
+}
+
+int pod_sign_file_prehashed(const pod_enclave_t* enclave, const char* input_path,
+                            const char* signature_path) {
+    int ret = -POD_ERR_INVALID_ARGUMENT;
+    uint8_t digest[EC_PREHASH_SIZE];
+    uint8_t signature[EC_SIGNATURE_SIZE];
+
+    if (!input_path || !signature_path) {
+        ERROR("Invalid path\n");
+        goto out;
+    }
+
+    ret = hash_file(input_path, digest);
+    if (ret < 0)
+        goto out;
+
+    ret = pod_sign_prehashed(enclave, NULL, 0, digest, sizeof(digest), &signature,
+                             sizeof(signature));
     if (ret < 0)
         goto out;
 
+    if (write_file(signature_path, sizeof(signature), &signature) != 0) {
+        ret = -POD_ERR_GENERIC;
+        goto out;
+    }
+
     DBG("Saved signature to '%s'\n", signature_path);
     ret = 0;
 
@@ -427,6 +915,7 @@ int o_store_sealed_data(const uint8_t* sealed_data, size_t sealed_size) {
 
     if (g_sealed_state_size < sealed_size) {
       ERROR("Provided buffer is too small to fit required size: %ld\n", sealed_size);
+      g_sealed_state_size = sealed_size;
       return -1;
     }
 
@@ -435,7 +924,7 @@ int o_store_sealed_data(const uint8_t* sealed_data, size_t sealed_size) {
     return 0;
 }
 
-// OCALL: print string
-void o_print(const char* str) {
-    DBG("%s", str);
+// OCALL: log message of the enclave
+void o_log(int level, const char* str) {
+    pod_log(level, "%s", str);
 }
@@ -1,8 +1,76 @@
 #ifndef POD_SGX_H
 #define POD_SGX_H
 
-/** Enables enclave debugging and NULLIFIES ENCLAVE MEMORY PROTECTION. */
-#define ENCLAVE_DEBUG_ENABLED 1
+#include <stddef.h>
+#include <stdint.h>
+
+/* Error codes. Functions below return them negated, e.g. -POD_ERR_INVALID_SPID. */
+#define POD_ERR_GENERIC            1 /*!< Unspecified error. */
+#define POD_ERR_INVALID_ARGUMENT   2 /*!< Invalid argument (e.g. NULL buffer). */
+#define POD_ERR_ENCLAVE_NOT_FOUND  3 /*!< Enclave binary could not be opened. */
+#define POD_ERR_ENCLAVE_LOAD       4 /*!< Loading or initializing the enclave failed. */
+#define POD_ERR_NOT_LOADED         5 /*!< Enclave is not loaded. */
+#define POD_ERR_SEALED_STATE       6 /*!< Sealed state is corrupt or from another enclave. */
+#define POD_ERR_AESM_UNAVAILABLE   7 /*!< AESM service could not be reached. */
+#define POD_ERR_INVALID_SPID       8 /*!< Invalid SPID or quote type. */
+#define POD_ERR_BUFFER_TOO_SMALL   9 /*!< Provided output buffer is too small. */
+#define POD_ERR_QUOTE             10 /*!< Generating the quote failed. */
+#define POD_ERR_SIGN              11 /*!< Signing failed. */
+#define POD_ERR_LAUNCH_DENIED     12 /*!< Enclave is not allowed to run in production mode. */
+
+/* Sealing policies, i.e. which enclave identity the sealing key is derived from. Values match
+ * SGX_KEYPOLICY_*. Keys sealed with POD_SEAL_MRSIGNER can also be unsealed by future versions of the
+ * enclave signed with the same key. */
+#define POD_SEAL_MRENCLAVE 0x0001 /*!< Only this exact enclave build can unseal the keys. */
+#define POD_SEAL_MRSIGNER  0x0002 /*!< Any enclave signed with the same key can unseal the keys. */
+
+/* Launch modes of the enclave. Only enclaves signed with a key allowed to launch production
+ * enclaves (or any key, on platforms with Flexible Launch Control) can run in production mode. */
+#define POD_LAUNCH_PRODUCTION 0 /*!< Enclave memory is protected. */
+#define POD_LAUNCH_DEBUG      1 /*!< Enables enclave debugging and NULLIFIES ENCLAVE MEMORY PROTECTION. */
+
+/*! Maximum size of a relying party identifier (in bytes). */
+#define POD_MAX_RELYING_PARTY_SIZE 256
+
+/*! Maximum size of data and its additional authenticated data sealed together (in bytes). */
+#define POD_MAX_SEALED_DATA_SIZE 65536
+
+/* Log levels passed to the log callback. Values match the levels of the Rust `log` crate. */
+#define POD_LOG_ERROR 1 /*!< Operation failed. */
+#define POD_LOG_WARN  2 /*!< Something looks wrong, but the operation proceeds. */
+#define POD_LOG_INFO  3 /*!< Informational message. */
+#define POD_LOG_DEBUG 4 /*!< Debugging details. */
+
+/*! Callback receiving log messages, see pod_set_log_callback(). */
+typedef void (*pod_log_callback_t)(int level, const char* message);
+
+/*! Handle of a loaded PoD enclave. Several enclaves can be loaded at the same time.
+ *  Earlier versions of this library kept a single global enclave instead: all functions operating
+ *  on an enclave, including pod_sign_buffer() and pod_sign_file(), now take its handle as first
+ *  argument, so callers need to be updated and rebuilt. */
+typedef struct pod_enclave pod_enclave_t;
+
+/*!
+ *  \brief Get the `sgx_status_t` of the last failed SGX call made by the calling thread.
+ *
+ *  Useful for finding out why a pod_* function failed. Reset to SGX_SUCCESS on entry to every
+ *  pod_* function.
+ *
+ *  \return Last `sgx_status_t`, or SGX_SUCCESS (0) if there was none.
+ */
+uint32_t pod_last_sgx_status(void);
+
+/*!
+ *  \brief Set the callback receiving log messages of this library and of the enclave.
+ *
+ *  Messages of all levels are passed to the callback, one at a time, NUL-terminated and usually
+ *  ending with a newline. Without a callback (the default), messages are printed to stdout
+ *  (errors to stderr), and debug messages only in verbose mode. This is unsuitable for hosts
+ *  using stdout for other purposes, e.g. for native messaging.
+ *
+ *  \param[in] callback Log callback, or NULL to restore the default.
+ */
+void pod_set_log_callback(pod_log_callback_t callback);
 
 /*!
  *  \brief Get size of an open file.
@@ -42,64 +110,276 @@ int write_file(const char* path, size_t size, const void* buffer);
  *         Loads enclave, generates new enclave key pair, and seals the private key into the provided
  *         buffer.
  *
- *  \param[in] enclave_path       Path to enclave binary.
- *  \param[in] sealed_state       Buffer to seal the private key to.
- *  \param[in] sealed_state_size  Size of the provided buffer.
+ *  \param[in]  enclave_path       Path to enclave binary.
+ *  \param[in]  launch_mode        POD_LAUNCH_PRODUCTION or POD_LAUNCH_DEBUG.
+ *  \param[in]  seal_policy        POD_SEAL_MRENCLAVE or POD_SEAL_MRSIGNER.
+ *  \param[in]  sealed_state       Buffer to seal the private key to.
+ *  \param[in]  sealed_state_size  Size of the provided buffer.
+ *  \param[out] enclave            On success, handle of the loaded enclave. Release it with
+ *                                 pod_unload_enclave().
  *
- *  \return On success, number of bytes written to the buffer. On failure, negative value.
+ *  \return On success, number of bytes written to the buffer. On failure, negated POD_ERR_* code.
  */
-int pod_init_enclave(const char* enclave_path, uint8_t* sealed_state, size_t sealed_state_size);
+int pod_init_enclave(const char* enclave_path, uint16_t launch_mode, uint16_t seal_policy,
+                     uint8_t* sealed_state, size_t sealed_state_size, pod_enclave_t** enclave);
 
 /*!
  *  \brief Load PoD enclave and restore its private key from sealed state.
  *
- *  \param[in] enclave_path      Path to enclave binary.
- *  \param[in] sealed_state_path Path to sealed enclave state.
+ *  \param[in]  enclave_path      Path to enclave binary.
+ *  \param[in]  launch_mode       POD_LAUNCH_PRODUCTION or POD_LAUNCH_DEBUG.
+ *  \param[in]  sealed_state      Buffer with sealed enclave state.
+ *  \param[in]  sealed_state_size Size of \p sealed_state in bytes.
+ *  \param[out] enclave           On success, handle of the loaded enclave. Release it with
+ *                                pod_unload_enclave().
  *
- *  \return 0 on success, negative on error.
+ *  \return 0 on success, negated POD_ERR_* code on error.
  */
-int pod_load_enclave(const char* enclave_path, const uint8_t* sealed_state, size_t sealed_state_size);
+int pod_load_enclave(const char* enclave_path, uint16_t launch_mode, const uint8_t* sealed_state,
+                     size_t sealed_state_size, pod_enclave_t** enclave);
 
 /*!
- *  \brief Unload PoD enclave.
+ *  \brief Seal the loaded PoD enclave's private key again, e.g. under a different policy.
  *
- *  \return 0 on success, negative on error.
+ *  Sealing with POD_SEAL_MRSIGNER before replacing the enclave binary lets the new version
+ *  restore the same private key.
+ *
+ *  \param[in] enclave           Enclave handle.
+ *  \param[in] seal_policy       POD_SEAL_MRENCLAVE or POD_SEAL_MRSIGNER.
+ *  \param[in] sealed_state      Buffer to seal the private key to.
+ *  \param[in] sealed_state_size Size of the provided buffer.
+ *
+ *  \return On success, number of bytes written to the buffer. On failure, negated POD_ERR_* code.
+ */
+int pod_reseal_keys(const pod_enclave_t* enclave, uint16_t seal_policy, uint8_t* sealed_state,
+                    size_t sealed_state_size);
+
+/*!
+ *  \brief Replace the loaded PoD enclave's key pair with a freshly generated one.
+ *
+ *  The new public key is signed with the old private key, proving continuity between the two.
+ *  The signed message is "pod-enclave-key-rotation-v1\0" || old public key || new public key,
+ *  where both keys are those derived for \p relying_party, if given. On failure, the old key pair
+ *  stays in place.
+ *
+ *  \param[in]  enclave            Enclave handle.
+ *  \param[in]  seal_policy        POD_SEAL_MRENCLAVE or POD_SEAL_MRSIGNER.
+ *  \param[in]  relying_party      Optional identifier of the relying party (e.g. origin or SPID)
+ *                                 whose derived keys to sign with. If NULL, the master keys are used.
+ *  \param[in]  relying_party_size Size of \p relying_party in bytes.
+ *  \param[out] sealed_state       Buffer to seal the new private key to.
+ *  \param[in]  sealed_state_size  Size of the provided buffer.
+ *  \param[out] signature          Buffer that will receive the signature of the new public key.
+ *  \param[in]  signature_size     Size of \p signature in bytes.
+ *
+ *  \return On success, number of bytes written to \p sealed_state. On failure, negated POD_ERR_*
+ *          code.
+ */
+int pod_rotate_key(pod_enclave_t* enclave, uint16_t seal_policy, const uint8_t* relying_party,
+                   size_t relying_party_size, uint8_t* sealed_state, size_t sealed_state_size,
+                   void* signature, size_t signature_size);
+
+/*!
+ *  \brief Get the size of \p data_size bytes of data sealed with \p aad_size bytes of additional
+ *         authenticated data by pod_seal_data().
+ *
+ *  \param[in] aad_size  Size of the additional authenticated data in bytes.
+ *  \param[in] data_size Size of the data in bytes.
+ *
+ *  \return Size of the sealed data in bytes.
+ */
+size_t pod_sealed_data_size(size_t aad_size, size_t data_size);
+
+/*!
+ *  \brief Seal application data (e.g. tokens or vault keys) with the loaded PoD enclave.
+ *
+ *  The data is encrypted, and together with \p aad authenticated, with the enclave's sealing key,
+ *  so that only the same enclave (or, with POD_SEAL_MRSIGNER, one signed with the same key) on
+ *  the same machine can unseal it. Such data can never be loaded as the enclave's private key,
+ *  nor can sealed keys be unsealed with pod_unseal_data().
+ *
+ *  \param[in]  enclave          Enclave handle.
+ *  \param[in]  seal_policy      POD_SEAL_MRENCLAVE or POD_SEAL_MRSIGNER.
+ *  \param[in]  aad              Optional additional authenticated data, stored in plain text. May be
+ *                               NULL if \p aad_size is 0.
+ *  \param[in]  aad_size         Size of \p aad in bytes.
+ *  \param[in]  data             Data to seal.
+ *  \param[in]  data_size        Size of \p data in bytes. Together with \p aad_size at most
+ *                               POD_MAX_SEALED_DATA_SIZE, and not 0.
+ *  \param[out] sealed_data      Buffer that will receive the sealed data.
+ *  \param[in]  sealed_data_size Size of \p sealed_data in bytes, see pod_sealed_data_size().
+ *
+ *  \return On success, number of bytes written to \p sealed_data. On failure, negated POD_ERR_*
+ *          code.
  */
-int pod_unload_enclave(void);
+int pod_seal_data(const pod_enclave_t* enclave, uint16_t seal_policy, const void* aad,
+                  size_t aad_size, const void* data, size_t data_size, uint8_t* sealed_data,
+                  size_t sealed_data_size);
+
+/*!
+ *  \brief Get the sizes of the additional authenticated data and the data sealed in
+ *         \p sealed_data, as needed by pod_unseal_data(). The sizes are not authenticated until
+ *         unsealing succeeds.
+ *
+ *  \param[in]  sealed_data      Data sealed with pod_seal_data().
+ *  \param[in]  sealed_data_size Size of \p sealed_data in bytes.
+ *  \param[out] aad_size         Size of the additional authenticated data in bytes.
+ *  \param[out] data_size        Size of the data in bytes.
+ *
+ *  \return 0 on success, negated POD_ERR_* code on error.
+ */
+int pod_get_sealed_data_sizes(const uint8_t* sealed_data, size_t sealed_data_size,
+                              size_t* aad_size, size_t* data_size);
+
+/*!
+ *  \brief Unseal application data sealed with pod_seal_data().
+ *
+ *  \param[in]  enclave          Enclave handle.
+ *  \param[in]  sealed_data      Data sealed with pod_seal_data().
+ *  \param[in]  sealed_data_size Size of \p sealed_data in bytes.
+ *  \param[out] aad              Buffer that will receive the additional authenticated data.
+ *  \param[in]  aad_size         Size of \p aad in bytes, see pod_get_sealed_data_sizes().
+ *  \param[out] data             Buffer that will receive the data.
+ *  \param[in]  data_size        Size of \p data in bytes, see pod_get_sealed_data_sizes().
+ *
+ *  \return 0 on success, negated POD_ERR_* code on error. POD_ERR_SEALED_STATE means the data is
+ *          corrupt, or was sealed by another enclave or on another machine.
+ */
+int pod_unseal_data(const pod_enclave_t* enclave, const uint8_t* sealed_data,
+                    size_t sealed_data_size, void* aad, size_t aad_size, void* data,
+                    size_t data_size);
+
+/*!
+ *  \brief Unload PoD enclave and free its handle.
+ *
+ *  \param[in] enclave Enclave handle. NULL is a no-op.
+ *
+ *  \return 0 on success, negated POD_ERR_* code on error.
+ */
+int pod_unload_enclave(pod_enclave_t* enclave);
+
+/*!
+ *  \brief Get public key of the loaded PoD enclave.
+ *
+ *  \param[in]  enclave            Enclave handle.
+ *  \param[in]  relying_party      Optional identifier of the relying party (e.g. origin or SPID)
+ *                                 whose derived key to use. If NULL, the master key is used.
+ *  \param[in]  relying_party_size Size of \p relying_party in bytes.
+ *  \param[out] pubkey             Buffer that will receive the public key.
+ *  \param[in]  pubkey_size        Size of \p pubkey in bytes.
+ *
+ *  \return 0 on success, negated POD_ERR_* code on error.
+ */
+int pod_get_public_key(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                       size_t relying_party_size, uint8_t* pubkey, size_t pubkey_size);
+
+/*!
+ *  \brief Get the body of the loaded PoD enclave's own report (`sgx_report_body_t`).
+ *         Contains the enclave's identity, e.g. MRENCLAVE and MRSIGNER.
+ *
+ *  \param[in]  enclave          Enclave handle.
+ *  \param[out] report_body      Buffer that will receive the report body.
+ *  \param[in]  report_body_size Size of \p report_body in bytes.
+ *
+ *  \return On success, number of bytes written to the buffer. On failure, negated POD_ERR_* code.
+ */
+int pod_get_report_body(const pod_enclave_t* enclave, uint8_t* report_body,
+                        size_t report_body_size);
 
 /*!
  *  \brief Generate valid quote of this PoD enclave for remote attestation with IAS services.
+ *         The report data of the quote is public key || SHA-256(public key || user data),
+ *         where the public key is the one of the chosen key.
  *
+ *  \param[in] enclave            Enclave handle.
+ *  \param[in] relying_party      Optional identifier of the relying party (e.g. origin or SPID)
+ *                                whose derived key to use. If NULL, the master key is used.
+ *  \param[in] relying_party_size Size of \p relying_party in bytes.
+ *  \param[in] user_data          Optional data to bind the quote to, e.g. a nonce issued by the
+ *                                verifier. May be NULL if \p user_data_size is 0.
+ *  \param[in] user_data_size     Size of \p user_data in bytes.
  *  \param[in] sp_id_str          Service Provider ID (hex string).
  *  \param[in] sp_quote_type_str  Quote type as string ("linkable"/"unlinkable").
+ *  \param[in] nonce              Optional 16-byte nonce for the QE report. If NULL, a random
+ *                                one is used.
  *  \param[in] quote_buffer       Buffer to save the quote to.
  *  \param[in] quote_buffer_size  Size of the provided buffer.
  *
- *  \return On success, number of bytes written to the buffer. On failure, negative value.
+ *  \return On success, number of bytes written to the buffer. On failure, negated POD_ERR_* code.
  */
-int pod_get_quote(const char* sp_id_str, const char* sp_quote_type_str, uint8_t* quote_buffer,
+int pod_get_quote(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                  size_t relying_party_size, const void* user_data, size_t user_data_size,
+                  const char* sp_id_str,
+                  const char* sp_quote_type_str, const uint8_t* nonce, uint8_t* quote_buffer,
                   size_t quote_buffer_size);
 
 /*!
  *  \brief Create PoD enclave digital signature for data buffer.
  *
- *  \param[in]  data           Buffer with data to sign.
- *  \param[in]  data_size      Size of \p data in bytes.
- *  \param[out] signature      Buffer that will receive the signature.
- *  \param[in]  signature_size Size of \p signature in bytes.
+ *  \param[in]  enclave            Enclave handle.
+ *  \param[in]  relying_party      Optional identifier of the relying party (e.g. origin or SPID)
+ *                                 whose derived key to use. If NULL, the master key is used.
+ *  \param[in]  relying_party_size Size of \p relying_party in bytes.
+ *  \param[in]  data               Buffer with data to sign.
+ *  \param[in]  data_size          Size of \p data in bytes.
+ *  \param[out] signature          Buffer that will receive the signature.
+ *  \param[in]  signature_size     Size of \p signature in bytes.
  *
- *  \return 0 on success, negative on error.
+ *  \return 0 on success, negated POD_ERR_* code on error.
+ */
+int pod_sign_buffer(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                    size_t relying_party_size, const void* data, size_t data_size,
+                    void* signature, size_t signature_size);
+
+/*!
+ *  \brief Create PoD enclave Ed25519ph (RFC 8032, empty context) signature for a SHA-512 digest.
+ *
+ *  Unlike pod_sign_buffer(), only the digest is passed into the enclave, so the signed message
+ *  can be of any size.
+ *
+ *  \param[in]  enclave            Enclave handle.
+ *  \param[in]  relying_party      Optional identifier of the relying party (e.g. origin or SPID)
+ *                                 whose derived key to use. If NULL, the master key is used.
+ *  \param[in]  relying_party_size Size of \p relying_party in bytes.
+ *  \param[in]  digest             SHA-512 digest of the message to sign.
+ *  \param[in]  digest_size        Size of \p digest in bytes (64).
+ *  \param[out] signature          Buffer that will receive the signature.
+ *  \param[in]  signature_size     Size of \p signature in bytes.
+ *
+ *  \return 0 on success, negated POD_ERR_* code on error.
  */
-int pod_sign_buffer(const void* data, size_t data_size, void* signature, size_t signature_size);
+int pod_sign_prehashed(const pod_enclave_t* enclave, const uint8_t* relying_party,
+                       size_t relying_party_size, const uint8_t* digest, size_t digest_size,
+                       void* signature, size_t signature_size);
 
 /*!
  *  \brief Create PoD enclave digital signature for a file.
  *
+ *  The signature is a plain Ed25519 signature of the whole file, which is read into memory
+ *  first. Use pod_sign_file_prehashed() for large files.
+ *
+ *  \param[in] enclave        Enclave handle.
+ *  \param[in] input_path     Path to file to sign.
+ *  \param[in] signature_path Path where the signature will be saved.
+ *
+ *  \return 0 on success, negative on error.
+ */
+int pod_sign_file(const pod_enclave_t* enclave, const char* input_path, const char* signature_path);
+
+/*!
+ *  \brief Create PoD enclave Ed25519ph signature for a file.
+ *
+ *  The file is hashed with SHA-512 in chunks and signed with Ed25519ph, see
+ *  pod_sign_prehashed(), so it never has to fit in memory. The signature differs from the one
+ *  created by pod_sign_file(), and has to be verified as an Ed25519ph one (with empty context).
+ *
+ *  \param[in] enclave        Enclave handle.
  *  \param[in] input_path     Path to file to sign.
  *  \param[in] signature_path Path where the signature will be saved.
  *
  *  \return 0 on success, negative on error.
  */
-int pod_sign_file(const char* input_path, const char* signature_path);
+int pod_sign_file_prehashed(const pod_enclave_t* enclave, const char* input_path,
+                            const char* signature_path);
 
 #endif /* POD_SGX_H */
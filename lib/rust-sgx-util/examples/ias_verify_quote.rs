@@ -1,5 +1,6 @@
 use anyhow::Result;
 use rust_sgx_util::{set_verbose, IasHandle, Nonce, Quote};
+use std::convert::TryFrom;
 use std::fs;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -24,10 +25,16 @@ fn main() -> Result<()> {
     set_verbose(opt.verbose);
     let ias_handle = IasHandle::new(&opt.api_key, None, None)?;
     let quote = Quote::from(fs::read(&opt.quote_path)?);
-    let nonce = opt.nonce.as_ref().map(|x| Nonce::from(x.as_bytes()));
-    match ias_handle.verify_quote(&quote, nonce.as_ref(), None, None, None, None) {
-        Err(_) => println!("Verification of quote unsuccessful!"),
-        Ok(()) => {}
-    };
+    let nonce = opt
+        .nonce
+        .as_ref()
+        .map(|x| Nonce::try_from(x.as_bytes()))
+        .transpose()?;
+    if ias_handle
+        .verify_quote(&quote, nonce.as_ref(), None, None, None, None)
+        .is_err()
+    {
+        println!("Verification of quote unsuccessful!");
+    }
     Ok(())
 }
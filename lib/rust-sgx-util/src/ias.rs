@@ -6,7 +6,7 @@ use std::ops::Deref;
 use std::path::Path;
 use std::ptr::{self, NonNull};
 use std::str::FromStr;
-use std::{fmt, slice, u32};
+use std::{fmt, slice};
 
 const IAS_VERIFY_URL: &str = "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report";
 const IAS_SIGRL_URL: &str = "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/sigrl";
@@ -152,12 +152,10 @@ impl IasHandle {
         advisory_path: Option<&Path>,
     ) -> Result<()> {
         let nonce = nonce.map(|nonce| CString::new(nonce.deref())).transpose()?;
-        let report_path = report_path.map(|path| path_to_c_string(path)).transpose()?;
-        let sig_path = sig_path.map(|path| path_to_c_string(path)).transpose()?;
-        let cert_path = cert_path.map(|path| path_to_c_string(path)).transpose()?;
-        let advisory_path = advisory_path
-            .map(|path| path_to_c_string(path))
-            .transpose()?;
+        let report_path = report_path.map(path_to_c_string).transpose()?;
+        let sig_path = sig_path.map(path_to_c_string).transpose()?;
+        let cert_path = cert_path.map(path_to_c_string).transpose()?;
+        let advisory_path = advisory_path.map(path_to_c_string).transpose()?;
         let ret = unsafe {
             c::ias_verify_quote(
                 self.context.as_ptr(),
@@ -17,15 +17,29 @@
 //! Currently, this crate requires you compile and install `sgx_util` as
 //! a shared library.
 //!
+//! If you only need the [`Quote`] and [`Nonce`] types (e.g., to pass quotes
+//! around without talking to the IAS), you can disable the default `ias`
+//! feature, in which case the crate doesn't link against `sgx_util` at all:
+//!
+//! ```toml
+//! rust-sgx-util = { version = "0.2", default-features = false }
+//! ```
+//!
+//! [`Quote`]: struct.Quote.html
+//! [`Nonce`]: struct.Nonce.html
+//!
 //! ## Usage examples
 //!
 //! You can find usage examples in the `examples` dir of the crate.
 //!
+#[cfg(feature = "ias")]
 mod c;
+#[cfg(feature = "ias")]
 mod ias;
 #[cfg(feature = "with_serde")]
 mod ser_de;
 
+#[cfg(feature = "ias")]
 pub use ias::*;
 
 #[cfg(feature = "with_serde")]
@@ -66,6 +80,7 @@ pub enum Error {
 }
 
 /// Set verbosity on/off.
+#[cfg(feature = "ias")]
 pub fn set_verbose(verbose: bool) {
     unsafe { c::set_verbose(verbose) }
 }
@@ -87,9 +102,40 @@ pub fn set_verbose(verbose: bool) {
 pub struct Quote(#[cfg_attr(feature = "with_serde", serde(with = "ser_de"))] Vec<u8>);
 
 impl Quote {
+    const REPORT_BODY_BEGIN: usize = 48;
     const REPORT_BEGIN: usize = 368;
     const REPORT_END: usize = 432;
 
+    /// Returns the enclave's report body (`sgx_report_body_t`) embedded
+    /// within this `Quote`, e.g., to inspect the enclave's attributes.
+    ///
+    /// The size of the returned slice is 384 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Like [`report_data`], this function will fail with
+    /// [`Error::QuoteTooShort`] if `Quote` is shorter than `432` bytes, and
+    /// doesn't do any other validations.
+    ///
+    /// [`report_data`]: struct.Quote.html#method.report_data
+    /// [`Error::QuoteTooShort`]: enum.Error.html#variant.QuoteTooShort
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_sgx_util::Quote;
+    /// let quote = Quote::from(&[0u8; 438][..]);
+    /// assert_eq!(quote.report_body().unwrap().len(), 384);
+    ///
+    /// let quote = Quote::from(&[0u8; 10][..]);
+    /// assert!(quote.report_body().is_err());
+    /// ```
+    pub fn report_body(&self) -> Result<&[u8]> {
+        self.0
+            .get(Self::REPORT_BODY_BEGIN..Self::REPORT_END)
+            .ok_or(Error::QuoteTooShort)
+    }
+
     /// Returns `report_data` bytes embedded within this `Quote`.
     ///
     /// The size of the returned slice is 64 bytes.
@@ -1,13 +1,21 @@
 use actix_web::client::Client;
 use actix_web::HttpMessage;
 use anyhow::anyhow;
-use pod_api::{PodEnclave, QuoteType};
+use pod_api::{
+    AsyncPodEnclave, PodEnclave, QuoteType, RelyingParty, SigningPayload, SigningPurpose,
+    SoftwareBackend, Spid,
+};
 use rust_sgx_util::Quote;
 use serde::Serialize;
 use structopt::StructOpt;
 
+#[cfg(feature = "sgx")]
 const SEALED_KEYS_PATH: &str = "pod_data.sealed";
+#[cfg(feature = "sgx")]
 const ENCLAVE_PATH: &str = "../pod-enclave/pod_enclave.signed.so";
+const SOFTWARE_SEALED_KEYS_PATH: &str = "pod_data.software.sealed";
+const SOFTWARE_SECRET_PATH: &str = "pod_data.software.secret";
+const DEFAULT_ORIGIN: &str = "http://localhost:3000";
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -17,6 +25,9 @@ struct Opt {
     /// Server port to connect to (defaults to 8080).
     #[structopt(long)]
     port: Option<u16>,
+    /// Use the software backend instead of SGX (for testing only).
+    #[structopt(long)]
+    software: bool,
     #[structopt(subcommand)]
     cmd: Command,
 }
@@ -28,12 +39,16 @@ enum Command {
         /// Your username.
         login: String,
         /// Service Provider's ID (SPID) as given by the SP.
-        spid: String,
+        spid: Spid,
     },
     /// Authenticate with the service.
     Authenticate {
         /// Your username.
         login: String,
+        /// Origin the server expects challenges to be signed for (defaults to
+        /// http://localhost:3000).
+        #[structopt(long)]
+        origin: Option<RelyingParty>,
     },
 }
 
@@ -47,29 +62,74 @@ struct RegisterInfo {
 struct ChallengeResponse {
     login: String,
     response: String,
+    timestamp: u64,
+}
+
+#[cfg(feature = "sgx")]
+fn sgx_enclave() -> anyhow::Result<PodEnclave> {
+    Ok(PodEnclave::new(ENCLAVE_PATH, SEALED_KEYS_PATH)?)
+}
+
+#[cfg(not(feature = "sgx"))]
+fn sgx_enclave() -> anyhow::Result<PodEnclave> {
+    Err(anyhow!("built without SGX support, use --software instead"))
 }
 
 #[actix_rt::main]
 async fn main() -> anyhow::Result<()> {
     let opt = Opt::from_args();
     // Turn on enclave logging
-    pod_api::set_verbose(true);
+    simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default())?;
 
     let address = opt.address.unwrap_or_else(|| "127.0.0.1".to_owned());
     let port = opt.port.unwrap_or(8080);
     let base_uri = format!("http://{}:{}", address, port);
     let client = Client::default();
-    let pod_enclave = PodEnclave::new(ENCLAVE_PATH, SEALED_KEYS_PATH)?;
+    let pod_enclave = if opt.software {
+        let backend = SoftwareBackend::from_secret_file(SOFTWARE_SECRET_PATH)?;
+        PodEnclave::with_backend(backend, SOFTWARE_SEALED_KEYS_PATH)?
+    } else {
+        sgx_enclave()?
+    };
+    // Keep the blocking enclave calls off the actix executor.
+    let pod_enclave = AsyncPodEnclave::new(pod_enclave)?;
 
     match opt.cmd {
         Command::Register { login, spid } => {
-            // Get the quote
-            let quote = pod_enclave.get_quote(&spid, QuoteType::Unlinkable)?;
-
-            println!("POST /register");
+            println!("GET /register");
             let mut response = client
-                .post(format!("{}/register", base_uri))
+                .get(format!("{}/register", base_uri))
                 .header("User-Agent", "TestClient")
+                .send()
+                .await
+                .map_err(|err| anyhow!("{:?}", err))?;
+            let status_code = response.status();
+            println!("    | status_code: {}", status_code);
+            let body = response.body().await.map_err(|err| anyhow!("{:?}", err))?;
+            let cookies = response.cookies()?.clone();
+            if status_code != 200 {
+                return Err(anyhow!("Expected GET /register to return 200"));
+            }
+            let json: serde_json::Value = serde_json::from_slice(&body)?;
+            println!("    | body: {}", json);
+
+            // Get the quote bound to the nonce
+            let nonce = json["nonce"]
+                .as_str()
+                .ok_or(anyhow!("invalid String for nonce"))?;
+            let nonce = base64::decode(nonce)?;
+            let quote = pod_enclave
+                .get_quote(spid, QuoteType::Unlinkable, nonce)
+                .await?;
+
+            println!("\nPOST /register");
+            let mut builder = client
+                .post(format!("{}/register", base_uri))
+                .header("User-Agent", "TestClient");
+            for cookie in cookies {
+                builder = builder.cookie(cookie);
+            }
+            let mut response = builder
                 .send_json(&RegisterInfo {
                     login: login.clone(),
                     quote,
@@ -86,7 +146,7 @@ async fn main() -> anyhow::Result<()> {
                 println!("    | body: {}", json);
             }
         }
-        Command::Authenticate { login } => {
+        Command::Authenticate { login, origin } => {
             println!("GET /auth");
             let mut response = client
                 .get(format!("{}/auth", base_uri))
@@ -114,8 +174,14 @@ async fn main() -> anyhow::Result<()> {
                 .as_str()
                 .ok_or(anyhow!("invalid String for challenge"))?;
             let challenge = base64::decode(challenge)?;
-            let response = pod_enclave.sign(&challenge)?;
-            let response = base64::encode(&response[..]);
+            let origin = match origin {
+                Some(origin) => origin,
+                None => DEFAULT_ORIGIN.parse()?,
+            };
+            let payload = SigningPayload::now(origin, SigningPurpose::Authentication, challenge);
+            let response = pod_enclave.sign_payload(payload.clone()).await?;
+            let response = response.to_base64();
+            let timestamp = payload.timestamp();
 
             println!("\nPOST /auth");
             let mut builder = client
@@ -125,7 +191,11 @@ async fn main() -> anyhow::Result<()> {
                 builder = builder.cookie(cookie);
             }
             let mut response = builder
-                .send_json(&ChallengeResponse { login, response })
+                .send_json(&ChallengeResponse {
+                    login,
+                    response,
+                    timestamp,
+                })
                 .await
                 .map_err(|err| anyhow!("{:?}", err))?;
             println!("    | status_code: {}", response.status());
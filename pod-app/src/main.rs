@@ -1,18 +1,28 @@
 mod messages;
+mod session;
+#[cfg(test)]
+mod testing;
 
 use anyhow::{anyhow, Context, Result};
 use messages::reply;
 use nix::sys::signal::{self, SigHandler, Signal};
-use serde::Deserialize;
+use pod_api::{LaunchMode, Measurement, SealPolicy};
+use serde::{Deserialize, Serialize};
+use session::Session;
 use std::convert::{TryFrom, TryInto};
+use std::fs::OpenOptions;
 use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs, process};
 
 const DEFAULT_PRIVATE_KEY_PATH: &str = "private_key.sealed";
 const DEFAULT_ENCLAVE_PATH: &str = "../pod-enclave/pod_enclave.signed.so";
+const DEFAULT_IDLE_TIMEOUT: u64 = 300;
+const LOG_FILE_MODE: u32 = 0o600;
 
 static SIGNALED: AtomicBool = AtomicBool::new(false);
 
@@ -24,22 +34,59 @@ extern "C" fn handle_signals(signal: libc::c_int) {
     );
 }
 
+/// Backend holding the private key. See `pod_api::EnclaveBackend`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Backend {
+    #[cfg_attr(feature = "sgx", default)]
+    Sgx,
+    #[cfg_attr(not(feature = "sgx"), default)]
+    Software,
+    Tpm,
+}
+
+/// Where the sealed private key is kept. See `pod_api::SealedStore`.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SealedStore {
+    #[default]
+    File,
+    Keyring,
+    SecretService,
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 struct Config {
     private_key: PathBuf,
+    sealed_store: SealedStore,
     enclave: PathBuf,
     log_output: PathBuf,
+    backend: Backend,
+    launch_mode: LaunchMode,
+    seal_policy: SealPolicy,
+    expected_mr_signer: Option<Measurement>,
+    expected_mr_enclave: Option<Measurement>,
+    tpm_tcti: Option<String>,
+    allow_raw_signing: bool,
+    idle_timeout: u64,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let enclave = PathBuf::from(DEFAULT_ENCLAVE_PATH);
-        let private_key = PathBuf::from(DEFAULT_PRIVATE_KEY_PATH);
-        let log_output = PathBuf::from(log_filename());
         Self {
-            enclave,
-            private_key,
-            log_output,
+            private_key: PathBuf::from(DEFAULT_PRIVATE_KEY_PATH),
+            sealed_store: SealedStore::default(),
+            enclave: PathBuf::from(DEFAULT_ENCLAVE_PATH),
+            log_output: PathBuf::from(log_filename()),
+            backend: Backend::default(),
+            launch_mode: LaunchMode::default(),
+            seal_policy: SealPolicy::default(),
+            expected_mr_signer: None,
+            expected_mr_enclave: None,
+            tpm_tcti: None,
+            allow_raw_signing: false,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
         }
     }
 }
@@ -88,11 +135,14 @@ fn run(config: Config) -> Result<()> {
     unsafe { signal::signal(Signal::SIGTERM, handler)? };
     unsafe { signal::signal(Signal::SIGINT, handler)? };
     log::debug!("Registered SIGTERM and SIGINT signal handlers");
-    // Ok, first pass will load and unload enclave at each message received
-    // event. However, this is definitely not optimal and we should spawn
-    // it only once and hand out Arc<> to the spawned instance instead.
-    // This will require some Mutexing though since PodEnclave is not safe to send
-    // across thread boundaries.
+    // The enclave stays loaded across messages, and is unloaded when idle
+    // (a timeout of 0 keeps it loaded) or upon a termination signal.
+    let session = Arc::new(Mutex::new(Session::new()));
+    let idle_timeout = match config.idle_timeout {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    session::spawn_reaper(session.clone(), idle_timeout);
     loop {
         if SIGNALED.load(Ordering::Relaxed) {
             log::debug!("Received termination signal, exiting...");
@@ -130,7 +180,10 @@ fn run(config: Config) -> Result<()> {
 
         log::debug!("Message received");
 
-        let mut reply = reply(msg, &config)?;
+        // Hold the session until the whole reply is written, so that the
+        // reaper can't exit midway through it.
+        let mut locked_session = session::lock(&session)?;
+        let mut reply = reply(msg, &config, &mut locked_session)?;
         let reply_len: u32 = reply
             .len()
             .try_into()
@@ -149,32 +202,53 @@ fn run(config: Config) -> Result<()> {
             Ok(Interrupt::Continue) => {}
         };
         io::stdout().flush()?;
+        drop(locked_session);
 
         log::debug!("Reply sent");
     }
 
+    session::lock(&session)?.close();
     Ok(())
 }
 
-fn config() -> Config {
-    // Firstly, check in xdg config folder.
+fn config() -> Result<Config> {
+    // Any value missing from the (optional) config file in xdg config
+    // folder falls back to its default.
     let dirs = xdg::BaseDirectories::with_prefix("pod-app")
-        .expect("couldn't create xdg base dir instance");
-
-    match dirs.find_data_file("pod_enclave.signed.so") {
-        Some(enclave) => {
-            let data_home = dirs.get_data_home();
-            let private_key = data_home.join("private_key.sealed");
-            let log_output = data_home.join(log_filename());
-
-            Config {
-                private_key,
-                enclave,
-                log_output,
-            }
+        .context("couldn't create xdg base dir instance")?;
+    let (path, mut table) = match dirs.find_config_file("config.toml") {
+        Some(path) => {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("couldn't read config file {}", path.display()))?;
+            let table = toml::from_str(&contents)
+                .with_context(|| format!("couldn't parse config file {}", path.display()))?;
+            (Some(path), table)
+        }
+        None => (None, toml::value::Table::new()),
+    };
+
+    // If the enclave is installed in xdg data folder, keep the private key
+    // and logs there too.
+    if let Some(enclave) = dirs.find_data_file("pod_enclave.signed.so") {
+        let data_home = dirs.get_data_home();
+        let paths = vec![
+            ("enclave", enclave),
+            ("private_key", data_home.join(DEFAULT_PRIVATE_KEY_PATH)),
+            ("log_output", data_home.join(log_filename())),
+        ];
+        for (key, path) in paths {
+            table
+                .entry(key)
+                .or_insert_with(|| path.to_string_lossy().into_owned().into());
         }
-        None => Config::default(),
     }
+
+    toml::Value::Table(table)
+        .try_into()
+        .with_context(|| match path {
+            Some(path) => format!("couldn't parse config file {}", path.display()),
+            None => "couldn't build default config".to_owned(),
+        })
 }
 
 fn log_filename() -> String {
@@ -186,9 +260,23 @@ fn log_filename() -> String {
 }
 
 fn main() {
-    let config = config();
-    let log_output =
-        fs::File::create(&config.log_output).expect("could initialize file for logger output");
+    // The logger needs the config, so report config errors on stderr, which
+    // the browser logs for native messaging hosts.
+    let config = match config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("pod-app: {:#}", err);
+            process::exit(1);
+        }
+    };
+    // Requests are logged, so keep the log to ourselves.
+    let log_output = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(LOG_FILE_MODE)
+        .open(&config.log_output)
+        .expect("could initialize file for logger output");
 
     simplelog::WriteLogger::init(
         log::LevelFilter::Debug,
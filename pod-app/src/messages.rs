@@ -1,55 +1,359 @@
-use super::Config;
+use super::session::Session;
+use super::{Backend, Config, SealedStore};
 use anyhow::Result;
-use pod_api::{PodEnclave, QuoteType};
+#[cfg(feature = "tpm")]
+use pod_api::TpmBackend;
+use pod_api::{
+    Ed25519PublicKey, Ed25519Signature, EnclaveReport, FileStore, KeyRotation, KeyringStore,
+    PodEnclave, PodError, QuoteType, RelyingParty, SecretServiceStore, SigningPayload,
+    SigningPurpose, SoftwareBackend, Spid,
+};
+#[cfg(feature = "sgx")]
+use pod_api::{LaunchMode, SgxBackend};
 use rust_sgx_util::Quote;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "msg", rename_all = "snake_case")]
 enum IncomingMessage {
+    // Sent first, so that both sides know what the other supports.
+    Hello {
+        protocol_version: u32,
+    },
+    // Messages using the key take an optional relying party, e.g., the
+    // origin of the service, to use the key derived for it.
+    GetPublicKey {
+        #[serde(default)]
+        relying_party: Option<RelyingParty>,
+    },
+    // Quotes are registered with a service, so they always bind the key
+    // derived for it rather than the master key.
     GetQuote {
-        spid: String,
+        spid: Spid,
+        relying_party: RelyingParty,
+        #[serde(default, with = "base_64")]
+        user_data: Vec<u8>,
     },
+    // The challenge is signed as part of a `SigningPayload` bound to the
+    // origin of the service which issued it, with the key derived for that
+    // origin. `relying_party`, if given, has to be the origin too.
     SignChallenge {
         #[serde(with = "base_64")]
         challenge: Vec<u8>,
+        origin: RelyingParty,
+        #[serde(default)]
+        purpose: Option<SigningPurpose>,
+        #[serde(default)]
+        relying_party: Option<RelyingParty>,
+    },
+    // Only honoured if `allow_raw_signing` is set in the config.
+    SignRaw {
+        #[serde(with = "base_64")]
+        data: Vec<u8>,
+        #[serde(default)]
+        relying_party: Option<RelyingParty>,
+    },
+    // Replaces the master key, but proves the rotation with the keys derived
+    // for `relying_party`, the ones that service has registered.
+    RotateKey {
+        spid: Spid,
+        relying_party: RelyingParty,
+        #[serde(default, with = "base_64")]
+        user_data: Vec<u8>,
+    },
+    GetIdentity,
+    // The origin asking is bound into the sealed data, so that data sealed
+    // for one origin can't be unsealed by another.
+    Seal {
+        #[serde(with = "base_64")]
+        data: Vec<u8>,
+        origin: RelyingParty,
+        #[serde(default, with = "base_64")]
+        aad: Vec<u8>,
+    },
+    Unseal {
+        #[serde(with = "base_64")]
+        sealed: Vec<u8>,
+        origin: RelyingParty,
     },
+    // Any other `msg`, replied to with `ErrorCode::UnknownMessage`.
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(tag = "msg", rename_all = "snake_case")]
 #[allow(dead_code)]
 pub(super) enum OutgoingMessage {
+    Hello {
+        protocol_version: u32,
+        app_version: &'static str,
+        backend: &'static str,
+        identity: Option<Identity>,
+        messages: Vec<&'static str>,
+        algorithms: &'static [&'static str],
+    },
+
+    GetPublicKey {
+        pub_key: Ed25519PublicKey,
+        fingerprint: String,
+    },
+
     GetQuote {
         quote: Quote,
     },
 
     SignChallenge {
+        signed: Ed25519Signature,
+        timestamp: u64,
+    },
+
+    SignRaw {
+        signed: Ed25519Signature,
+    },
+
+    RotateKey {
+        rotation: KeyRotation,
+        quote: Quote,
+    },
+
+    GetIdentity {
+        backend: Backend,
+        #[serde(flatten)]
+        identity: Identity,
+    },
+
+    Seal {
+        #[serde(with = "base_64")]
+        sealed: Vec<u8>,
+    },
+
+    Unseal {
+        #[serde(with = "base_64")]
+        data: Vec<u8>,
         #[serde(with = "base_64")]
-        signed: Vec<u8>,
+        aad: Vec<u8>,
+    },
+
+    Error {
+        code: ErrorCode,
+        description: String,
+        request: Option<String>,
     },
 }
 
 impl OutgoingMessage {
+    pub fn get_public_key(pub_key: Ed25519PublicKey) -> Self {
+        Self::GetPublicKey {
+            pub_key,
+            fingerprint: pub_key.fingerprint().to_string(),
+        }
+    }
+
     pub fn get_quote(quote: Quote) -> Self {
         Self::GetQuote { quote }
     }
 
-    pub fn sign_challenge<B: AsRef<[u8]>>(signed: B) -> Self {
-        Self::SignChallenge {
-            signed: signed.as_ref().to_vec(),
+    pub fn sign_challenge(signed: Ed25519Signature, timestamp: u64) -> Self {
+        Self::SignChallenge { signed, timestamp }
+    }
+
+    pub fn sign_raw(signed: Ed25519Signature) -> Self {
+        Self::SignRaw { signed }
+    }
+
+    pub fn rotate_key(rotation: KeyRotation, quote: Quote) -> Self {
+        Self::RotateKey { rotation, quote }
+    }
+
+    pub fn hello(config: &Config, identity: Option<Identity>) -> Self {
+        let messages = MESSAGES
+            .iter()
+            .copied()
+            .filter(|&msg| msg != "sign_raw" || config.allow_raw_signing)
+            .collect();
+        Self::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            app_version: env!("CARGO_PKG_VERSION"),
+            backend: backend_name(config.backend),
+            identity,
+            messages,
+            algorithms: ALGORITHMS,
+        }
+    }
+
+    pub fn get_identity(backend: Backend, report: EnclaveReport) -> Self {
+        Self::GetIdentity {
+            backend,
+            identity: Identity::new(report),
+        }
+    }
+
+    pub fn seal(sealed: Vec<u8>) -> Self {
+        Self::Seal { sealed }
+    }
+
+    pub fn unseal(data: Vec<u8>, aad: Vec<u8>) -> Self {
+        Self::Unseal { data, aad }
+    }
+
+    pub fn error(code: ErrorCode, description: String, request: Option<String>) -> Self {
+        Self::Error {
+            code,
+            description,
+            request,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown message")]
+struct UnknownMessage;
+
+#[derive(Debug, thiserror::Error)]
+#[error("unsupported protocol version {}, expected {}", _0, PROTOCOL_VERSION)]
+struct UnsupportedProtocolVersion(u32);
+
+/// Stable code of an `error` reply, for the extension to act on without
+/// parsing the description. Codes are never renamed or reused; new ones may
+/// be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ErrorCode {
+    /// Request is not valid JSON, lacks `msg`, or has missing or malformed
+    /// fields.
+    InvalidRequest,
+    /// `msg` is not one `pod-app` knows.
+    UnknownMessage,
+    /// `protocol_version` sent with `hello` is not supported.
+    UnsupportedProtocolVersion,
+    /// Enclave (or TPM) couldn't be loaded or reached.
+    EnclaveUnavailable,
+    /// Enclave binary isn't the expected one.
+    UntrustedEnclave,
+    /// Sealed private key couldn't be read, unsealed or stored.
+    KeyUnavailable,
+    /// Quote couldn't be generated, e.g., the AESM service is down.
+    QuoteUnavailable,
+    /// `sign_raw` was sent, but `allow_raw_signing` is disabled.
+    RawSigningDisabled,
+    /// Blob passed to `unseal` is corrupt, or was sealed elsewhere.
+    SealedDataInvalid,
+    /// Any other failure.
+    Internal,
+}
+
+impl ErrorCode {
+    fn of(err: &anyhow::Error) -> Self {
+        if err.is::<serde_json::Error>() {
+            return ErrorCode::InvalidRequest;
+        }
+        if err.is::<UnknownMessage>() {
+            return ErrorCode::UnknownMessage;
+        }
+        if err.is::<UnsupportedProtocolVersion>() {
+            return ErrorCode::UnsupportedProtocolVersion;
+        }
+        let err = match err.downcast_ref::<PodError>() {
+            Some(err) => err,
+            None => return ErrorCode::Internal,
+        };
+        match err {
+            PodError::EnclaveNotFound(_)
+            | PodError::EnclaveLoadFailed(_)
+            | PodError::EnclaveNotLoaded
+            | PodError::InvalidSigStruct(_)
+            | PodError::LaunchDenied
+            | PodError::Tpm(_)
+            | PodError::Sgx(_) => ErrorCode::EnclaveUnavailable,
+            PodError::UntrustedEnclave(_) => ErrorCode::UntrustedEnclave,
+            PodError::SealedStateInvalid
+            | PodError::SealedFileCorrupt(_)
+            | PodError::SealedFileUnsupported(_)
+            | PodError::SealedFileMismatch(_)
+            | PodError::SealedStore(_)
+            | PodError::SealingFailed => ErrorCode::KeyUnavailable,
+            PodError::AesmUnavailable(_)
+            | PodError::QuoteFailed(_)
+            | PodError::QuoteBufferTooSmall => ErrorCode::QuoteUnavailable,
+            PodError::RawSigningDisabled => ErrorCode::RawSigningDisabled,
+            PodError::SealedDataInvalid => ErrorCode::SealedDataInvalid,
+            PodError::InvalidSealDataSize(_)
+            | PodError::InvalidSpid(_)
+            | PodError::InvalidRelyingParty(_)
+            | PodError::InvalidSigningPurpose(_)
+            | PodError::InvalidSigningPayload
+            | PodError::Base64(_) => ErrorCode::InvalidRequest,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Identity of the loaded enclave, as reported by `get_identity` and `hello`.
+#[derive(Debug, Serialize)]
+pub(super) struct Identity {
+    mr_enclave: String,
+    mr_signer: String,
+    isv_prod_id: u16,
+    isv_svn: u16,
+    flags: String,
+    xfrm: String,
+    debug: bool,
+}
+
+impl Identity {
+    fn new(report: EnclaveReport) -> Self {
+        Self {
+            mr_enclave: to_hex(report.identity().mr_enclave()),
+            mr_signer: to_hex(report.identity().mr_signer()),
+            isv_prod_id: report.isv_prod_id(),
+            isv_svn: report.isv_svn(),
+            flags: format!("{:#018x}", report.flags()),
+            xfrm: format!("{:#018x}", report.xfrm()),
+            debug: report.is_debug(),
         }
     }
 }
 
+/// Version of the native-messaging protocol, bumped on incompatible changes
+/// to the messages. Adding messages or fields doesn't require bumping it.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Messages handled by `pod-app`, as advertised in the `hello` reply.
+const MESSAGES: &[&str] = &[
+    "hello",
+    "get_public_key",
+    "get_quote",
+    "sign_challenge",
+    "sign_raw",
+    "rotate_key",
+    "get_identity",
+    "seal",
+    "unseal",
+];
+
+/// Signature algorithms of the keys, as advertised in the `hello` reply.
+const ALGORITHMS: &[&str] = &["ed25519"];
+
+/// Name of `backend` in the `hello` reply, telling a simulated enclave apart.
+fn backend_name(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Sgx if cfg!(feature = "sgx-sim") => "sgx_sim",
+        Backend::Sgx => "sgx",
+        Backend::Software => "software",
+        Backend::Tpm => "tpm",
+    }
+}
+
 mod base_64 {
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    pub(crate) fn serialize<S>(blob: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    pub(crate) fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&base64::encode(blob))
+        serializer.serialize_str(&base64::encode(bytes))
     }
 
     pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
@@ -61,27 +365,397 @@ mod base_64 {
     }
 }
 
-pub(super) fn reply<B: AsRef<[u8]>>(msg: B, config: &Config) -> Result<Vec<u8>> {
-    let msg: IncomingMessage = serde_json::from_slice(msg.as_ref())?;
-    log::debug!("Received message content: {:?}", msg);
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(super) fn open_enclave(config: &Config) -> Result<PodEnclave> {
+    let mut pod_enclave = load_enclave(config)?;
+    pod_enclave.set_raw_signing(config.allow_raw_signing);
+    // Knowing which enclave build is in use helps with support cases.
+    let report = pod_enclave.identity()?;
+    log::debug!(
+        "Loaded enclave with MRENCLAVE {}, MRSIGNER {}, ISVPRODID {}, ISVSVN {}",
+        to_hex(report.identity().mr_enclave()),
+        to_hex(report.identity().mr_signer()),
+        report.isv_prod_id(),
+        report.isv_svn()
+    );
+    if report.is_debug() {
+        log::warn!("Enclave runs in debug mode: the private key is NOT protected!");
+    }
+    Ok(pod_enclave)
+}
 
-    let reply = match msg {
-        IncomingMessage::GetQuote { spid } => {
-            let pod_enclave = PodEnclave::new(&config.enclave, &config.private_key)?;
-            let quote = pod_enclave.get_quote(spid, QuoteType::Unlinkable)?;
-            let reply = OutgoingMessage::get_quote(quote);
-            let serialized = serde_json::to_vec(&reply)?;
-            serialized
-        }
-        IncomingMessage::SignChallenge { challenge } => {
-            let pod_enclave = PodEnclave::new(&config.enclave, &config.private_key)?;
-            let signature = pod_enclave.sign(challenge)?;
-            let reply = OutgoingMessage::sign_challenge(signature);
-            let serialized = serde_json::to_vec(&reply)?;
-            serialized
+fn load_enclave(config: &Config) -> Result<PodEnclave> {
+    match config.backend {
+        #[cfg(feature = "sgx")]
+        Backend::Sgx => {
+            let mut backend = SgxBackend::new(&config.enclave)
+                .with_launch_mode(config.launch_mode)
+                .with_seal_policy(config.seal_policy);
+            // Unless configured otherwise, only trust enclaves signed with the
+            // same key as the one built along with `pod-app`. Unpinned
+            // production enclaves are refused by `SgxBackend`.
+            match config
+                .expected_mr_signer
+                .or_else(SgxBackend::built_mr_signer)
+            {
+                Some(mr_signer) => backend = backend.with_expected_mr_signer(mr_signer),
+                None if config.launch_mode == LaunchMode::Debug => {
+                    log::warn!("Enclave signer not pinned: any enclave binary will be loaded!")
+                }
+                None => {}
+            }
+            if let Some(mr_enclave) = config.expected_mr_enclave {
+                backend = backend.with_expected_mr_enclave(mr_enclave);
+            }
+            Ok(PodEnclave::with_store(backend, sealed_store(config))?)
+        }
+        #[cfg(not(feature = "sgx"))]
+        Backend::Sgx => Err(anyhow::anyhow!("pod-app was built without SGX support")),
+        Backend::Software => {
+            log::warn!("Using software backend: the private key is NOT protected by SGX!");
+            // The secret encrypting the private key sits next to it.
+            let backend =
+                SoftwareBackend::from_secret_file(config.private_key.with_extension("secret"))?;
+            Ok(PodEnclave::with_store(backend, sealed_store(config))?)
+        }
+        #[cfg(feature = "tpm")]
+        Backend::Tpm => {
+            let mut backend = TpmBackend::new();
+            if let Some(tcti) = &config.tpm_tcti {
+                backend = backend.with_tcti(tcti);
+            }
+            Ok(PodEnclave::with_store(backend, sealed_store(config))?)
+        }
+        #[cfg(not(feature = "tpm"))]
+        Backend::Tpm => Err(anyhow::anyhow!("pod-app was built without TPM support")),
+    }
+}
+
+/// Name of the keyring key, or Secret Service item, holding the sealed keys.
+const SEALED_STORE_NAME: &str = "pod-app";
+
+fn sealed_store(config: &Config) -> Box<dyn pod_api::SealedStore> {
+    match config.sealed_store {
+        SealedStore::File => Box::new(FileStore::new(&config.private_key)),
+        SealedStore::Keyring => Box::new(KeyringStore::new(SEALED_STORE_NAME)),
+        SealedStore::SecretService => Box::new(SecretServiceStore::new(SEALED_STORE_NAME)),
+    }
+}
+
+/// Handles `msg`, and returns the serialized reply. Failing to handle `msg`
+/// is reported in an `error` reply rather than as `Err`, so that a single bad
+/// message doesn't end the session.
+pub(super) fn reply<B: AsRef<[u8]>>(
+    msg: B,
+    config: &Config,
+    session: &mut Session,
+) -> Result<Vec<u8>> {
+    // Parsed separately first, so that errors can tell which request failed.
+    let (request, reply) = match serde_json::from_slice::<serde_json::Value>(msg.as_ref()) {
+        Ok(msg) => {
+            let request = msg
+                .get("msg")
+                .and_then(|name| name.as_str())
+                .map(String::from);
+            // Only the type is logged, as messages may carry secrets, e.g.,
+            // the data to seal.
+            log::debug!("Received message: {:?}", request);
+            let reply = serde_json::from_value(msg)
+                .map_err(anyhow::Error::from)
+                .and_then(|msg| handle(msg, config, session));
+            (request, reply)
+        }
+        Err(err) => (None, Err(err.into())),
+    };
+    let reply = match reply {
+        Ok(reply) => {
+            log::debug!("Handled message: {:?}", request);
+            reply
+        }
+        Err(err) => {
+            let code = ErrorCode::of(&err);
+            log::warn!("Replying with error {:?}: {:#}", code, err);
+            OutgoingMessage::error(code, format!("{:#}", err), request)
         }
     };
+    Ok(serde_json::to_vec(&reply)?)
+}
+
+/// Prefixes `aad` with `origin` (preceded by its length), so that data sealed
+/// with it is bound to `origin`.
+fn bind_origin(origin: &RelyingParty, aad: &[u8]) -> Vec<u8> {
+    let origin = origin.as_bytes();
+    let origin_len = u16::try_from(origin.len()).expect("relying party should fit u16 length");
+    let mut bound = Vec::with_capacity(2 + origin.len() + aad.len());
+    bound.extend_from_slice(&origin_len.to_le_bytes());
+    bound.extend_from_slice(origin);
+    bound.extend_from_slice(aad);
+    bound
+}
 
-    log::debug!("Reply content: {:?}", reply);
+/// Returns the `aad` passed to [`bind_origin`], provided it was bound to
+/// `origin`.
+///
+/// [`bind_origin`]: fn.bind_origin.html
+fn unbind_origin<'a>(origin: &RelyingParty, bound: &'a [u8]) -> Result<&'a [u8]> {
+    bound
+        .strip_prefix(&bind_origin(origin, &[])[..])
+        .ok_or_else(|| {
+            anyhow::Error::from(PodError::SealedDataInvalid)
+                .context("data was sealed for another origin")
+        })
+}
+
+fn handle(msg: IncomingMessage, config: &Config, session: &mut Session) -> Result<OutgoingMessage> {
+    let reply = match msg {
+        IncomingMessage::Hello { protocol_version } => {
+            if protocol_version != PROTOCOL_VERSION {
+                return Err(UnsupportedProtocolVersion(protocol_version).into());
+            }
+            // The handshake succeeds even if the enclave can't be loaded;
+            // other messages report why.
+            let identity = match session
+                .enclave(config)
+                .and_then(|pod_enclave| Ok(pod_enclave.identity()?))
+            {
+                Ok(report) => Some(Identity::new(report)),
+                Err(err) => {
+                    log::warn!("Enclave identity unavailable: {:#}", err);
+                    None
+                }
+            };
+            OutgoingMessage::hello(config, identity)
+        }
+        IncomingMessage::GetPublicKey { relying_party } => {
+            let pod_enclave = session.enclave(config)?;
+            let pub_key = match relying_party {
+                Some(relying_party) => pod_enclave.public_key_for(&relying_party)?,
+                None => pod_enclave.public_key()?,
+            };
+            OutgoingMessage::get_public_key(pub_key)
+        }
+        IncomingMessage::GetQuote {
+            spid,
+            relying_party,
+            user_data,
+        } => {
+            let pod_enclave = session.enclave(config)?;
+            let quote = pod_enclave.get_quote_for(
+                &relying_party,
+                &spid,
+                QuoteType::Unlinkable,
+                user_data,
+            )?;
+            OutgoingMessage::get_quote(quote)
+        }
+        IncomingMessage::SignChallenge {
+            challenge,
+            origin,
+            purpose,
+            relying_party,
+        } => {
+            // Otherwise a page could get its challenge signed with the key
+            // of another service.
+            if let Some(relying_party) = relying_party.filter(|rp| *rp != origin) {
+                return Err(anyhow::Error::from(PodError::InvalidRelyingParty(
+                    relying_party.to_string(),
+                ))
+                .context(format!("relying_party differs from origin '{}'", origin)));
+            }
+            let pod_enclave = session.enclave(config)?;
+            let purpose = purpose.unwrap_or(SigningPurpose::Authentication);
+            let payload = SigningPayload::now(origin.clone(), purpose, challenge);
+            let signature = pod_enclave.sign_payload_for(&origin, &payload)?;
+            OutgoingMessage::sign_challenge(signature, payload.timestamp())
+        }
+        IncomingMessage::SignRaw {
+            data,
+            relying_party,
+        } => {
+            let pod_enclave = session.enclave(config)?;
+            let signature = match relying_party {
+                Some(relying_party) => pod_enclave.sign_for(&relying_party, data)?,
+                None => pod_enclave.sign(data)?,
+            };
+            OutgoingMessage::sign_raw(signature)
+        }
+        IncomingMessage::RotateKey {
+            spid,
+            relying_party,
+            user_data,
+        } => {
+            let pod_enclave = session.enclave(config)?;
+            let (rotation, quote) = pod_enclave.rotate_key_for(
+                &relying_party,
+                &spid,
+                QuoteType::Unlinkable,
+                user_data,
+            )?;
+            OutgoingMessage::rotate_key(rotation, quote)
+        }
+        IncomingMessage::GetIdentity => {
+            let pod_enclave = session.enclave(config)?;
+            OutgoingMessage::get_identity(config.backend, pod_enclave.identity()?)
+        }
+        IncomingMessage::Seal { data, origin, aad } => {
+            let pod_enclave = session.enclave(config)?;
+            let sealed = pod_enclave.seal(data, bind_origin(&origin, &aad))?;
+            OutgoingMessage::seal(sealed)
+        }
+        IncomingMessage::Unseal { sealed, origin } => {
+            let pod_enclave = session.enclave(config)?;
+            let (data, aad) = pod_enclave.unseal(sealed)?;
+            OutgoingMessage::unseal(data, unbind_origin(&origin, &aad)?.to_vec())
+        }
+        IncomingMessage::Unknown => return Err(UnknownMessage.into()),
+    };
     Ok(reply)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::software_config;
+    use anyhow::anyhow;
+    use serde_json::Value;
+
+    fn send(msg: &str, config: &Config, session: &mut Session) -> Value {
+        serde_json::from_slice(&reply(msg, config, session).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn errors_map_to_codes() {
+        let codes = vec![
+            (
+                anyhow::Error::from(serde_json::from_str::<Value>("{").unwrap_err()),
+                ErrorCode::InvalidRequest,
+            ),
+            (UnknownMessage.into(), ErrorCode::UnknownMessage),
+            (
+                UnsupportedProtocolVersion(2).into(),
+                ErrorCode::UnsupportedProtocolVersion,
+            ),
+            (
+                PodError::EnclaveNotLoaded.into(),
+                ErrorCode::EnclaveUnavailable,
+            ),
+            (
+                PodError::UntrustedEnclave("wrong MRSIGNER".to_owned()).into(),
+                ErrorCode::UntrustedEnclave,
+            ),
+            (
+                PodError::SealedStateInvalid.into(),
+                ErrorCode::KeyUnavailable,
+            ),
+            (
+                PodError::QuoteBufferTooSmall.into(),
+                ErrorCode::QuoteUnavailable,
+            ),
+            (
+                PodError::RawSigningDisabled.into(),
+                ErrorCode::RawSigningDisabled,
+            ),
+            (
+                PodError::SealedDataInvalid.into(),
+                ErrorCode::SealedDataInvalid,
+            ),
+            (
+                PodError::InvalidSigningPayload.into(),
+                ErrorCode::InvalidRequest,
+            ),
+            (anyhow!("something else"), ErrorCode::Internal),
+        ];
+        for (err, code) in codes {
+            assert_eq!(ErrorCode::of(&err), code, "{:#}", err);
+            // Context added along the way doesn't hide the cause.
+            let err = err.context("handling message");
+            assert_eq!(ErrorCode::of(&err), code, "{:#}", err);
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_invalid_request() {
+        let (_dir, config) = software_config();
+        let reply = send("{\"msg\":", &config, &mut Session::new());
+        assert_eq!(reply["msg"], "error");
+        assert_eq!(reply["code"], "invalid_request");
+        assert!(reply["request"].is_null());
+    }
+
+    #[test]
+    fn unknown_message_is_reported() {
+        let (_dir, config) = software_config();
+        let reply = send(r#"{"msg":"frobnicate"}"#, &config, &mut Session::new());
+        assert_eq!(reply["msg"], "error");
+        assert_eq!(reply["code"], "unknown_message");
+        assert_eq!(reply["request"], "frobnicate");
+    }
+
+    #[test]
+    fn error_reply_names_request() {
+        let (_dir, config) = software_config();
+        let mut session = Session::new();
+
+        let reply = send(
+            r#"{"msg":"get_quote","spid":"nope","relying_party":"https://example.com"}"#,
+            &config,
+            &mut session,
+        );
+        assert_eq!(reply["code"], "invalid_request");
+        assert_eq!(reply["request"], "get_quote");
+
+        let reply = send(r#"{"msg":"sign_raw","data":"AAAA"}"#, &config, &mut session);
+        assert_eq!(reply["code"], "raw_signing_disabled");
+        assert_eq!(reply["request"], "sign_raw");
+
+        // The session survives errors.
+        let reply = send(r#"{"msg":"get_public_key"}"#, &config, &mut session);
+        assert_eq!(reply["msg"], "get_public_key");
+    }
+
+    #[test]
+    fn hello_with_same_version_is_accepted() {
+        let (_dir, config) = software_config();
+        let msg = format!(
+            r#"{{"msg":"hello","protocol_version":{}}}"#,
+            PROTOCOL_VERSION
+        );
+        let reply = send(&msg, &config, &mut Session::new());
+        assert_eq!(reply["msg"], "hello");
+        assert_eq!(reply["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(reply["backend"], "software");
+        assert!(reply["identity"].is_object());
+    }
+
+    #[test]
+    fn hello_with_other_version_is_rejected() {
+        let (_dir, config) = software_config();
+        let msg = format!(
+            r#"{{"msg":"hello","protocol_version":{}}}"#,
+            PROTOCOL_VERSION + 1
+        );
+        let reply = send(&msg, &config, &mut Session::new());
+        assert_eq!(reply["msg"], "error");
+        assert_eq!(reply["code"], "unsupported_protocol_version");
+        assert_eq!(reply["request"], "hello");
+    }
+
+    #[test]
+    fn extension_speaks_same_version() {
+        let background = include_str!("../../pod-ext/background.js");
+        assert!(background.contains("msg: HELLO, protocol_version: PROTOCOL_VERSION"));
+
+        let constants = include_str!("../../pod-ext/js/constants.js");
+        let version = constants
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("PROTOCOL_VERSION:"))
+            .expect("constants.js should define PROTOCOL_VERSION");
+        assert_eq!(
+            version.trim().trim_end_matches(',').parse::<u32>().unwrap(),
+            PROTOCOL_VERSION
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use super::{messages, Config, SIGNALED};
+use anyhow::{anyhow, Result};
+use pod_api::PodEnclave;
+use std::process;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the reaper checks whether the enclave has been idle for too
+/// long, or a termination signal was received.
+const REAPER_TICK: Duration = Duration::from_millis(100);
+
+/// Enclave kept loaded for the life of the native-messaging session, so that
+/// each message costs a single ECALL rather than loading the enclave anew.
+///
+/// The enclave is loaded lazily by the first message needing it, and unloaded
+/// by the reaper (see [`spawn_reaper`]) once idle for longer than the
+/// configured timeout, until the next message loads it again.
+///
+/// [`spawn_reaper`]: fn.spawn_reaper.html
+pub(super) struct Session {
+    enclave: Option<PodEnclave>,
+    last_used: Instant,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            enclave: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// Returns the loaded enclave, loading it first if need be.
+    pub fn enclave(&mut self, config: &Config) -> Result<&PodEnclave> {
+        self.last_used = Instant::now();
+        if self.enclave.is_none() {
+            self.enclave = Some(messages::open_enclave(config)?);
+        }
+        Ok(self.enclave.as_ref().expect("enclave just loaded"))
+    }
+
+    /// Unloads the enclave, if loaded.
+    pub fn close(&mut self) {
+        if self.enclave.take().is_some() {
+            log::debug!("Enclave unloaded");
+        }
+    }
+
+    fn is_idle(&self, timeout: Duration) -> bool {
+        self.enclave.is_some() && self.last_used.elapsed() >= timeout
+    }
+}
+
+/// Spawns the thread unloading the enclave of `session` once idle for longer
+/// than `idle_timeout` (if any), and on SIGINT or SIGTERM, after which it
+/// exits the process.
+///
+/// The main thread spends most of its time blocked reading stdin, which
+/// signals don't interrupt, hence the unloading is done here.
+pub(super) fn spawn_reaper(session: Arc<Mutex<Session>>, idle_timeout: Option<Duration>) {
+    thread::spawn(move || loop {
+        thread::sleep(REAPER_TICK);
+        // The main thread holds the lock while handling a message and
+        // writing its reply, so this waits for the reply to be sent.
+        let mut session = match session.lock() {
+            Ok(session) => session,
+            Err(_) => return,
+        };
+        if tick(&mut session, idle_timeout, SIGNALED.load(Ordering::Relaxed)) == Tick::Exit {
+            // The enclave is unloaded by now. The lock is kept until the
+            // process is gone, so that no message can load it again.
+            log::debug!("Application successfully stopped...");
+            process::exit(0);
+        }
+    });
+}
+
+/// What the reaper does after a [`tick`].
+///
+/// [`tick`]: fn.tick.html
+#[derive(Debug, PartialEq, Eq)]
+enum Tick {
+    Continue,
+    Exit,
+}
+
+/// Unloads the enclave of `session` if it's been idle for longer than
+/// `idle_timeout` (if any), or if `signaled`, in which case the process has
+/// to exit.
+fn tick(session: &mut Session, idle_timeout: Option<Duration>, signaled: bool) -> Tick {
+    if signaled {
+        log::debug!("Received termination signal, exiting...");
+        session.close();
+        return Tick::Exit;
+    }
+    if let Some(timeout) = idle_timeout {
+        if session.is_idle(timeout) {
+            log::debug!("Enclave idle for {:?}, unloading", timeout);
+            session.close();
+        }
+    }
+    Tick::Continue
+}
+
+/// Locks `session`, failing if a panic left it poisoned.
+pub(super) fn lock(session: &Mutex<Session>) -> Result<MutexGuard<'_, Session>> {
+    session
+        .lock()
+        .map_err(|_| anyhow!("enclave session poisoned by a panic"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::software_config;
+
+    #[test]
+    fn idle_enclave_is_unloaded_and_reloaded_lazily() {
+        let (_dir, config) = software_config();
+        let mut session = Session::new();
+        let pub_key = session.enclave(&config).unwrap().public_key().unwrap();
+
+        let timeout = Duration::from_millis(50);
+        assert_eq!(tick(&mut session, Some(timeout), false), Tick::Continue);
+        assert!(session.enclave.is_some());
+
+        thread::sleep(timeout);
+        assert_eq!(tick(&mut session, None, false), Tick::Continue);
+        assert!(session.enclave.is_some());
+        assert_eq!(tick(&mut session, Some(timeout), false), Tick::Continue);
+        assert!(session.enclave.is_none());
+
+        assert_eq!(
+            session.enclave(&config).unwrap().public_key().unwrap(),
+            pub_key
+        );
+    }
+
+    #[test]
+    fn signal_closes_session_before_exit() {
+        let (_dir, config) = software_config();
+        let mut session = Session::new();
+        session.enclave(&config).unwrap();
+
+        assert_eq!(tick(&mut session, None, true), Tick::Exit);
+        assert!(session.enclave.is_none());
+    }
+}
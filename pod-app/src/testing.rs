@@ -0,0 +1,45 @@
+//! Fixtures shared by the unit tests.
+use super::{Backend, Config};
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Temporary directory, removed on drop, i.e., also when the test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pod-app-test-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).expect("temp dir should be created");
+        Self(path)
+    }
+
+    pub(crate) fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Returns a config using the software backend, with its keys and log in a
+/// fresh directory, which must outlive it.
+pub(crate) fn software_config() -> (TempDir, Config) {
+    let dir = TempDir::new();
+    let config = Config {
+        private_key: dir.path("private_key.sealed"),
+        log_output: dir.path("pod-app.log"),
+        backend: Backend::Software,
+        ..Config::default()
+    };
+    (dir, config)
+}
@@ -0,0 +1,282 @@
+/* Ed25519ph signing (RFC 8032), for SGX SSL builds whose OpenSSL can't sign a caller supplied
+ * prehash (ED25519ph appeared in OpenSSL 3.5). OpenSSL still provides SHA-512; the field and group
+ * arithmetic below is adapted from TweetNaCl (public domain), and runs in constant time. */
+#include <openssl/crypto.h>
+#include <openssl/evp.h>
+#include <string.h>
+
+#include "ed25519ph.h"
+
+typedef int64_t gf[16];
+
+static const gf gf0;
+static const gf gf1 = {1};
+/* 2 * d, where d is the curve constant */
+static const gf D2 = {0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0,
+                      0xd130, 0xeef3, 0x80f2, 0x198e, 0xfce7, 0x56df, 0xd9dc, 0x2406};
+/* Coordinates of the base point */
+static const gf X = {0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c,
+                     0xdc5c, 0xfdd6, 0xe231, 0xc0a4, 0x53fe, 0xcd6e, 0x36d3, 0x2169};
+static const gf Y = {0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+                     0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666};
+/* Order of the base point, little endian */
+static const int64_t L[32] = {0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7,
+                              0xa2, 0xde, 0xf9, 0xde, 0x14, 0,    0,    0,    0,    0,    0,
+                              0,    0,    0,    0,    0,    0,    0,    0,    0,    0x10};
+
+/* dom2(1, "") prefix of the hashed messages, see RFC 8032, section 2 */
+static const uint8_t DOM2[] = "SigEd25519 no Ed25519 collisions\x01\x00";
+#define DOM2_SIZE (sizeof(DOM2) - 1)
+
+static void set25519(gf r, const gf a) {
+    for (int i = 0; i < 16; i++)
+        r[i] = a[i];
+}
+
+static void car25519(gf o) {
+    for (int i = 0; i < 16; i++) {
+        o[i] += (1LL << 16);
+        int64_t c = o[i] >> 16;
+        o[(i + 1) * (i < 15)] += c - 1 + 37 * (c - 1) * (i == 15);
+        o[i] -= c * (1LL << 16);
+    }
+}
+
+/* Swaps p and q if b is 1, in constant time */
+static void sel25519(gf p, gf q, int b) {
+    int64_t c = ~(b - 1);
+    for (int i = 0; i < 16; i++) {
+        int64_t t = c & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+static void pack25519(uint8_t* o, const gf n) {
+    gf m, t;
+    set25519(t, n);
+    car25519(t);
+    car25519(t);
+    car25519(t);
+    for (int j = 0; j < 2; j++) {
+        m[0] = t[0] - 0xffed;
+        for (int i = 1; i < 15; i++) {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        int b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        sel25519(t, m, 1 - b);
+    }
+    for (int i = 0; i < 16; i++) {
+        o[2 * i] = t[i] & 0xff;
+        o[2 * i + 1] = t[i] >> 8;
+    }
+}
+
+static uint8_t par25519(const gf a) {
+    uint8_t d[32];
+    pack25519(d, a);
+    return d[0] & 1;
+}
+
+static void A(gf o, const gf a, const gf b) {
+    for (int i = 0; i < 16; i++)
+        o[i] = a[i] + b[i];
+}
+
+static void Z(gf o, const gf a, const gf b) {
+    for (int i = 0; i < 16; i++)
+        o[i] = a[i] - b[i];
+}
+
+static void M(gf o, const gf a, const gf b) {
+    int64_t t[31] = {0};
+    for (int i = 0; i < 16; i++)
+        for (int j = 0; j < 16; j++)
+            t[i + j] += a[i] * b[j];
+    for (int i = 0; i < 15; i++)
+        t[i] += 38 * t[i + 16];
+    for (int i = 0; i < 16; i++)
+        o[i] = t[i];
+    car25519(o);
+    car25519(o);
+}
+
+static void inv25519(gf o, const gf i) {
+    gf c;
+    set25519(c, i);
+    for (int a = 253; a >= 0; a--) {
+        M(c, c, c);
+        if (a != 2 && a != 4)
+            M(c, c, i);
+    }
+    set25519(o, c);
+}
+
+/* p += q, in extended coordinates */
+static void add(gf p[4], gf q[4]) {
+    gf a, b, c, d, t, e, f, g, h;
+    Z(a, p[1], p[0]);
+    Z(t, q[1], q[0]);
+    M(a, a, t);
+    A(b, p[0], p[1]);
+    A(t, q[0], q[1]);
+    M(b, b, t);
+    M(c, p[3], q[3]);
+    M(c, c, D2);
+    M(d, p[2], q[2]);
+    A(d, d, d);
+    Z(e, b, a);
+    Z(f, d, c);
+    A(g, d, c);
+    A(h, b, a);
+    M(p[0], e, f);
+    M(p[1], h, g);
+    M(p[2], g, f);
+    M(p[3], e, h);
+}
+
+static void cswap(gf p[4], gf q[4], uint8_t b) {
+    for (int i = 0; i < 4; i++)
+        sel25519(p[i], q[i], b);
+}
+
+static void pack(uint8_t* r, gf p[4]) {
+    gf tx, ty, zi;
+    inv25519(zi, p[2]);
+    M(tx, p[0], zi);
+    M(ty, p[1], zi);
+    pack25519(r, ty);
+    r[31] ^= par25519(tx) << 7;
+}
+
+/* p = s * B, where B is the base point */
+static void scalarbase(gf p[4], const uint8_t* s) {
+    gf q[4];
+    set25519(q[0], X);
+    set25519(q[1], Y);
+    set25519(q[2], gf1);
+    M(q[3], X, Y);
+
+    set25519(p[0], gf0);
+    set25519(p[1], gf1);
+    set25519(p[2], gf1);
+    set25519(p[3], gf0);
+    for (int i = 255; i >= 0; --i) {
+        uint8_t b = (s[i / 8] >> (i & 7)) & 1;
+        cswap(p, q, b);
+        add(q, p);
+        add(p, p);
+        cswap(p, q, b);
+    }
+}
+
+/* r = x mod L, where x is a 64 limb little endian number */
+static void modL(uint8_t* r, int64_t x[64]) {
+    int64_t carry;
+    int i, j;
+    for (i = 63; i >= 32; --i) {
+        carry = 0;
+        for (j = i - 32; j < i - 12; ++j) {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry * 256;
+        }
+        x[j] += carry;
+        x[i] = 0;
+    }
+    carry = 0;
+    for (j = 0; j < 32; j++) {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for (j = 0; j < 32; j++)
+        x[j] -= carry * L[j];
+    for (i = 0; i < 32; i++) {
+        x[i + 1] += x[i] >> 8;
+        r[i] = x[i] & 255;
+    }
+}
+
+/* Reduces the 64-byte little endian number r mod L into its first 32 bytes */
+static void reduce(uint8_t* r) {
+    int64_t x[64];
+    for (int i = 0; i < 64; i++)
+        x[i] = r[i];
+    memset(r, 0, 64);
+    modL(r, x);
+    OPENSSL_cleanse(x, sizeof(x));
+}
+
+/* out = SHA-512(dom2 || a || b || c); b may be NULL */
+static int hash_dom2(uint8_t out[64], const uint8_t a[32], const uint8_t* b, const uint8_t c[64]) {
+    int ret = -1;
+    EVP_MD_CTX* md = EVP_MD_CTX_new();
+    if (!md)
+        return -1;
+
+    if (EVP_DigestInit_ex(md, EVP_sha512(), NULL) != 1)
+        goto out;
+    if (EVP_DigestUpdate(md, DOM2, DOM2_SIZE) != 1)
+        goto out;
+    if (EVP_DigestUpdate(md, a, 32) != 1)
+        goto out;
+    if (b && EVP_DigestUpdate(md, b, 32) != 1)
+        goto out;
+    if (EVP_DigestUpdate(md, c, 64) != 1)
+        goto out;
+    if (EVP_DigestFinal_ex(md, out, NULL) != 1)
+        goto out;
+
+    ret = 0;
+out:
+    EVP_MD_CTX_free(md);
+    return ret;
+}
+
+int ed25519ph_sign(uint8_t signature[64], const uint8_t private_key[32],
+                   const uint8_t public_key[32], const uint8_t digest[64]) {
+    int ret = -1;
+    uint8_t h[64], r[64], k[64];
+    int64_t x[64];
+    gf p[4];
+
+    /* Secret scalar s and prefix, see RFC 8032, section 5.1.5 */
+    if (EVP_Digest(private_key, 32, h, NULL, EVP_sha512(), NULL) != 1)
+        goto out;
+    h[0] &= 248;
+    h[31] &= 127;
+    h[31] |= 64;
+
+    /* r = SHA-512(dom2 || prefix || PH(M)) mod L, R = r * B */
+    if (hash_dom2(r, h + 32, NULL, digest) < 0)
+        goto out;
+    reduce(r);
+    scalarbase(p, r);
+    pack(signature, p);
+
+    /* k = SHA-512(dom2 || R || A || PH(M)) mod L */
+    if (hash_dom2(k, signature, public_key, digest) < 0)
+        goto out;
+    reduce(k);
+
+    /* S = (r + k * s) mod L */
+    memset(x, 0, sizeof(x));
+    for (int i = 0; i < 32; i++)
+        x[i] = r[i];
+    for (int i = 0; i < 32; i++)
+        for (int j = 0; j < 32; j++)
+            x[i + j] += k[i] * (int64_t)h[j];
+    modL(signature + 32, x);
+
+    ret = 0;
+out:
+    OPENSSL_cleanse(h, sizeof(h));
+    OPENSSL_cleanse(r, sizeof(r));
+    OPENSSL_cleanse(x, sizeof(x));
+    OPENSSL_cleanse(p, sizeof(p));
+    return ret;
+}
@@ -0,0 +1,13 @@
+#ifndef ED25519PH_H
+#define ED25519PH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/*! Signs the SHA-512 digest of a message with Ed25519ph (RFC 8032, section 5.1) and an empty
+ *  context, given the 32-byte private key (seed) and the matching public key.
+ *  Returns 0 on success, -1 on failure. */
+int ed25519ph_sign(uint8_t signature[64], const uint8_t private_key[32],
+                   const uint8_t public_key[32], const uint8_t digest[64]);
+
+#endif
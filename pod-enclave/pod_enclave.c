@@ -1,11 +1,14 @@
 #include <assert.h>
 #include <openssl/evp.h>
+#include <openssl/hmac.h>
+#include <openssl/sha.h>
 #include <sgx_utils.h>
 #include <stdbool.h>
 #include <stdio.h>
 #include <string.h>
 #include <tSgxSSL_api.h>
 
+#include "ed25519ph.h"
 #include "pod_enclave.h"
 #include "pod_enclave_t.h"
 #include "pod_enclave_mrsigner.h"
@@ -26,49 +29,47 @@ void zero_memory(void* mem, size_t size) {
 }
 
 #define PRINT_BUFFER_MAX 4096
-void eprintf(const char* fmt, ...) {
+static void enclave_log(int level, const char* fmt, ...) {
 	char buf[PRINT_BUFFER_MAX];
 	buf[PRINT_BUFFER_MAX - 1] = 0;
 	va_list ap;
 	va_start(ap, fmt);
 	vsnprintf(buf, PRINT_BUFFER_MAX, fmt, ap);
 	va_end(ap);
-	o_print(buf);
+	o_log(level, buf);
 }
 
-void _hexdump(void* data, size_t size) {
-    uint8_t* ptr = (uint8_t*)data;
+#define DBG(fmt, ...)   enclave_log(ENCLAVE_LOG_DEBUG, fmt, ##__VA_ARGS__)
+#define ERROR(fmt, ...) enclave_log(ENCLAVE_LOG_ERROR, fmt, ##__VA_ARGS__)
 
-    for (size_t i = 0; i < size; i++)
-        eprintf("%02x", ptr[i]);
-    eprintf("\n");
-}
+/* Logs the enclave public key as a single hex encoded line */
+static void log_public_key(void) {
+    char hex[EC_PUBLIC_KEY_SIZE * 2 + 1] = {0};
 
-#define hexdump(x) _hexdump((void*)&x, sizeof(x))
+    for (size_t i = 0; i < EC_PUBLIC_KEY_SIZE; i++)
+        snprintf(&hex[i * 2], 3, "%02x", g_public_key[i]);
+    DBG("Enclave public key: %s\n", hex);
+}
 
-int generate_public_key(void) {
+// Export raw public key of the given private key
+static int get_raw_public_key(EVP_PKEY* key, uint8_t* pubkey) {
     int ret = -1;
 
-    if (!g_ec_ctx)
-        goto out;
-
-    // export raw public key
     size_t pubkey_size;
-    int openssl_ret = EVP_PKEY_get_raw_public_key(g_private_key, NULL, &pubkey_size);
+    int openssl_ret = EVP_PKEY_get_raw_public_key(key, NULL, &pubkey_size);
     if (openssl_ret <= 0) {
-        eprintf("Failed to get public key size: %d\n", openssl_ret);
+        ERROR("Failed to get public key size: %d\n", openssl_ret);
         goto out;
     }
 
     if (pubkey_size != EC_PUBLIC_KEY_SIZE) {
-        eprintf("Invalid public key size\n");
+        ERROR("Invalid public key size\n");
         goto out;
     }
 
-    openssl_ret = EVP_PKEY_get_raw_public_key(g_private_key, (unsigned char*)&g_public_key,
-                                              &pubkey_size);
+    openssl_ret = EVP_PKEY_get_raw_public_key(key, (unsigned char*)pubkey, &pubkey_size);
     if (openssl_ret <= 0) {
-        eprintf("Failed to get public key: %d\n", openssl_ret);
+        ERROR("Failed to get public key: %d\n", openssl_ret);
         goto out;
     }
 
@@ -77,22 +78,90 @@ out:
     return ret;
 }
 
+int generate_public_key(void) {
+    if (!g_ec_ctx)
+        return -1;
+
+    return get_raw_public_key(g_private_key, g_public_key);
+}
+
+/* Derive the private key of a relying party from the master private key
+ * The seed of the derived key is HMAC-SHA256(master private key, RELYING_PARTY_KEY_DOMAIN ||
+ * relying_party), so that every relying party sees a different, unlinkable public key. */
+static EVP_PKEY* derive_private_key(const uint8_t* relying_party, size_t relying_party_size) {
+    EVP_PKEY* key = NULL;
+    HMAC_CTX* hmac = NULL;
+    uint8_t master[EC_PRIVATE_KEY_SIZE];
+    size_t master_size = sizeof(master);
+    uint8_t seed[SHA256_DIGEST_LENGTH];
+    unsigned int seed_size = sizeof(seed);
+
+    int ret = EVP_PKEY_get_raw_private_key(g_private_key, master, &master_size);
+    if (ret <= 0) {
+        ERROR("Failed to get private key data: %d\n", ret);
+        goto out;
+    }
+
+    hmac = HMAC_CTX_new();
+    if (!hmac
+        || HMAC_Init_ex(hmac, master, master_size, EVP_sha256(), NULL) != 1
+        || HMAC_Update(hmac, (const unsigned char*)RELYING_PARTY_KEY_DOMAIN,
+                       sizeof(RELYING_PARTY_KEY_DOMAIN)) != 1
+        || HMAC_Update(hmac, relying_party, relying_party_size) != 1
+        || HMAC_Final(hmac, seed, &seed_size) != 1) {
+        ERROR("Failed to derive relying party key seed\n");
+        goto out;
+    }
+
+    key = EVP_PKEY_new_raw_private_key(EC_CURVE_ID, NULL, seed, seed_size);
+    if (!key)
+        ERROR("Failed to create relying party private key\n");
+
+out:
+    HMAC_CTX_free(hmac);
+    zero_memory(master, sizeof(master));
+    zero_memory(seed, sizeof(seed));
+    return key;
+}
+
+/* Get the private key to use: the master one if relying_party is NULL, the one derived for
+ * relying_party otherwise. The key has to be released with release_key. */
+static EVP_PKEY* acquire_key(const uint8_t* relying_party, size_t relying_party_size) {
+    if (!g_initialized)
+        return NULL;
+
+    if (!relying_party)
+        return g_private_key;
+
+    if (relying_party_size == 0 || relying_party_size > MAX_RELYING_PARTY_SIZE) {
+        ERROR("Invalid relying party size: %zu\n", relying_party_size);
+        return NULL;
+    }
+
+    return derive_private_key(relying_party, relying_party_size);
+}
+
+static void release_key(EVP_PKEY* key) {
+    if (key != g_private_key)
+        EVP_PKEY_free(key);
+}
+
 int generate_private_key(void) {
     int ret = -1;
 
     if (!g_ec_ctx)
         goto out;
 
-    eprintf("Generating enclave private key...\n");
+    DBG("Generating enclave private key...\n");
     int openssl_ret = EVP_PKEY_keygen_init(g_ec_ctx);
     if (openssl_ret <= 0) {
-        eprintf("Failed to initialize keygen: %d\n", openssl_ret);
+        ERROR("Failed to initialize keygen: %d\n", openssl_ret);
         goto out;
     }
 
     openssl_ret = EVP_PKEY_keygen(g_ec_ctx, &g_private_key);
     if (openssl_ret <= 0) {
-        eprintf("Failed to generate private key: %d\n", openssl_ret);
+        ERROR("Failed to generate private key: %d\n", openssl_ret);
         goto out;
     }
 
@@ -103,17 +172,19 @@ out:
 }
 
 /* ECALL: initialize enclave
- * If sealed_data is provided, unseal private key from it. If not, generate new key pair.
+ * If sealed_data is provided, unseal private key from it. If not, generate new key pair and seal
+ * it using key_policy (SGX_KEYPOLICY_MRENCLAVE or SGX_KEYPOLICY_MRSIGNER).
  * Enclave public key is stored in pubkey if pubkey_size is enough for it. */
-int e_initialize(uint8_t* sealed_data, size_t sealed_size, uint8_t* pubkey, size_t pubkey_size) {
+int e_initialize(uint8_t* sealed_data, size_t sealed_size, uint16_t key_policy, uint8_t* pubkey,
+                 size_t pubkey_size) {
     int ret = -1;
 
-    eprintf("Enclave initializing...\n");
+    DBG("Enclave initializing...\n");
     OPENSSL_init_crypto(0, NULL);
 
     g_ec_ctx = EVP_PKEY_CTX_new_id(EC_CURVE_ID, NULL);
     if (!g_ec_ctx) {
-        eprintf("Failed to create crypto context\n");
+        ERROR("Failed to create crypto context\n");
         goto out;
     }
 
@@ -122,10 +193,11 @@ int e_initialize(uint8_t* sealed_data, size_t sealed_size, uint8_t* pubkey, size
         if (ret < 0)
             goto out;
 
-        ret = seal_keys();
+        ret = seal_keys(key_policy);
         if (ret < 0)
             goto out;
     } else {
+        // Unsealing works regardless of the policy the data was sealed with
         ret = unseal_keys(sealed_data, sealed_size);
         if (ret < 0)
             goto out;
@@ -133,15 +205,14 @@ int e_initialize(uint8_t* sealed_data, size_t sealed_size, uint8_t* pubkey, size
         ret = generate_public_key();
     }
 
-    eprintf("Enclave public key: ");
-    hexdump(g_public_key);
+    log_public_key();
 
     if (pubkey_size >= EC_PUBLIC_KEY_SIZE) {
-        eprintf("Copying enclave public key...\n");
+        DBG("Copying enclave public key...\n");
         memcpy(pubkey, &g_public_key, EC_PUBLIC_KEY_SIZE);
     }
 
-    eprintf("Enclave initialization OK\n");
+    DBG("Enclave initialization OK\n");
     ret = 0;
 
 out:
@@ -152,30 +223,35 @@ out:
 }
 
 // Seal enclave keys
-int seal_keys(void) {
+int seal_keys(uint16_t key_policy) {
     sgx_status_t sgx_ret = SGX_ERROR_UNEXPECTED;
     uint8_t* sealed_keys = NULL;
     size_t sealed_size = 0;
 
-    eprintf("Sealing enclave keys...\n");
+    DBG("Sealing enclave keys...\n");
+
+    if (key_policy != SGX_KEYPOLICY_MRENCLAVE && key_policy != SGX_KEYPOLICY_MRSIGNER) {
+        ERROR("Invalid sealing policy: %u\n", key_policy);
+        return -1;
+    }
 
     unsigned char* key_raw = NULL;
     size_t key_size = 0;
     int ret = EVP_PKEY_get_raw_private_key(g_private_key, NULL, &key_size);
     if (ret <= 0) {
-        eprintf("Failed to get private key size: %d\n", ret);
+        ERROR("Failed to get private key size: %d\n", ret);
         goto out;
     }
 
     key_raw = malloc(key_size);
     if (!key_raw) {
-        eprintf("Failed to allocate memory\n");
+        ERROR("Failed to allocate memory\n");
         goto out;
     }
 
     ret = EVP_PKEY_get_raw_private_key(g_private_key, key_raw, &key_size);
     if (ret <= 0) {
-        eprintf("Failed to get private key data: %d\n", ret);
+        ERROR("Failed to get private key data: %d\n", ret);
         goto out;
     }
 
@@ -183,11 +259,11 @@ int seal_keys(void) {
     sealed_size = sgx_calc_sealed_data_size(0, key_size);
     sealed_keys = malloc(sealed_size);
     if (!sealed_keys) {
-        eprintf("Failed to allocate memory\n");
+        ERROR("Failed to allocate memory\n");
         goto out;
     }
 
-    sgx_ret = sgx_seal_data_ex(ENCLAVE_SEALING_POLICY,
+    sgx_ret = sgx_seal_data_ex(key_policy,
                                g_seal_attributes,
                                0, // misc mask, reserved
                                0, // additional data size
@@ -197,13 +273,13 @@ int seal_keys(void) {
                                sealed_size,
                                (sgx_sealed_data_t*)sealed_keys);
     if (sgx_ret != SGX_SUCCESS) {
-        eprintf("Failed to seal keys\n");
+        ERROR("Failed to seal keys\n");
         goto out;
     }
 
     sgx_ret = o_store_sealed_data(&ret, sealed_keys, sealed_size);
     if (sgx_ret != SGX_SUCCESS || ret < 0) {
-        eprintf("Failed to store sealed keys\n");
+        ERROR("Failed to store sealed keys\n");
     }
 
 out:
@@ -226,22 +302,29 @@ int unseal_keys(const uint8_t* sealed_data, size_t sealed_size) {
     uint8_t* unsealed_keys = NULL;
     uint32_t unsealed_size = 0;
 
-    eprintf("Unsealing enclave keys...\n");
+    DBG("Unsealing enclave keys...\n");
 
     if (sealed_size < sizeof(sgx_sealed_data_t)) {
-        eprintf("Invalid sealed data\n");
+        ERROR("Invalid sealed data\n");
+        goto out;
+    }
+
+    // Keys are sealed without additional data; anything else is application data (see
+    // e_seal_data), which must never be loaded as the private key
+    if (sgx_get_add_mac_txt_len((const sgx_sealed_data_t*)sealed_data) != 0) {
+        ERROR("Sealed data doesn't contain enclave keys\n");
         goto out;
     }
 
     unsealed_size = sgx_get_encrypt_txt_len((const sgx_sealed_data_t*)sealed_data);
     if (unsealed_size == UINT32_MAX) {
-        eprintf("Failed to get unsealed data size\n");
+        ERROR("Failed to get unsealed data size\n");
         goto out;
     }
 
     unsealed_keys = malloc(unsealed_size);
     if (!unsealed_keys) {
-        eprintf("Failed to allocate memory\n");
+        ERROR("Failed to allocate memory\n");
         goto out;
     }
 
@@ -251,7 +334,7 @@ int unseal_keys(const uint8_t* sealed_data, size_t sealed_size) {
                               unsealed_keys,
                               &unsealed_size);
     if (sgx_ret != SGX_SUCCESS) {
-        eprintf("Failed to unseal enclave keys\n");
+        ERROR("Failed to unseal enclave keys\n");
         goto out;
     }
 
@@ -259,7 +342,7 @@ int unseal_keys(const uint8_t* sealed_data, size_t sealed_size) {
     // Recreate private key from the unsealed blob
     g_private_key = EVP_PKEY_new_raw_private_key(EC_CURVE_ID, NULL, unsealed_keys, unsealed_size);
     if (!g_private_key) {
-        eprintf("Failed to recreate private key\n");
+        ERROR("Failed to recreate private key\n");
         goto out;
     }
 
@@ -283,7 +366,7 @@ int get_report(const sgx_target_info_t* target_info, const sgx_report_data_t* re
 
     sgx_status_t sgx_ret = sgx_create_report(target_info, report_data, report);
     if (sgx_ret != SGX_SUCCESS) {
-        eprintf("Failed to create enclave report: %d\n", sgx_ret);
+        ERROR("Failed to create enclave report: %d\n", sgx_ret);
         ret = -1;
     } else {
         ret = 0;
@@ -293,27 +376,191 @@ out:
     return ret;
 }
 
-/* ECALL: get enclave report */
-int e_get_report(const sgx_target_info_t* target_info, sgx_report_t* report) {
-    sgx_report_data_t report_data = {0};
+/* ECALL: seal enclave keys again using key_policy, e.g. to migrate them to an MRSIGNER based
+ * policy before upgrading the enclave. Sealed keys are passed out via o_store_sealed_data. */
+int e_seal_keys(uint16_t key_policy) {
+    if (!g_initialized)
+        return -1;
 
-    // Use public key as custom data in the report
-    assert(sizeof g_public_key <= sizeof report_data);
-    memcpy(&report_data, g_public_key, sizeof g_public_key);
+    return seal_keys(key_policy);
+}
 
-    return get_report(target_info, &report_data, report);
+/* ECALL: seal application data using key_policy
+ * The additional authenticated data is SEALED_DATA_DOMAIN || aad, so that the sealed data can't be
+ * confused with sealed keys. Returns the number of bytes written to sealed_data. */
+int e_seal_data(uint16_t key_policy, const uint8_t* aad, size_t aad_size, const uint8_t* data,
+                size_t data_size, uint8_t* sealed_data, size_t sealed_size) {
+    int ret = -1;
+    uint8_t* mac_text = NULL;
+    size_t mac_text_size = sizeof(SEALED_DATA_DOMAIN) + aad_size;
+
+    if (!g_initialized)
+        goto out;
+
+    if (key_policy != SGX_KEYPOLICY_MRENCLAVE && key_policy != SGX_KEYPOLICY_MRSIGNER) {
+        ERROR("Invalid sealing policy: %u\n", key_policy);
+        goto out;
+    }
+
+    if (data_size == 0 || aad_size > MAX_SEALED_DATA_SIZE
+        || data_size > MAX_SEALED_DATA_SIZE - aad_size) {
+        ERROR("Invalid size of data to seal: %zu + %zu\n", data_size, aad_size);
+        goto out;
+    }
+
+    uint32_t required_size = sgx_calc_sealed_data_size(mac_text_size, data_size);
+    if (required_size == UINT32_MAX || sealed_size < required_size) {
+        ERROR("Invalid sealed data buffer size: %zu\n", sealed_size);
+        goto out;
+    }
+
+    mac_text = malloc(mac_text_size);
+    if (!mac_text) {
+        ERROR("Failed to allocate memory\n");
+        goto out;
+    }
+    memcpy(mac_text, SEALED_DATA_DOMAIN, sizeof(SEALED_DATA_DOMAIN));
+    if (aad_size > 0)
+        memcpy(mac_text + sizeof(SEALED_DATA_DOMAIN), aad, aad_size);
+
+    sgx_status_t sgx_ret = sgx_seal_data_ex(key_policy,
+                                            g_seal_attributes,
+                                            0, // misc mask, reserved
+                                            mac_text_size,
+                                            mac_text,
+                                            data_size,
+                                            data,
+                                            required_size,
+                                            (sgx_sealed_data_t*)sealed_data);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to seal data: %d\n", sgx_ret);
+        goto out;
+    }
+
+    ret = required_size;
+out:
+    free(mac_text);
+    return ret;
 }
 
-/* ECALL: sign data with enclave's private key */
-int e_sign_data(const void* data, size_t data_size, void* signature, size_t signature_size) {
+/* ECALL: unseal application data sealed with e_seal_data
+ * aad_size and data_size have to match the sizes recorded in the sealed data. */
+int e_unseal_data(const uint8_t* sealed_data, size_t sealed_size, uint8_t* aad, size_t aad_size,
+                  uint8_t* data, size_t data_size) {
     int ret = -1;
-    EVP_MD_CTX* md = NULL;
+    const sgx_sealed_data_t* sealed = (const sgx_sealed_data_t*)sealed_data;
+    uint8_t* mac_text = NULL;
+    uint8_t* text = NULL;
 
     if (!g_initialized)
         goto out;
 
+    if (sealed_size < sizeof(sgx_sealed_data_t)) {
+        ERROR("Invalid sealed data\n");
+        goto out;
+    }
+
+    uint32_t mac_text_size = sgx_get_add_mac_txt_len(sealed);
+    uint32_t text_size = sgx_get_encrypt_txt_len(sealed);
+    if (mac_text_size == UINT32_MAX || text_size == UINT32_MAX
+        || sgx_calc_sealed_data_size(mac_text_size, text_size) > sealed_size) {
+        ERROR("Invalid sealed data\n");
+        goto out;
+    }
+
+    if (mac_text_size < sizeof(SEALED_DATA_DOMAIN)
+        || mac_text_size - sizeof(SEALED_DATA_DOMAIN) != aad_size || text_size != data_size) {
+        ERROR("Sealed data doesn't match the expected sizes\n");
+        goto out;
+    }
+
+    mac_text = malloc(mac_text_size);
+    text = malloc(text_size);
+    if (!mac_text || !text) {
+        ERROR("Failed to allocate memory\n");
+        goto out;
+    }
+
+    sgx_status_t sgx_ret = sgx_unseal_data(sealed, mac_text, &mac_text_size, text, &text_size);
+    if (sgx_ret != SGX_SUCCESS) {
+        ERROR("Failed to unseal data: %d\n", sgx_ret);
+        goto out;
+    }
+
+    if (memcmp(mac_text, SEALED_DATA_DOMAIN, sizeof(SEALED_DATA_DOMAIN)) != 0) {
+        ERROR("Sealed data is not application data\n");
+        goto out;
+    }
+
+    memcpy(data, text, data_size);
+    if (aad_size > 0)
+        memcpy(aad, mac_text + sizeof(SEALED_DATA_DOMAIN), aad_size);
+    ret = 0;
+out:
+    if (text) {
+        zero_memory(text, data_size);
+        free(text);
+    }
+    free(mac_text);
+    return ret;
+}
+
+/* ECALL: get enclave report
+ * The report data is public key || SHA-256(public key || user_data), where the public key is the
+ * master one, or the one derived for relying_party if it's not NULL. user_data (e.g. a nonce
+ * issued by the verifier) binds the report to a particular request, and may be empty. */
+int e_get_report(const sgx_target_info_t* target_info, const uint8_t* relying_party,
+                 size_t relying_party_size, const void* user_data, size_t user_data_size,
+                 sgx_report_t* report) {
+    sgx_report_data_t report_data = {0};
+    uint8_t* public_key = report_data.d;
+    uint8_t* binding = report_data.d + EC_PUBLIC_KEY_SIZE;
+    SHA256_CTX sha;
+
+    EVP_PKEY* key = acquire_key(relying_party, relying_party_size);
+    if (!key)
+        return -1;
+
+    assert(EC_PUBLIC_KEY_SIZE + SHA256_DIGEST_LENGTH <= sizeof report_data);
+    int ret = get_raw_public_key(key, public_key);
+    release_key(key);
+    if (ret < 0)
+        return ret;
+
+    if (SHA256_Init(&sha) != 1
+        || SHA256_Update(&sha, public_key, EC_PUBLIC_KEY_SIZE) != 1
+        || (user_data_size > 0 && SHA256_Update(&sha, user_data, user_data_size) != 1)
+        || SHA256_Final(binding, &sha) != 1) {
+        ERROR("Failed to hash user data\n");
+        return -1;
+    }
+
+    return get_report(target_info, &report_data, report);
+}
+
+/* ECALL: get public key derived for relying_party */
+int e_get_public_key(const uint8_t* relying_party, size_t relying_party_size, uint8_t* pubkey,
+                     size_t pubkey_size) {
+    if (pubkey_size < EC_PUBLIC_KEY_SIZE)
+        return -1;
+
+    EVP_PKEY* key = acquire_key(relying_party, relying_party_size);
+    if (!key)
+        return -1;
+
+    int ret = get_raw_public_key(key, pubkey);
+    release_key(key);
+    return ret;
+}
+
+/* Sign data with the given private key */
+static int sign_data(EVP_PKEY* key, const void* data, size_t data_size, void* signature,
+                     size_t signature_size) {
+    int ret = -1;
+    EVP_MD_CTX* md = NULL;
+
     if (signature_size != EC_SIGNATURE_SIZE) {
-        eprintf("Invalid signature size %zu, expected %zu\n", signature_size, EC_SIGNATURE_SIZE);
+        ERROR("Invalid signature size %zu, expected %zu\n", signature_size, EC_SIGNATURE_SIZE);
         goto out;
     }
 
@@ -321,7 +568,7 @@ int e_sign_data(const void* data, size_t data_size, void* signature, size_t sign
     if (!md)
         goto out;
 
-    ret = EVP_DigestSignInit(md, NULL, NULL, NULL, g_private_key);
+    ret = EVP_DigestSignInit(md, NULL, NULL, NULL, key);
     if (ret != 1) {
         ret = -1;
         goto out;
@@ -333,10 +580,158 @@ int e_sign_data(const void* data, size_t data_size, void* signature, size_t sign
         goto out;
     }
 
-    eprintf("Signed %zu bytes of data\n", data_size);
+    DBG("Signed %zu bytes of data\n", data_size);
     ret = 0;
 out:
     if (md)
         EVP_MD_CTX_destroy(md);
     return ret;
 }
+
+/* ECALL: sign data with enclave's private key, or with the key derived for relying_party if it's
+ * not NULL */
+int e_sign_data(const uint8_t* relying_party, size_t relying_party_size, const void* data,
+                size_t data_size, void* signature, size_t signature_size) {
+    EVP_PKEY* key = acquire_key(relying_party, relying_party_size);
+    if (!key)
+        return -1;
+
+    int ret = sign_data(key, data, data_size, signature, signature_size);
+    release_key(key);
+    return ret;
+}
+
+/* Sign SHA-512 digest of a message with the given private key, using Ed25519ph (RFC 8032) with
+ * empty context. Only the digest crosses the enclave boundary, so messages of any size can be
+ * signed. OpenSSL only learned to sign a caller supplied prehash in 3.5, hence ed25519ph_sign. */
+static int sign_prehashed(EVP_PKEY* key, const uint8_t* digest, size_t digest_size,
+                          void* signature, size_t signature_size) {
+    int ret = -1;
+    uint8_t private_key[EC_PRIVATE_KEY_SIZE];
+    uint8_t public_key[EC_PUBLIC_KEY_SIZE];
+    size_t private_key_size = sizeof(private_key);
+
+    if (digest_size != EC_PREHASH_SIZE) {
+        ERROR("Invalid digest size %zu, expected %zu\n", digest_size, EC_PREHASH_SIZE);
+        goto out;
+    }
+
+    if (signature_size != EC_SIGNATURE_SIZE) {
+        ERROR("Invalid signature size %zu, expected %zu\n", signature_size, EC_SIGNATURE_SIZE);
+        goto out;
+    }
+
+    if (EVP_PKEY_get_raw_private_key(key, private_key, &private_key_size) != 1
+            || private_key_size != sizeof(private_key)) {
+        ERROR("Failed to get private key\n");
+        goto out;
+    }
+
+    if (get_raw_public_key(key, public_key) < 0)
+        goto out;
+
+    if (ed25519ph_sign(signature, private_key, public_key, digest) < 0) {
+        ERROR("Failed to sign prehashed data\n");
+        goto out;
+    }
+
+    DBG("Signed prehashed data\n");
+    ret = 0;
+out:
+    zero_memory(private_key, sizeof(private_key));
+    return ret;
+}
+
+/* ECALL: sign SHA-512 digest of data with enclave's private key using Ed25519ph, or with the key
+ * derived for relying_party if it's not NULL */
+int e_sign_prehashed(const uint8_t* relying_party, size_t relying_party_size,
+                     const uint8_t* digest, size_t digest_size, void* signature,
+                     size_t signature_size) {
+    EVP_PKEY* key = acquire_key(relying_party, relying_party_size);
+    if (!key)
+        return -1;
+
+    int ret = sign_prehashed(key, digest, digest_size, signature, signature_size);
+    release_key(key);
+    return ret;
+}
+
+/* ECALL: replace enclave's key pair with a freshly generated one
+ * The new private key is sealed using key_policy and passed out via o_store_sealed_data, and the
+ * new public key is stored in pubkey. The new public key of relying_party (or the new master public
+ * key if relying_party is NULL) is signed with its old private key into signature, proving
+ * continuity between the two keys. The signed message is
+ * KEY_ROTATION_DOMAIN || old public key || new public key.
+ * On failure, the old key pair stays in place. */
+int e_rotate_key(uint16_t key_policy, const uint8_t* relying_party, size_t relying_party_size,
+                 uint8_t* pubkey, size_t pubkey_size, void* signature, size_t signature_size) {
+    int ret = -1;
+    EVP_PKEY* old_private_key = g_private_key;
+    uint8_t old_public_key[EC_PUBLIC_KEY_SIZE];
+    EVP_PKEY* old_key = NULL;
+    EVP_PKEY* new_key = NULL;
+    uint8_t message[sizeof(KEY_ROTATION_DOMAIN) + 2 * EC_PUBLIC_KEY_SIZE];
+    uint8_t* old_signed_key = message + sizeof(KEY_ROTATION_DOMAIN);
+    uint8_t* new_signed_key = old_signed_key + EC_PUBLIC_KEY_SIZE;
+
+    if (!g_initialized)
+        return -1;
+
+    if (pubkey_size < EC_PUBLIC_KEY_SIZE)
+        return -1;
+
+    DBG("Rotating enclave keys...\n");
+    memcpy(old_public_key, g_public_key, sizeof(old_public_key));
+
+    old_key = acquire_key(relying_party, relying_party_size);
+    if (!old_key)
+        return -1;
+
+    memcpy(message, KEY_ROTATION_DOMAIN, sizeof(KEY_ROTATION_DOMAIN));
+    ret = get_raw_public_key(old_key, old_signed_key);
+    if (ret < 0)
+        goto out;
+
+    g_private_key = NULL;
+    ret = generate_private_key();
+    if (ret < 0)
+        goto out;
+
+    ret = -1;
+    new_key = acquire_key(relying_party, relying_party_size);
+    if (!new_key)
+        goto out;
+
+    ret = get_raw_public_key(new_key, new_signed_key);
+    if (ret < 0)
+        goto out;
+
+    ret = sign_data(old_key, message, sizeof(message), signature, signature_size);
+    if (ret < 0)
+        goto out;
+
+    ret = seal_keys(key_policy);
+    if (ret < 0)
+        goto out;
+
+    memcpy(pubkey, g_public_key, EC_PUBLIC_KEY_SIZE);
+    log_public_key();
+    ret = 0;
+
+out:
+    // Keys derived for the relying party, unlike the master ones, are owned here
+    if (relying_party) {
+        EVP_PKEY_free(old_key);
+        EVP_PKEY_free(new_key);
+    }
+    if (ret == 0) {
+        EVP_PKEY_free(old_private_key);
+    } else {
+        // Keep the old key pair
+        if (g_private_key != old_private_key)
+            EVP_PKEY_free(g_private_key);
+        g_private_key = old_private_key;
+        memcpy(g_public_key, old_public_key, sizeof(old_public_key));
+    }
+    return ret;
+}
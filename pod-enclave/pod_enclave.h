@@ -8,23 +8,46 @@
 #include <stdint.h>
 #include <stdlib.h>
 
-/*! Enclave sealing policy:
- *  sealing keys can be derived using MRENCLAVE or MRSIGNER. */
-#define ENCLAVE_SEALING_POLICY SGX_KEYPOLICY_MRENCLAVE
-
 /*! Enclave flags that will matter for sealing/unsealing secrets (keys). */
 #define ENCLAVE_SEALING_ATTRIBUTES (SGX_FLAGS_INITTED | SGX_FLAGS_DEBUG | SGX_FLAGS_MODE64BIT) 
 
 /*! Size of the EC public key (in bytes). */
 #define EC_PUBLIC_KEY_SIZE 32
 
+/*! Size of the raw EC private key (in bytes). */
+#define EC_PRIVATE_KEY_SIZE 32
+
 /*! Size of the EC signature (in bytes). */
 #define EC_SIGNATURE_SIZE 64
 
+/*! Size of the SHA-512 prehash signed with Ed25519ph (in bytes). */
+#define EC_PREHASH_SIZE 64
+
+/*! Domain separator of the message signed when rotating keys (including the terminating NUL). */
+#define KEY_ROTATION_DOMAIN "pod-enclave-key-rotation-v1"
+
+/*! Domain separator of the relying party key derivation (including the terminating NUL). */
+#define RELYING_PARTY_KEY_DOMAIN "pod-enclave-relying-party-key-v1"
+
+/*! Domain separator prepended to the additional authenticated data of application data sealed
+ *  with e_seal_data (including the terminating NUL), so that it can't be mistaken for sealed keys.
+ */
+#define SEALED_DATA_DOMAIN "pod-enclave-sealed-data-v1"
+
+/*! Maximum size of application data and its additional authenticated data together (in bytes). */
+#define MAX_SEALED_DATA_SIZE 65536
+
+/*! Maximum size of a relying party identifier (in bytes). */
+#define MAX_RELYING_PARTY_SIZE 256
+
+/* Levels of messages logged via o_log. Values match POD_LOG_* of libpod_sgx. */
+#define ENCLAVE_LOG_ERROR 1
+#define ENCLAVE_LOG_DEBUG 4
+
 /*! EC curve ID used for digital signatures. */
 #define EC_CURVE_ID EVP_PKEY_ED25519
 
-int seal_keys(void);
+int seal_keys(uint16_t key_policy);
 
 int unseal_keys(const uint8_t* sealed_data, size_t sealed_size);
 
@@ -1,21 +1,36 @@
-const { CHALLENGE, REGISTER, SPID } = constants;
+const { CHALLENGE, ERROR, HELLO, PROTOCOL_VERSION, REGISTER, SPID } = constants;
 var port = browser.runtime.connectNative("pod_app");
 console.log('port', port);
 let waitingResponse = null;
+// Capabilities of pod-app, as reported by the handshake; null until then.
+let podApp = null;
+
+port.postMessage({ msg: HELLO, protocol_version: PROTOCOL_VERSION });
 
 browser.runtime.onMessage.addListener((request, sender, sendResponse) => {
+	if (podApp && !podApp.messages.includes(request.type)) {
+		sendResponse({ type: request.type, response: { error: "unknown_message" } });
+		return;
+	}
 	if (request.type === REGISTER) {
 		console.info(REGISTER);
+		// Register the key derived for the page's origin, which is the one
+		// pod-app signs its challenges with.
 		port.postMessage({
 			msg: REGISTER,
-			spid: SPID
+			spid: SPID,
+			relying_party: new URL(sender.url).origin,
+			user_data: request.data
 		})
 		waitingResponse = curryResponse(sendResponse, REGISTER);
 	} else if (request.type === CHALLENGE) {
 		console.info(CHALLENGE, request.data);
+		// Bind the signature to the origin of the page asking for it, as
+		// reported by the browser, so that it's useless to any other site.
 		port.postMessage({
 			msg: CHALLENGE,
-			challenge: request.data
+			challenge: request.data,
+			origin: new URL(sender.url).origin
 		})
 		waitingResponse = curryResponse(sendResponse, CHALLENGE)
 	}
@@ -23,12 +38,24 @@ browser.runtime.onMessage.addListener((request, sender, sendResponse) => {
 });
 
 port.onMessage.addListener(response => {
+	if (response.msg === HELLO) {
+		console.info(HELLO, response);
+		podApp = response;
+		return;
+	} else if (response.msg === ERROR && response.request === HELLO) {
+		console.error("pod-app handshake failed", response);
+		return;
+	}
 	if (response.msg === REGISTER) {
 		console.info(REGISTER, response);
 		waitingResponse(response.quote)
 	} else if (response.msg === CHALLENGE) {
 		console.info(CHALLENGE, response);
-		waitingResponse(response.signed)
+		waitingResponse({ signed: response.signed, timestamp: response.timestamp })
+	} else if (response.msg === ERROR) {
+		// See pod-app's README for the possible codes.
+		console.warn(ERROR, response);
+		waitingResponse({ error: response.code, description: response.description })
 	}
 	waitingResponse = null
 })
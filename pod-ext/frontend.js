@@ -28,7 +28,8 @@ window.addEventListener(
 );
 
 function handleResponse(source, { type, response }) {
-	source.postMessage({ host: HOST, data: `${response}` }, "*");
+	const data = typeof response === "object" ? response : `${response}`;
+	source.postMessage({ host: HOST, data }, "*");
 }
 
 function handleError(error) {
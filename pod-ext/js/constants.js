@@ -4,5 +4,9 @@ const constants = Object.freeze({
 	HOST: "POD_EXT",
 	REGISTER: "get_quote",
 	CHALLENGE: "sign_challenge",
+	ERROR: "error",
+	HELLO: "hello",
+	// Version of the pod-app native-messaging protocol spoken.
+	PROTOCOL_VERSION: 1,
 	SPID: "2CFD6C88BD1B280D3D6C33977D53A1A3"
 })
\ No newline at end of file
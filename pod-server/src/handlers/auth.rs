@@ -1,4 +1,4 @@
-use super::{AppError, Message};
+use super::{unix_time, AppError, Message};
 use crate::models::User;
 use crate::AppData;
 
@@ -7,9 +7,14 @@ use diesel::prelude::*;
 use actix_identity::Identity;
 use actix_session::Session;
 use actix_web::{web, HttpResponse, Responder};
+use pod_api::{Ed25519PublicKey, Ed25519Signature, SigningPayload, SigningPurpose};
 use serde::Deserialize;
 use tokio_diesel::{AsyncRunQueryDsl, OptionalExtension};
 
+/// How far the time at which the challenge was signed may be off from ours,
+/// in seconds.
+const MAX_TIMESTAMP_SKEW: u64 = 300;
+
 pub async fn get(session: Session, identity: Identity) -> impl Responder {
     if let Some(id) = identity.identity() {
         log::info!("User '{}' already authenticated", id);
@@ -48,6 +53,7 @@ pub async fn get(session: Session, identity: Identity) -> impl Responder {
 pub struct AuthChallengeResponse {
     login: String,
     response: String, // base64 encoded
+    timestamp: u64,   // seconds since the UNIX epoch
 }
 
 pub async fn post(
@@ -87,10 +93,27 @@ pub async fn post(
         None => return Err(AppError::InvalidChallenge),
     };
 
-    let pub_key_ = ed25519_dalek::PublicKey::from_bytes(&base64::decode(&record.pub_key)?)?;
-    let enc_blob = base64::decode(&response.response)?;
-    let signature = ed25519_dalek::Signature::from_bytes(&enc_blob)?;
-    pub_key_.verify(&blob, &signature)?;
+    let now = unix_time();
+    let skew = now.max(response.timestamp) - now.min(response.timestamp);
+    if skew > MAX_TIMESTAMP_SKEW {
+        log::info!(
+            "Challenge response from user '{}' signed {}s off.",
+            response.login,
+            skew
+        );
+        return Err(AppError::StaleSignature);
+    }
+
+    // The challenge is only accepted if signed for our origin.
+    let payload = SigningPayload::new(
+        app_data.origin.clone(),
+        SigningPurpose::Authentication,
+        blob,
+        response.timestamp,
+    );
+    let pub_key_ = Ed25519PublicKey::from_base64(&record.pub_key)?;
+    let signature = Ed25519Signature::from_base64(&response.response)?;
+    payload.verify(&pub_key_, &signature)?;
 
     identity.remember(response.login.clone());
     session.purge();
@@ -1,12 +1,28 @@
 pub mod auth;
 pub mod register;
+pub mod rotate;
+
+use crate::AppData;
 
 use actix_identity::Identity;
+use actix_session::Session;
 use actix_web::dev::HttpResponseBuilder;
+use actix_web::error::BlockingError;
 use actix_web::http::StatusCode;
-use actix_web::{HttpResponse, Responder, ResponseError};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
+use pod_api::{Ed25519PublicKey, EnclaveReport, SoftwareBackend, TpmBackend};
+use rust_sgx_util::{IasHandle, Quote};
 use serde::Serialize;
 use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long a nonce issued for a quote stays valid, in seconds.
+const NONCE_LIFETIME: u64 = 300;
+/// Maximum number of nonces issued but not used yet, so that clients can't
+/// exhaust the server's memory by requesting nonces.
+const MAX_PENDING_NONCES: usize = 100_000;
 
 pub async fn index(identity: Identity) -> impl Responder {
     match identity.identity() {
@@ -15,6 +31,140 @@ pub async fn index(identity: Identity) -> impl Responder {
     }
 }
 
+/// Nonces issued for quotes and not used yet, along with the time they were
+/// issued at.
+///
+/// The session lives in the client's cookie, which the client can replay, so
+/// whether a nonce was used already has to be tracked by the server.
+#[derive(Default)]
+pub struct Nonces(Mutex<HashMap<String, u64>>);
+
+impl Nonces {
+    fn insert(&self, nonce: String) -> Result<(), AppError> {
+        let mut nonces = self.0.lock().map_err(|_| AppError::NoncesPoisoned)?;
+        let now = unix_time();
+        nonces.retain(|_, issued| now.saturating_sub(*issued) < NONCE_LIFETIME);
+        if nonces.len() >= MAX_PENDING_NONCES {
+            return Err(AppError::TooManyNonces);
+        }
+        nonces.insert(nonce, now);
+        Ok(())
+    }
+
+    /// Removes `nonce`, and returns whether it was issued and hasn't expired.
+    fn remove(&self, nonce: &str) -> Result<bool, AppError> {
+        let mut nonces = self.0.lock().map_err(|_| AppError::NoncesPoisoned)?;
+        Ok(match nonces.remove(nonce) {
+            Some(issued) => unix_time().saturating_sub(issued) < NONCE_LIFETIME,
+            None => false,
+        })
+    }
+}
+
+/// Generates a fresh nonce for the client to bind its next quote to, and
+/// stores it in the session, as well as in the server's pending nonces.
+fn issue_nonce(session: &Session, app_data: &AppData) -> Result<String, AppError> {
+    let mut blob = [0u8; 32];
+    getrandom::getrandom(&mut blob)?;
+    let nonce = base64::encode(&blob[..]);
+    log::debug!("Generated nonce: {}", nonce);
+
+    session
+        .set("nonce", nonce.clone())
+        .map_err(|_| AppError::InvalidCookie)?;
+    app_data.nonces.insert(nonce.clone())?;
+    Ok(nonce)
+}
+
+/// Removes the nonce from the session and the server's pending nonces, so
+/// that it can be used only once, even if the client replays an earlier
+/// cookie, and returns it if it hasn't expired yet.
+fn take_nonce(session: &Session, app_data: &AppData) -> Result<Vec<u8>, AppError> {
+    let nonce = session
+        .get::<String>("nonce")
+        .map_err(|_| AppError::InvalidCookie)?;
+    session.remove("nonce");
+    match nonce {
+        Some(nonce) if app_data.nonces.remove(&nonce)? => Ok(base64::decode(nonce)?),
+        _ => Err(AppError::InvalidNonce),
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// Verifies `quote` submitted by user `login`, either with IAS or, if allowed,
+/// as a software or TPM quote, and extracts the enclave's public key from it.
+/// The quote has to be bound to `nonce`, so that it can't be replayed, and come
+/// from an enclave running in production mode, unless debug enclaves are
+/// allowed.
+async fn verify_quote(
+    login: &str,
+    quote: &Quote,
+    nonce: &[u8],
+    app_data: &AppData,
+) -> Result<Ed25519PublicKey, AppError> {
+    if SoftwareBackend::is_software_quote(quote) {
+        if !app_data.allow_software_quotes {
+            log::info!("Rejecting software quote from user '{}'.", login);
+            return Err(AppError::SoftwareQuoteRejected);
+        }
+        // Software quotes are self-signed, so there's nothing to verify with IAS.
+        SoftwareBackend::verify_quote(quote)
+            .map_err(|err| AppError::InvalidSoftwareQuote(err.to_string()))?;
+    } else if TpmBackend::is_tpm_quote(quote) {
+        if !app_data.allow_tpm_quotes {
+            log::info!("Rejecting TPM quote from user '{}'.", login);
+            return Err(AppError::TpmQuoteRejected);
+        }
+        let attestation = TpmBackend::verify_quote(quote)
+            .map_err(|err| AppError::InvalidTpmQuote(err.to_string()))?;
+        let attestation_key = attestation.attestation_key();
+        if !app_data.trusted_tpm_keys.contains(attestation_key) {
+            log::info!(
+                "Rejecting TPM quote signed by untrusted attestation key {} from user '{}'.",
+                attestation_key,
+                login
+            );
+            return Err(AppError::UntrustedTpm);
+        }
+    } else {
+        // IAS happily verifies quotes of debug enclaves, so check the
+        // attributes ourselves.
+        let report = EnclaveReport::from_report_body(quote.report_body()?)?;
+        if report.is_debug() && !app_data.allow_debug_enclaves {
+            log::info!("Rejecting quote of a debug enclave from user '{}'.", login);
+            return Err(AppError::DebugEnclaveRejected);
+        }
+        let quote = quote.clone();
+        if let Err(err) = web::block(move || {
+            // Verify the provided data with IAS.
+            let api_key = env::var("POD_SERVER_API_KEY")?;
+            let handle = IasHandle::new(&api_key, None, None)?;
+            handle.verify_quote(&quote, None, None, None, None, None)?;
+            Ok(())
+        })
+        .await
+        {
+            match err {
+                BlockingError::Error(err) => return Err(err),
+                BlockingError::Canceled => return Err(AppError::ActixBlockingCanceled),
+            }
+        };
+    }
+
+    // Extract pub_key from Quote, checking it's bound to the nonce
+    let report_data = quote.report_data()?;
+    Ed25519PublicKey::from_report_data(report_data, nonce).map_err(|_| {
+        log::info!("Quote from user '{}' not bound to the issued nonce.", login);
+        AppError::QuoteNotBound
+    })
+}
+
 #[derive(Serialize)]
 enum Status {
     Ok,
@@ -78,10 +228,36 @@ enum AppError {
     ActixBlockingCanceled,
     #[error("decoding base64 to blob: {}", _0)]
     Base64Decode(#[from] base64::DecodeError),
-    #[error("parsing ed25519 signature: {}", _0)]
-    Ed25519Signature(#[from] ed25519_dalek::SignatureError),
+    #[error("pod-api error: {}", _0)]
+    PodApi(#[from] pod_api::PodError),
     #[error("fetching entropy: {}", _0)]
     GetRandom(#[from] getrandom::Error),
+    #[error("software quotes are not accepted")]
+    SoftwareQuoteRejected,
+    #[error("invalid software quote: {}", _0)]
+    InvalidSoftwareQuote(String),
+    #[error("nonce missing or expired")]
+    InvalidNonce,
+    #[error("quote not bound to the issued nonce")]
+    QuoteNotBound,
+    #[error("key rotation doesn't match the registered key")]
+    RotationKeyMismatch,
+    #[error("quote doesn't attest the rotated key")]
+    RotationQuoteMismatch,
+    #[error("challenge signed too long ago or in the future")]
+    StaleSignature,
+    #[error("quotes of debug enclaves are not accepted")]
+    DebugEnclaveRejected,
+    #[error("TPM quotes are not accepted")]
+    TpmQuoteRejected,
+    #[error("invalid TPM quote: {}", _0)]
+    InvalidTpmQuote(String),
+    #[error("TPM quote signed by an untrusted attestation key")]
+    UntrustedTpm,
+    #[error("too many pending nonces")]
+    TooManyNonces,
+    #[error("pending nonces poisoned by a panic")]
+    NoncesPoisoned,
 }
 
 impl ResponseError for AppError {
@@ -102,8 +278,21 @@ impl ResponseError for AppError {
                 (StatusCode::INTERNAL_SERVER_ERROR, Message::error())
             }
             AppError::Base64Decode(_) => (StatusCode::INTERNAL_SERVER_ERROR, Message::error()),
-            AppError::Ed25519Signature(_) => (StatusCode::OK, Message::error()),
+            AppError::PodApi(_) => (StatusCode::OK, Message::error()),
             AppError::GetRandom(_) => (StatusCode::INTERNAL_SERVER_ERROR, Message::error()),
+            AppError::SoftwareQuoteRejected => (StatusCode::OK, Message::error()),
+            AppError::InvalidSoftwareQuote(_) => (StatusCode::OK, Message::error()),
+            AppError::InvalidNonce => (StatusCode::OK, Message::error()),
+            AppError::QuoteNotBound => (StatusCode::OK, Message::error()),
+            AppError::RotationKeyMismatch => (StatusCode::FORBIDDEN, Message::error()),
+            AppError::RotationQuoteMismatch => (StatusCode::OK, Message::error()),
+            AppError::StaleSignature => (StatusCode::OK, Message::error()),
+            AppError::DebugEnclaveRejected => (StatusCode::OK, Message::error()),
+            AppError::TpmQuoteRejected => (StatusCode::OK, Message::error()),
+            AppError::InvalidTpmQuote(_) => (StatusCode::OK, Message::error()),
+            AppError::UntrustedTpm => (StatusCode::OK, Message::error()),
+            AppError::TooManyNonces => (StatusCode::SERVICE_UNAVAILABLE, Message::error()),
+            AppError::NoncesPoisoned => (StatusCode::INTERNAL_SERVER_ERROR, Message::error()),
         };
         HttpResponseBuilder::new(code).json(message.add_param("description", format!("{}", self)))
     }
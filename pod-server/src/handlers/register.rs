@@ -1,23 +1,39 @@
-use super::{AppError, Message};
+use super::{issue_nonce, take_nonce, verify_quote, AppError, Message};
 use crate::models::{NewUser, User};
 use crate::AppData;
 
 use diesel::prelude::*;
 
-use actix_web::error::BlockingError;
+use actix_session::Session;
 use actix_web::{web, HttpResponse, Responder};
-use rust_sgx_util::{IasHandle, Quote};
+use rust_sgx_util::Quote;
 use serde::Deserialize;
-use std::env;
 use tokio_diesel::{AsyncRunQueryDsl, OptionalExtension};
 
+pub async fn get(session: Session, app_data: web::Data<AppData>) -> impl Responder {
+    log::info!("Received register nonce request.");
+
+    let nonce = issue_nonce(&session, &app_data)?;
+    Ok::<_, AppError>(
+        HttpResponse::Ok().json(
+            Message::ok()
+                .add_param("description", "nonce successfully generated")
+                .add_param("nonce", nonce),
+        ),
+    )
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RegisterInfo {
     login: String,
     quote: Quote,
 }
 
-pub async fn post(info: web::Json<RegisterInfo>, app_data: web::Data<AppData>) -> impl Responder {
+pub async fn post(
+    info: web::Json<RegisterInfo>,
+    app_data: web::Data<AppData>,
+    session: Session,
+) -> impl Responder {
     use crate::schema::users::dsl::*;
 
     log::info!(
@@ -39,26 +55,9 @@ pub async fn post(info: web::Json<RegisterInfo>, app_data: web::Data<AppData>) -
         return Err(AppError::AlreadyRegistered);
     }
 
-    let quote = info.quote.clone();
-    if let Err(err) = web::block(move || {
-        // Verify the provided data with IAS.
-        let api_key = env::var("POD_SERVER_API_KEY")?;
-        let handle = IasHandle::new(&api_key, None, None)?;
-        handle.verify_quote(&quote, None, None, None, None, None)?;
-        Ok(())
-    })
-    .await
-    {
-        match err {
-            BlockingError::Error(err) => return Err(err),
-            BlockingError::Canceled => return Err(AppError::ActixBlockingCanceled),
-        }
-    };
-
-    // Extract pub_key from Quote
-    // ED25519 public key is 32 bytes long
-    let report_data = info.quote.report_data()?;
-    let pub_key_ = base64::encode(&report_data[..32]);
+    let nonce = take_nonce(&session, &app_data)?;
+    let pub_key_ = verify_quote(&info.login, &info.quote, &nonce, &app_data).await?;
+    let pub_key_ = pub_key_.to_base64();
     log::debug!("Extracted public key (base64 encoded): {}", pub_key_);
 
     // Insert user to the database.
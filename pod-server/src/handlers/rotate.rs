@@ -0,0 +1,86 @@
+use super::{issue_nonce, take_nonce, verify_quote, AppError, Message};
+use crate::models::User;
+use crate::AppData;
+
+use diesel::prelude::*;
+
+use actix_session::Session;
+use actix_web::{web, HttpResponse, Responder};
+use pod_api::{Ed25519PublicKey, KeyRotation};
+use rust_sgx_util::Quote;
+use serde::Deserialize;
+use tokio_diesel::{AsyncRunQueryDsl, OptionalExtension};
+
+pub async fn get(session: Session, app_data: web::Data<AppData>) -> impl Responder {
+    log::info!("Received key rotation nonce request.");
+
+    let nonce = issue_nonce(&session, &app_data)?;
+    Ok::<_, AppError>(
+        HttpResponse::Ok().json(
+            Message::ok()
+                .add_param("description", "nonce successfully generated")
+                .add_param("nonce", nonce),
+        ),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateInfo {
+    login: String,
+    rotation: String, // base64 encoded
+    quote: Quote,
+}
+
+pub async fn post(
+    info: web::Json<RotateInfo>,
+    app_data: web::Data<AppData>,
+    session: Session,
+) -> impl Responder {
+    use crate::schema::users::dsl::*;
+
+    log::info!(
+        "Received key rotation request for user with login '{}'.",
+        info.login
+    );
+    log::debug!("Received data: {:?}", info);
+
+    // Fetch user's record and extract their pub_key.
+    let record = users
+        .filter(login.eq(info.login.clone()))
+        .get_result_async::<User>(&app_data.pool)
+        .await
+        .optional()?;
+    let record = match record {
+        Some(record) => record,
+        None => return Err(AppError::NotRegistered),
+    };
+
+    // The rotation has to be signed with the currently registered key...
+    let rotation = KeyRotation::from_base64(&info.rotation)?;
+    if rotation.old_public_key() != &Ed25519PublicKey::from_base64(&record.pub_key)? {
+        log::info!(
+            "Key rotation of user '{}' signed with unknown key.",
+            info.login
+        );
+        return Err(AppError::RotationKeyMismatch);
+    }
+    rotation.verify()?;
+
+    // ...and the new key has to be attested by the quote.
+    let nonce = take_nonce(&session, &app_data)?;
+    let new_pub_key = verify_quote(&info.login, &info.quote, &nonce, &app_data).await?;
+    if rotation.new_public_key() != &new_pub_key {
+        log::info!("Quote of user '{}' doesn't attest the new key.", info.login);
+        return Err(AppError::RotationQuoteMismatch);
+    }
+    let new_pub_key = new_pub_key.to_base64();
+    log::debug!("New public key (base64 encoded): {}", new_pub_key);
+
+    diesel::update(users.filter(id.eq(record.id)))
+        .set(pub_key.eq(new_pub_key))
+        .execute_async(&app_data.pool)
+        .await?;
+
+    log::info!("User '{}' successfully rotated their key.", info.login);
+    Ok(HttpResponse::Ok().json(Message::ok().add_param("description", "key rotation successful")))
+}
@@ -12,6 +12,7 @@ use anyhow::anyhow;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::SqliteConnection;
 use dotenv::dotenv;
+use pod_api::{Measurement, RelyingParty};
 use serde::Deserialize;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -33,10 +34,33 @@ struct Opt {
 struct ServerConfig {
     api_key: String,
     cookie_key: String,
+    /// Accept self-signed quotes generated by `pod_api::SoftwareBackend`.
+    /// NEVER enable this in production.
+    #[serde(default)]
+    allow_software_quotes: bool,
+    /// Accept quotes of enclaves running in debug mode, whose memory isn't
+    /// protected. Meant for development only.
+    #[serde(default)]
+    allow_debug_enclaves: bool,
+    /// Accept quotes generated by `pod_api::TpmBackend`.
+    #[serde(default)]
+    allow_tpm_quotes: bool,
+    /// Attestation keys of the TPMs whose quotes are accepted, see
+    /// `pod_api::TpmAttestation::attestation_key`. Required with
+    /// `allow_tpm_quotes`.
+    #[serde(default)]
+    trusted_tpm_keys: Vec<Measurement>,
+    /// Origin users sign challenges for, i.e., that of the web frontend.
+    #[serde(default = "default_origin")]
+    origin: String,
     #[serde(rename = "server")]
     bind: Option<BindAddress>,
 }
 
+fn default_origin() -> String {
+    "http://localhost:3000".to_string()
+}
+
 #[derive(Deserialize)]
 struct BindAddress {
     address: String,
@@ -45,6 +69,12 @@ struct BindAddress {
 
 pub struct AppData {
     pool: Pool<ConnectionManager<SqliteConnection>>,
+    allow_software_quotes: bool,
+    allow_debug_enclaves: bool,
+    allow_tpm_quotes: bool,
+    trusted_tpm_keys: Vec<Measurement>,
+    origin: RelyingParty,
+    nonces: handlers::Nonces,
 }
 
 #[actix_rt::main]
@@ -75,7 +105,29 @@ async fn main() -> anyhow::Result<()> {
     let db_url = env::var("DATABASE_URL")?;
     let manager = ConnectionManager::<SqliteConnection>::new(db_url);
     let pool = Pool::builder().build(manager)?;
-    let data = web::Data::new(AppData { pool });
+    if config.allow_software_quotes {
+        log::warn!("Accepting software quotes! Do NOT use this setting in production.");
+    }
+    if config.allow_debug_enclaves {
+        log::warn!("Accepting quotes of debug enclaves! Do NOT use this setting in production.");
+    }
+    // The endorsement key chain isn't checked, so without pinned keys any
+    // attestation key, genuine TPM or not, would do.
+    if config.allow_tpm_quotes && config.trusted_tpm_keys.is_empty() {
+        return Err(anyhow!(
+            "allow_tpm_quotes requires the attestation keys to trust in trusted_tpm_keys"
+        ));
+    }
+    let origin: RelyingParty = config.origin.parse()?;
+    let data = web::Data::new(AppData {
+        pool,
+        allow_software_quotes: config.allow_software_quotes,
+        allow_debug_enclaves: config.allow_debug_enclaves,
+        allow_tpm_quotes: config.allow_tpm_quotes,
+        trusted_tpm_keys: config.trusted_tpm_keys,
+        origin,
+        nonces: handlers::Nonces::default(),
+    });
     // Cookie config
     let cookie_key = config.cookie_key.clone();
 
@@ -95,7 +147,16 @@ async fn main() -> anyhow::Result<()> {
             .wrap(middleware::Logger::default())
             .app_data(data.clone())
             .route("/", web::get().to(handlers::index))
-            .route("/register", web::post().to(handlers::register::post))
+            .service(
+                web::resource("/register")
+                    .route(web::get().to(handlers::register::get))
+                    .route(web::post().to(handlers::register::post)),
+            )
+            .service(
+                web::resource("/rotate")
+                    .route(web::get().to(handlers::rotate::get))
+                    .route(web::post().to(handlers::rotate::post)),
+            )
             .service(
                 web::resource("/auth")
                     .route(web::get().to(handlers::auth::get))
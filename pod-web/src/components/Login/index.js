@@ -37,6 +37,11 @@ const Login = () => {
 	const messageListener = ({ data, origin }) => {
 		if (origin !== ORIGIN || data.host !== REMOTE) return;
 		console.log(data); // Answer
+		// pod-app couldn't handle the request; see its README for the codes.
+		if (data.data && data.data.error) {
+			alert(`Proof of device error: ${data.data.error}`);
+			return;
+		}
 		setSigned(data.data);
 	};
 
@@ -46,7 +51,7 @@ const Login = () => {
 		event.preventDefault();
 		const login = getById("usernameInput").value;
 		//const password = getById("passwordInput").value;
-		authRequest({ login, signed_challenge: signed })
+		authRequest({ login, ...signed })
 			.then((result) => {
 				console.log(result);
 				if (result.status === "Ok") {
@@ -110,13 +115,14 @@ function challengeRequest() {
 	});
 }
 
-function authRequest({ login, signed_challenge }) {
+function authRequest({ login, signed, timestamp }) {
 	return request("/auth", {
 		method: "post",
 		headers: { "Content-Type": "application/json" },
 		body: JSON.stringify({
 			login,
-			response: signed_challenge,
+			response: signed,
+			timestamp,
 		}),
 	});
 }
@@ -12,16 +12,32 @@ const Register = () => {
 	const [ errorStatus, setError ] = useState(false);
 	const [ quote, setQuote ] = useState(null);
 	useEffect(() => {
-		window.postMessage(
-			{ host: HOST, type: REGISTER },
-			ORIGIN
-		);
+		nonceRequest()
+			.then(({ nonce }) => {
+				if (!nonce)
+					throw new Error(
+						"Nonce cannot be empty.\nHint: Try removing cookies."
+					);
+				window.postMessage(
+					{ host: HOST, type: REGISTER, data: nonce },
+					ORIGIN
+				);
+			})
+			.catch((error) => {
+				console.warn(error);
+				alert(`Network Error: ${error.status}`);
+			});
 	}, []);
 
 	const messageListener = ({data, origin}) => {
 		if (origin !== ORIGIN || data.host !== REMOTE)
 			return;
 		console.log(data); // Answer here
+		// pod-app couldn't handle the request; see its README for the codes.
+		if (data.data && data.data.error) {
+			alert(`Proof of device error: ${data.data.error}`);
+			return;
+		}
 		setQuote(data.data)
 	};
 
@@ -86,6 +102,12 @@ const Register = () => {
 
 export default Register;
 
+function nonceRequest() {
+	return request('/register', {
+		method: 'get',
+	})
+}
+
 function registerRequest({ login, quote }) {
 	return request('/register', {
 		method: 'post',